@@ -0,0 +1,55 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+fn main() {
+    // Plugins are refused unless they were built by the same compiler, see core::plugin
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        .unwrap_or_default();
+
+    println!("cargo:rustc-env=SUNSET_RUSTC_VERSION={version}");
+    println!("cargo:rerun-if-env-changed=RUSTC");
+
+    // ... and against the same engine sources, a rebuilt engine can change the layout of what they share
+    // without its version changing
+    let mut hasher = DefaultHasher::new();
+    for path in source_files(Path::new("src")) {
+        path.hash(&mut hasher);
+        std::fs::read(&path).unwrap_or_default().hash(&mut hasher);
+    }
+
+    println!("cargo:rustc-env=SUNSET_CORE_HASH={:016x}", hasher.finish());
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+}
+
+/// Every file under `folder`, sorted so the hash does not depend on the order they are listed in.
+fn source_files(folder: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let mut folders = vec![folder.to_path_buf()];
+
+    while let Some(folder) = folders.pop() {
+        let Ok(entries) = std::fs::read_dir(&folder) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                folders.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
@@ -1,406 +1,293 @@
-use tracing::{debug, error, info};
+use std::time::Duration;
+
+use tracing::{debug, error, warn};
 
 use crate::{
-    assets::{Asset, AssetType},
+    assets::{
+        protocol::{RequestKind, Response, Status},
+        Asset, AssetType,
+    },
     core::{
-        command_queue::{Command, CommandType, IntoCommand, Task},
+        command_queue::{AsyncTask, Command, CommandType, IntoCommand},
+        command_registry::{join_args, split_args},
+        command_result::{CommandFailure, CommandResult},
+        config::EngineConfig,
         events::CommandEvent,
     },
 };
 
 use super::AssetStatus;
 
+/// How long an asset request may take before its task is cancelled.
+pub const ASSET_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[allow(dead_code)]
 pub struct AssetCommand {
     pub processed: bool,
     pub command_type: CommandType,
     pub args: String,
-    pub task: Option<Task<Vec<CommandEvent>>>,
+    pub future: Option<AsyncTask<CommandResult>>,
 }
 
 impl AssetCommand {
-    pub fn new(
-        command_type: CommandType,
-        args: String,
-        elp: winit::event_loop::EventLoopProxy<CommandEvent>,
-    ) -> Self {
-        let task = match command_type {
+    /// # Arguments
+    ///
+    /// * `args` - `-from_server <address> <path> <type>` or `-local <path> <type>`, read under `config.asset_root`
+    pub fn new(command_type: CommandType, args: String, config: &EngineConfig) -> Self {
+        let future = match command_type {
             CommandType::Get => {
-                let args: Vec<&str> = args.split(' ').collect();
-                match args[0] {
-                    "-h" => AssetCommand::display_help(),
-                    "-from_server" => AssetCommand::get_from_server(args[1..].join(" "), elp),
-                    "-local" => AssetCommand::get_local(args[1..].join(" ")),
-                    _ => AssetCommand::display_help(),
+                let tokens = split_args(&args);
+                let rest = join_args(tokens.get(1..).unwrap_or_default());
+                match tokens.first().map(String::as_str) {
+                    Some("-from_server") => {
+                        Some(AssetCommand::get_from_server(rest, Default::default()))
+                    }
+                    Some("-local") => {
+                        Some(AssetCommand::get_local(rest, config.asset_root.clone()))
+                    }
+                    _ => {
+                        error!("Unsupported asset command arguments: {args}");
+                        None
+                    }
                 }
             }
             _ => None,
         };
 
         Self {
-            processed: task.is_some(),
+            processed: future.is_some(),
             command_type,
             args,
-            task,
+            future,
         }
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
+    /// Requests an asset (`<address> <path> <type>`, see `split_args`) from the asset server,
+    /// reusing `connections` (Ex: over a WebSocket on the web).
     pub fn get_from_server(
         args: String,
-        _elp: winit::event_loop::EventLoopProxy<CommandEvent>,
-    ) -> Option<Task<Vec<CommandEvent>>> {
-        use std::{
-            io::{Read, Write},
-            net::TcpStream,
+        connections: super::protocol::AssetConnections,
+    ) -> AsyncTask<CommandResult> {
+        // 127.0.0.1 "shaders/my shader.wgsl" shader
+        let (address, path, type_name) = match split_args(&args).as_slice() {
+            [address, path, type_name] => {
+                (address.to_owned(), path.to_owned(), type_name.to_owned())
+            }
+            _ => return invalid_args(format!("expected <address> <path> <type>, got {args:?}")),
         };
 
-        use tracing::warn;
-
-        // 127.0.0.1 shader.wgsl shader
-        let cmd = move || {
-            let mut events = vec![];
-            let args: Vec<&str> = args.split(' ').collect();
-
-            if args.contains(&"changed") {
-                match TcpStream::connect(args[0]) {
-                    Ok(mut stream) => {
-                        debug!("Successfully connected to server {}", args[0]);
-
-                        let request = args[1..].join(" ");
-
-                        stream
-                            .write_all(&request.len().to_ne_bytes().to_vec())
-                            .unwrap_or_else(|err| {
-                                error!("Could not write data: {err}");
-                                ()
-                            });
-
-                        info!("Request size: {}", request.len());
-
-                        stream.write_all(request.as_bytes()).unwrap();
-
-                        let mut data = Vec::new();
+        let asset_type = AssetType::from_name(&type_name);
+        if asset_type == AssetType::Unknown {
+            warn!("Unkown asset type requested: {type_name}");
+        }
 
-                        stream.read_to_end(&mut data).unwrap_or(1);
+        request_from_server(address, RequestKind::Get, path, asset_type, connections)
+    }
 
-                        let try_convert_string = std::str::from_utf8(&data);
+    /// Asks the asset server at `address` which assets changed since the previous check made with `connections`,
+    /// received as a `CommandEvent::ChangedAssets`.
+    pub fn changed_from_server(
+        address: String,
+        connections: super::protocol::AssetConnections,
+    ) -> AsyncTask<CommandResult> {
+        request_from_server(
+            address,
+            RequestKind::Changed,
+            String::new(),
+            AssetType::String,
+            connections,
+        )
+    }
 
-                        if let Ok(res) = try_convert_string {
-                            let paths: Vec<String> = res
-                                .split(' ')
-                                .into_iter()
-                                .map(|path| path.to_string())
-                                .collect();
-                            events.push(CommandEvent::ChangedAssets(paths));
-                        }
-                        events
-                    }
-                    Err(e) => {
-                        println!("Failed to connect: {}", e);
-                        events
-                    }
-                }
-            } else {
-                debug!(
-                    "Get Asset {} of type {} from server {}",
-                    args[1], args[2], args[0]
-                );
-
-                let asset_type = args[2].to_ascii_lowercase().to_owned();
-                let asset_path = args[1].to_owned();
-                let asset_name = args[1].split('/').last().unwrap().to_owned();
-
-                debug!("path: {}, name: {}", asset_path, asset_name);
-
-                match TcpStream::connect(args[0]) {
-                    Ok(mut stream) => {
-                        debug!("Successfully connected to server {}", args[0]);
-
-                        let request = format!("get {} {}", asset_path, asset_type);
-
-                        stream
-                            .write_all(&request.len().to_ne_bytes().to_vec())
-                            .unwrap_or_else(|err| {
-                                error!("Could not write data: {err}");
-                                ()
-                            });
-
-                        info!("Request size: {}", request.len());
-
-                        stream.write_all(request.as_bytes()).unwrap_or_else(|err| {
-                            error!("Could not write data: {err}");
-                            ()
-                        });
-
-                        let mut data = Vec::new();
-
-                        stream.read_to_end(&mut data).unwrap_or_else(|err| {
-                            error!("Could not read data: {err}");
-                            0
-                        });
-
-                        let try_convert_string = std::str::from_utf8(&data);
-
-                        if let Ok(res) = try_convert_string {
-                            if res.contains("File not found") {
-                                error!("File not found: {}", asset_path);
-                                return vec![];
-                            }
-                        }
-
-                        let asset_type = match asset_type.as_str() {
-                            "shader" => AssetType::Shader,
-                            "string" => AssetType::String,
-                            "texture" => AssetType::Texture,
-                            "font" => AssetType::Font,
-                            "model" => AssetType::Model,
-                            "mesh" => AssetType::Mesh,
-                            "material" => AssetType::Material,
-                            _ => {
-                                warn!("Unkown asset type requested: {asset_type:?}");
-                                AssetType::Unknown
-                            }
-                        };
-
-                        vec![CommandEvent::Asset(Asset {
-                            asset_type,
-                            status: AssetStatus::Ready,
-                            data,
-                            name: asset_name,
-                            path: asset_path,
-                        })]
-                    }
-                    Err(e) => {
-                        println!("Failed to connect: {}", e);
-                        vec![]
-                    }
-                }
-            }
+    /// Reads the asset (`<path> <type>`, see `split_args`) from the files under `root`.
+    pub fn get_local(args: String, root: String) -> AsyncTask<CommandResult> {
+        let (asset_path, asset_type) = match split_args(&args).as_slice() {
+            [path, asset_type] => (path.to_owned(), asset_type.to_ascii_lowercase()),
+            _ => return invalid_args(format!("expected <path> <type>, got {args:?}")),
         };
 
-        Some(Box::new(cmd))
-    }
+        let task = async move {
+            let asset_name = asset_path.rsplit('/').next().unwrap_or_default().to_owned();
+            let asset_type = AssetType::from_name(&asset_type);
+
+            let full_path = std::path::Path::new(&root).join(&asset_path);
 
-    pub fn get_local(args: String) -> Option<Task<Vec<CommandEvent>>> {
-        let cmd = move || {
-            let args: Vec<&str> = args.split(' ').collect();
-            let asset_type = args[1].to_ascii_lowercase().to_owned();
-            let asset_path = args[0].to_owned();
-            let asset_name = args[0].split('/').last().unwrap().to_owned();
-
-            let asset_type = match asset_type.as_str() {
-                "shader" => AssetType::Shader,
-                "string" => AssetType::String,
-                "texture" => AssetType::Texture,
-                "font" => AssetType::Font,
-                "model" => AssetType::Model,
-                "mesh" => AssetType::Mesh,
-                "material" => AssetType::Material,
-                _ => AssetType::Unknown,
-            };
-
-            //let asset_folder_path = std::env::var("ASSETS_PATH").unwrap();
-            let asset_folder_path = "assets/";
-            let full_path = format!("{asset_folder_path}{asset_path}");
+            #[cfg(not(target_arch = "wasm32"))]
+            let data = async_std::fs::read(full_path).await;
 
+            #[cfg(target_arch = "wasm32")]
             let data = std::fs::read(full_path);
 
-            if data.is_ok() {
-                return vec![CommandEvent::Asset(Asset {
+            if let Ok(data) = data {
+                return Ok(vec![CommandEvent::Asset(Asset {
                     asset_type,
                     status: AssetStatus::Ready,
-                    data: data.unwrap(),
+                    data,
                     name: asset_name,
                     path: asset_path,
-                })];
+                })]
+                .into());
             }
 
-            vec![CommandEvent::Asset(Asset {
+            Ok(vec![CommandEvent::Asset(Asset {
                 asset_type,
                 status: AssetStatus::NotFound,
                 data: vec![],
                 name: asset_name,
                 path: asset_path,
             })]
+            .into())
         };
 
-        Some(Box::new(cmd))
+        Box::pin(task)
     }
+}
 
-    #[cfg(target_arch = "wasm32")]
-    pub fn get_from_server(
-        args: String,
-        elp: winit::event_loop::EventLoopProxy<CommandEvent>,
-    ) -> Option<Task<Vec<CommandEvent>>> {
-        let cmd = move || {
-            use wasm_bindgen::prelude::*;
-            // Needs a clone here so the function can become FnMut (otherwise elp will be moved out of the closure by the forgotten server closure below)
-            let elp_1 = elp.clone();
-            let elp_2 = elp.clone();
-
-            let args: Vec<&str> = args.split(' ').collect();
-            let addr = args[0];
-
-            if args.contains(&"changed") {
-                let url = format!("{}{}", "wss://", addr);
-
-                let ws = web_sys::WebSocket::new(url.as_str()).unwrap();
-                let cloned_ws = ws.clone();
-
-                let onopen_callback = Closure::<dyn FnMut()>::new(move || {
-                    //debug!("socket opened");
-                    match cloned_ws.send_with_str("get changed") {
-                        Ok(_) => {
-                            //debug!("message successfully sent");
-                        }
-                        Err(err) => error!("error sending message: {:?}", err),
-                    }
-                });
-                ws.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
-                onopen_callback.forget();
-
-                let onmessage_callback =
-                    Closure::<dyn FnMut(_)>::new(move |e: web_sys::MessageEvent| {
-                        if let Ok(blob) = e.data().dyn_into::<web_sys::Blob>() {
-                            debug!("message event, received blob: {:?}", blob);
-                        } else if let Ok(txt) = e.data().dyn_into::<js_sys::JsString>() {
-                            let res: String = txt.into();
-                            let paths: Vec<String> = res
-                                .split(' ')
-                                .into_iter()
-                                .map(|path| path.to_string())
-                                .collect();
-
-                            elp_1
-                                .clone()
-                                .send_event(CommandEvent::ChangedAssets(paths))
-                                .unwrap();
-                        } else {
-                            debug!("message event, received Unknown: {:?}", e.data());
-                        }
-                    });
-                // set message event handler on WebSocket
-                ws.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
-                // forget the callback to keep it alive
-                onmessage_callback.forget();
-
-                //CommandEvent::FilePending(file_path.to_owned())
-                vec![]
-            } else {
-                debug!("Get Asset {} from server {}", args[1], args[0]);
-
-                let file_path = args[1];
-
-                let request_type = args[2].to_ascii_lowercase().to_owned();
-                let asset_path = args[1].to_owned();
-                let asset_name = args[1].split('/').last().unwrap().to_owned();
-                let asset_type = match args[2].to_ascii_lowercase().as_str() {
-                    "shader" => AssetType::Shader,
-                    "string" => AssetType::String,
-                    "texture" => AssetType::Texture,
-                    "font" => AssetType::Font,
-                    "model" => AssetType::Model,
-                    "mesh" => AssetType::Mesh,
-                    "material" => AssetType::Material,
-                    _ => AssetType::Unknown,
-                };
-
-                let url = format!("{}{}", "wss://", addr);
-
-                let ws = web_sys::WebSocket::new(url.as_str()).unwrap();
-                let cloned_ws = ws.clone();
-
-                let file_path_copy = file_path.to_owned();
-                let onopen_callback = Closure::<dyn FnMut()>::new(move || {
-                    //debug!("socket opened");
-                    let msg = format!("{}{} {}", "get ", file_path_copy, request_type);
-                    match cloned_ws.send_with_str(msg.as_str()) {
-                        Ok(_) => debug!("message successfully sent: {msg}"),
-                        Err(err) => debug!("error sending message: {:?}", err),
-                    }
-                });
-                ws.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
-                onopen_callback.forget();
-
-                let onmessage_callback =
-                    Closure::<dyn FnMut(_)>::new(move |e: web_sys::MessageEvent| {
-                        if let Ok(blob) = e.data().dyn_into::<web_sys::Blob>() {
-                            let elp_c1 = elp_1.clone();
-                            let asset_type_clone = asset_type.clone();
-                            let asset_name_clone = asset_name.clone();
-                            let asset_path_clone = asset_path.clone();
-                            debug!("message event, received blob: {:?}", blob);
-                            // better alternative to juggling with FileReader is to use https://crates.io/crates/gloo-file
-                            let fr = web_sys::FileReader::new().unwrap();
-                            let fr_c = fr.clone();
-                            // create onLoadEnd callback
-                            let onloadend_cb =
-                                Closure::<dyn FnMut(_)>::new(move |_e: web_sys::ProgressEvent| {
-                                    let array = js_sys::Uint8Array::new(&fr_c.result().unwrap());
-                                    let _len = array.byte_length() as usize;
-                                    // here you can for example use the received image/png data
-
-                                    elp_c1
-                                        .clone()
-                                        .send_event(CommandEvent::Asset(Asset {
-                                            asset_type: asset_type_clone.clone(),
-                                            data: array.to_vec(),
-                                            status: AssetStatus::Ready,
-                                            name: asset_name_clone.clone(),
-                                            path: asset_path_clone.clone(),
-                                        }))
-                                        .unwrap();
-                                });
-                            fr.set_onloadend(Some(onloadend_cb.as_ref().unchecked_ref()));
-                            fr.read_as_array_buffer(&blob).expect("blob not readable");
-                            onloadend_cb.forget();
-                        } else if let Ok(txt) = e.data().dyn_into::<js_sys::JsString>() {
-                            //info!("message event, received Text: {:?}", txt);
-                            let data: String = txt.into();
-
-                            elp_2
-                                .send_event(CommandEvent::Asset(Asset {
-                                    asset_type: asset_type.clone(),
-                                    data: data.into(),
-                                    status: AssetStatus::Ready,
-                                    name: asset_name.clone(),
-                                    path: asset_path.clone(),
-                                }))
-                                .expect("Could not send event T-T");
-                        } else {
-                            debug!("message event, received Unknown: {:?}", e.data());
-                        }
-                    });
-                // set message event handler on WebSocket
-                ws.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
-                // forget the callback to keep it alive
-                onmessage_callback.forget();
-
-                //CommandEvent::FilePending(file_path.to_owned())
-                vec![]
-            }
+/// A task failing with `CommandFailure::InvalidArgs`, for requests that cannot be made.
+fn invalid_args(reason: String) -> AsyncTask<CommandResult> {
+    Box::pin(async move { Err(CommandFailure::InvalidArgs(reason)) })
+}
+
+/// Sends a request to the asset server at `address`, its answer is the event of the command.
+fn request_from_server(
+    address: String,
+    kind: RequestKind,
+    path: String,
+    asset_type: AssetType,
+    connections: super::protocol::AssetConnections,
+) -> AsyncTask<CommandResult> {
+    let task = async move {
+        // The asset server tracks the changes per client
+        let (request_path, asked) = match kind {
+            RequestKind::Get => (path.as_str(), path.as_str()),
+            RequestKind::Changed => (connections.client_id(), "the changed assets"),
         };
 
-        Some(Box::new(cmd))
-    }
+        debug!(
+            "Get {asked} of type {} from server {address}",
+            asset_type.as_str()
+        );
+
+        let response = connections
+            .request(&address, kind, request_path, asset_type.clone())
+            .await
+            .map_err(|err| {
+                CommandFailure::Failed(format!("Could not get {asked} from {address}: {err}"))
+            })?;
+
+        Ok(response_event(response, kind, &path, asset_type)?
+            .into_iter()
+            .collect::<Vec<_>>()
+            .into())
+    };
+
+    Box::pin(task)
+}
 
-    // TODO: ALL COMMANDS NEED THIS
-    fn display_help() -> Option<Task<Vec<CommandEvent>>> {
-        info!("-from_server <port> <file_path>: Query the asset server for the specified asset");
-        info!("-local <file_path>: Get the asset from the local asset dir (ONLY WORKS ON not(target = wasm32)");
-        None
+/// The event answering a request for the asset at `path` (read as `asset_type`) or for the changed assets.
+/// A missing asset is sent with `AssetStatus::NotFound`, as in local mode.
+///
+/// # Returns
+///
+/// None if no asset changed.
+fn response_event(
+    response: Response,
+    kind: RequestKind,
+    path: &str,
+    asset_type: AssetType,
+) -> Result<Option<CommandEvent>, CommandFailure> {
+    let status = match response.status {
+        Status::Ok => AssetStatus::Ready,
+        Status::NotFound if kind == RequestKind::Get => AssetStatus::NotFound,
+        status => {
+            return Err(CommandFailure::Failed(format!(
+                "Asset server could not answer for {path} ({status:?}): {}",
+                response.text()
+            )))
+        }
+    };
+
+    if kind == RequestKind::Changed {
+        let text = response.text();
+        let paths: Vec<String> = text
+            .lines()
+            .filter(|path| !path.is_empty())
+            .map(str::to_owned)
+            .collect();
+
+        return Ok((!paths.is_empty()).then_some(CommandEvent::ChangedAssets(paths)));
     }
+
+    // The type asked for wins, the server can only guess it from the file
+    let asset_type = match (asset_type, &status) {
+        (AssetType::Unknown, AssetStatus::Ready) => response.content_type,
+        (asset_type, _) => asset_type,
+    };
+
+    Ok(Some(CommandEvent::Asset(Asset {
+        asset_type,
+        data: match status {
+            AssetStatus::Ready => response.body,
+            _ => vec![],
+        },
+        status,
+        name: path.rsplit('/').next().unwrap_or(path).to_owned(),
+        path: path.to_owned(),
+    })))
 }
 
 impl IntoCommand for AssetCommand {
     fn into_command(self) -> Command {
         Command {
-            processed: self.task.is_some(),
+            processed: self.future.is_some(),
             app: "AssetServer".into(),
             command_type: self.command_type,
             args: Some(self.args),
-            task: self.task,
+            task: None,
+            future: self.future,
+            timeout: Some(ASSET_REQUEST_TIMEOUT),
+            reply_to: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_local_paths_with_spaces() {
+        let root = std::env::temp_dir().join(format!("sunset-asset-cmd-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("my shaders")).unwrap();
+        std::fs::write(root.join("my shaders/flat shader.wgsl"), "// flat").unwrap();
+
+        let args = join_args(&["my shaders/flat shader.wgsl", "shader"]);
+        let task = AssetCommand::get_local(args, root.to_string_lossy().into_owned());
+        let output = pollster::block_on(task).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let [CommandEvent::Asset(asset)] = output.events.as_slice() else {
+            panic!("expected an asset, got {:?}", output.events);
+        };
+        assert_eq!(asset.status, AssetStatus::Ready);
+        assert_eq!(asset.name, "flat shader.wgsl");
+        assert_eq!(asset.path, "my shaders/flat shader.wgsl");
+        assert_eq!(asset.data, b"// flat");
+    }
+
+    #[test]
+    fn missing_arguments_fail_the_request() {
+        let local = AssetCommand::get_local("shader.wgsl".into(), "assets/".into());
+        assert!(matches!(
+            pollster::block_on(local),
+            Err(CommandFailure::InvalidArgs(_))
+        ));
+
+        let server =
+            AssetCommand::get_from_server("127.0.0.1 shader.wgsl".into(), Default::default());
+        assert!(matches!(
+            pollster::block_on(server),
+            Err(CommandFailure::InvalidArgs(_))
+        ));
+    }
+}
@@ -1,96 +1,303 @@
 use std::collections::HashMap;
 
 use async_trait::async_trait;
-use tracing::{error, info};
-use winit::event_loop::EventLoopProxy;
+use tracing::{debug, error, info};
 
 use crate::{
     core::{
         app::App,
-        command_queue::{Command, Task},
-        events::CommandEvent,
+        command_queue::Command,
+        command_registry::{join_args, ArgDesc, ArgType, CommandDesc, ParsedArgs},
+        command_result::{CommandFailure, CommandOutput},
+        config::{AssetMode, EngineConfig},
+        events::{CommandEvent, EventKind},
+        proxy::EngineProxy,
     },
     prelude::command_queue::CommandType,
 };
 
-use super::{asset_cmd::AssetCommand, Asset, AssetStatus, AssetType};
+#[cfg(not(target_arch = "wasm32"))]
+use super::watcher::{AssetWatcher, DEFAULT_WATCH_INTERVAL};
+use super::{
+    asset_cmd::{AssetCommand, ASSET_REQUEST_TIMEOUT},
+    handle::{Assets, Handle, HandleRegistry},
+    loader::{AssetLoader, AssetLoaders, LoadedAsset},
+    protocol::AssetConnections,
+    Asset, AssetStatus, AssetType,
+};
+
+/// Seconds between two checks of the changed assets in server mode, on the wall clock (see `asset_server check`).
+pub const CHECK_INTERVAL: f32 = 10.0;
 
 #[derive(PartialEq, Debug)]
 enum ServerMode {
-    Local,
+    /// Root folder of the assets.
+    Local(String),
+    /// Address of the asset server.
     Server(String),
 }
 
 pub struct AssetServer {
     pub commands: Vec<Command>,
 
-    pub cached_assets: HashMap<String, Asset>,
+    /// Received assets, released once no handle to them is left (see `Handle`).
+    pub cached_assets: Assets<Asset>,
+    /// Assets asked for with the `get` command, kept loaded until released with the `release` command.
+    pub requested_assets: HashMap<String, Handle<Asset>>,
+    /// Decode the received assets, see `AssetLoader`.
+    pub loaders: AssetLoaders,
     pub changed_assets: Vec<(String, AssetType)>,
 
-    pub proxy: Option<EventLoopProxy<CommandEvent>>,
-    pub time_elapsed: f32,
+    pub proxy: Option<EngineProxy>,
     pub time_elapsed_fast: f32,
 
     mode: ServerMode,
+    /// When the changed assets were last checked in server mode, see `CHECK_INTERVAL`.
+    last_check: web_time::Instant,
+    /// The handles of the engine, see `EngineProxy::handles`.
+    handles: HandleRegistry,
+    /// Connections to the asset server, reused by the next requests.
+    connections: AssetConnections,
+    /// Watches the files read in local mode, started by `init`.
+    #[cfg(not(target_arch = "wasm32"))]
+    watcher: Option<AssetWatcher>,
 }
 
 impl AssetServer {
+    /// Requests the assets from the asset server at `addr`.
     pub fn new(addr: String) -> Self {
-        let cmd_args: Vec<String> = std::env::args().collect();
-        let mut mode = ServerMode::Server(addr);
-        if cmd_args.contains(&"local".to_string()) {
-            mode = ServerMode::Local;
+        AssetServer::with_mode(ServerMode::Server(addr))
+    }
+
+    /// Reads the assets from the files under `root` (Ex: `assets/`).
+    pub fn local(root: String) -> Self {
+        AssetServer::with_mode(ServerMode::Local(root))
+    }
+
+    /// Local or server mode depending on `EngineConfig::asset_mode`.
+    pub fn from_config(config: &EngineConfig) -> Self {
+        match config.asset_mode {
+            AssetMode::Local => AssetServer::local(config.asset_root.clone()),
+            AssetMode::Server => AssetServer::new(config.asset_address.clone()),
         }
+    }
 
+    fn with_mode(mode: ServerMode) -> Self {
         AssetServer {
             commands: vec![],
 
-            cached_assets: HashMap::new(),
+            cached_assets: Assets::new(),
+            requested_assets: HashMap::new(),
+            loaders: AssetLoaders::builtin(),
             changed_assets: Vec::new(),
 
             proxy: None,
-            time_elapsed: 0.0,
             time_elapsed_fast: 0.0,
             mode,
+            last_check: web_time::Instant::now(),
+            handles: HandleRegistry::default(),
+            connections: AssetConnections::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            watcher: None,
         }
     }
 
-    pub fn process_asset_command(&mut self, mut cmd: Command) {
-        let args = cmd.args.clone().unwrap();
-        let vec_args: Vec<&str> = args.split(' ').collect();
+    pub fn process_asset_command(&mut self, mut cmd: Command, args: ParsedArgs) {
+        let cmd = match args.command() {
+            "get" => {
+                let path = args.get_str("path").unwrap();
+                self.requested_assets
+                    .entry(path.to_owned())
+                    .or_insert_with(|| self.handles.handle(path));
+
+                let mut get = self.get(path, args.get_str("type").unwrap());
+                get.reply_to = cmd.reply_to.take();
+                get
+            }
+            "release" => {
+                let path = args.get_str("path").unwrap().to_owned();
+                let result = match self.requested_assets.remove(&path) {
+                    // The handle of the command is not counted
+                    Some(handle) => Ok(CommandOutput::message(format!(
+                        "released {path}, {} other handles left",
+                        handle.ref_count() - 1
+                    ))),
+                    None => Err(CommandFailure::Failed(format!(
+                        "{path} was not requested with get"
+                    ))),
+                };
 
-        let task = match vec_args[0].to_ascii_lowercase().as_str() {
-            "get" => self.get(&vec_args[1..].join(" ")),
+                cmd.processed = true;
+                cmd.task = Some(Box::new(move || result.clone()));
+                cmd
+            }
+            "list" => {
+                let message = self.describe_assets();
+
+                cmd.processed = true;
+                cmd.task = Some(Box::new(move || {
+                    Ok(CommandOutput::message(message.clone()))
+                }));
+                cmd
+            }
+            "check" => match self.check() {
+                Some(mut check) => {
+                    check.reply_to = cmd.reply_to.take();
+                    check
+                }
+                None => {
+                    cmd.processed = true;
+                    cmd.task = Some(Box::new(|| {
+                        Ok(CommandOutput::message(
+                            "local assets are watched, they are read again once saved",
+                        ))
+                    }));
+                    cmd
+                }
+            },
+            "loaders" => {
+                let message = self.loaders.describe();
+
+                cmd.processed = true;
+                cmd.task = Some(Box::new(move || {
+                    Ok(CommandOutput::message(message.clone()))
+                }));
+                cmd
+            }
             //"put" => self.put(args),
-            _ => AssetServer::unsupported(args.as_str()),
+            _ => {
+                cmd.processed = true;
+                cmd.task = AssetServer::unsupported(cmd.args.as_deref().unwrap_or_default());
+                cmd
+            }
         };
 
-        cmd.processed = true;
-        cmd.task = task;
-        cmd.args = Some(args);
-
         self.commands.push(cmd);
     }
 
-    pub fn get(&self, args: &str) -> Option<Task<Vec<CommandEvent>>> {
-        let vec_args: Vec<&str> = args.split(' ').collect();
+    /// A command fetching the asset in the background, the asset is received as a `CommandEvent::Asset`.
+    pub fn get(&self, path: &str, asset_type: &str) -> Command {
+        let future = match &self.mode {
+            ServerMode::Local(root) => {
+                let args = join_args(&[path, asset_type]);
 
-        if vec_args.len() < 2 {
-            error!("Expected 2 arguments to command <get>!");
+                AssetCommand::get_local(args, root.clone())
+            }
+            ServerMode::Server(addr) => {
+                let args = join_args(&[addr.as_str(), path, asset_type]);
+
+                AssetCommand::get_from_server(args, self.connections.clone())
+            }
+        };
+
+        Command::from_future(
+            "asset_server",
+            CommandType::Get,
+            Some(join_args(&["get", path, asset_type])),
+            future,
+        )
+        .with_timeout(ASSET_REQUEST_TIMEOUT)
+    }
+
+    /// A command asking the asset server which assets changed, received as a `CommandEvent::ChangedAssets`.
+    ///
+    /// # Returns
+    ///
+    /// None in local mode, the files are watched instead.
+    pub fn check(&self) -> Option<Command> {
+        let ServerMode::Server(addr) = &self.mode else {
             return None;
+        };
+
+        let future = AssetCommand::changed_from_server(addr.clone(), self.connections.clone());
+        let cmd = Command::from_future(
+            "asset_server",
+            CommandType::Other,
+            Some("check".into()),
+            future,
+        )
+        .with_timeout(ASSET_REQUEST_TIMEOUT);
+
+        Some(cmd)
+    }
+
+    /// Decodes the assets of a new format, or replaces the loader of a format (see `AssetLoaders::find`).
+    pub fn register_loader(&mut self, loader: impl AssetLoader + 'static) {
+        self.loaders.register(loader);
+    }
+
+    pub fn with_loader(mut self, loader: impl AssetLoader + 'static) -> Self {
+        self.register_loader(loader);
+        self
+    }
+
+    /// Decodes `asset` with its loader in the background, the result is sent as a `CommandEvent::LoadedAsset`.
+    fn load(&mut self, asset: &Asset, handle: Handle<Asset>) {
+        let Some(loader) = self.loaders.find(asset) else {
+            return;
+        };
+
+        let args = format!("load {}", asset.path);
+        let path = asset.path.clone();
+        let asset = asset.clone();
+        let task = async move {
+            let value = loader
+                .load(asset)
+                .await
+                .map_err(|err| CommandFailure::Failed(format!("Could not load {path}: {err}")))?;
+
+            let loaded = LoadedAsset::new(handle, loader.asset_type(), value);
+            Ok(vec![CommandEvent::LoadedAsset(loaded)].into())
+        };
+
+        self.commands.push(Command::from_future(
+            "asset_server",
+            CommandType::Other,
+            Some(args),
+            Box::pin(task),
+        ));
+    }
+
+    /// One `<id> <path> <type> <status> (<handles> handles)` line per cached asset, sorted by path.
+    pub fn describe_assets(&self) -> String {
+        if self.cached_assets.is_empty() {
+            return "no cached assets".into();
         }
 
-        match &self.mode {
-            ServerMode::Local => {
-                let args = format!("{} {}", vec_args[0], vec_args[1]);
+        let mut assets: Vec<(&str, &Asset)> = self.cached_assets.iter().collect();
+        assets.sort_by_key(|(path, _)| *path);
+
+        let lines: Vec<String> = assets
+            .into_iter()
+            .filter_map(|(path, asset)| {
+                let handle = self.cached_assets.handle(path)?;
+                // The handle just made is not counted
+                Some(format!(
+                    "{} {path} {} {:?} ({} handles)",
+                    handle.id(),
+                    asset.asset_type.as_str(),
+                    handle.status(),
+                    handle.ref_count() - 1
+                ))
+            })
+            .collect();
+
+        format!("cached assets:\n  {}", lines.join("\n  "))
+    }
 
-                return AssetCommand::get_local(args);
-            }
-            ServerMode::Server(addr) => {
-                let args = format!("{} {} {}", addr, vec_args[0], vec_args[1]);
-                return AssetCommand::get_from_server(args, self.proxy.clone().unwrap());
+    /// Watches (or stops watching) the file of the asset at `path` in local mode, it is read again once modified.
+    fn watch(&self, path: &str, watched: bool) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (ServerMode::Local(root), Some(watcher)) = (&self.mode, &self.watcher) {
+            let full_path = std::path::Path::new(root).join(path);
+            match watched {
+                true => watcher.watch(full_path, path),
+                false => watcher.unwatch(&full_path),
             }
         }
+
+        #[cfg(target_arch = "wasm32")]
+        let _ = (path, watched);
     }
 }
 
@@ -99,12 +306,68 @@ impl App for AssetServer {
     fn get_name(&self) -> String {
         "Asset Server".into()
     }
-    fn init(&mut self, elp: EventLoopProxy<CommandEvent>) {
+
+    // Received assets are cached before the apps requesting them process the asset events
+    fn priority(&self) -> i32 {
+        -5
+    }
+    fn init(&mut self, elp: EngineProxy) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let ServerMode::Local(_) = self.mode {
+            self.watcher = Some(AssetWatcher::start(elp.clone(), DEFAULT_WATCH_INTERVAL));
+        }
+
+        self.handles = elp.handles().clone();
         self.proxy = Some(elp.clone())
     }
 
-    async fn process_command(&mut self, cmd: Command) {
-        self.process_asset_command(cmd)
+    fn commands(&self) -> Vec<CommandDesc> {
+        vec![
+            CommandDesc::new("get", "Requests an asset from the asset server")
+                .arg(ArgDesc::positional(
+                    "path",
+                    ArgType::String,
+                    "Asset path relative to the asset root",
+                ))
+                .arg(ArgDesc::positional(
+                    "type",
+                    ArgType::String,
+                    "Asset type (shader, texture, font, model, mesh, material, string)",
+                )),
+            CommandDesc::new(
+                "release",
+                "Stops keeping an asset requested with get loaded, it is freed once no handle to it is left",
+            )
+            .arg(ArgDesc::positional(
+                "path",
+                ArgType::String,
+                "Asset path relative to the asset root",
+            )),
+            CommandDesc::new(
+                "list",
+                "Lists the cached assets with their id, status and number of handles",
+            ),
+            CommandDesc::new(
+                "loaders",
+                "Lists the asset loaders with the extensions they read",
+            ),
+            CommandDesc::new(
+                "check",
+                "Asks the asset server which files changed since the last check and requests them again",
+            ),
+        ]
+    }
+
+    async fn process_command(&mut self, cmd: Command, args: ParsedArgs) {
+        self.process_asset_command(cmd, args)
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![
+            EventKind::Asset,
+            EventKind::RequestCreateModel,
+            EventKind::ChangedAssets,
+        ]
     }
 
     async fn process_user_event(
@@ -114,33 +377,36 @@ impl App for AssetServer {
     ) {
         match event {
             CommandEvent::Asset(asset) => {
+                let handle = self.handles.handle(&asset.path);
+
                 if asset.status == AssetStatus::NotFound {
                     error!("File <{}> not found!", asset.path);
+                    handle.set_status(AssetStatus::NotFound);
                 } else {
-                    self.cached_assets.insert(asset.path.clone(), asset.clone());
+                    self.watch(&asset.path, true);
+                    handle.set_status(AssetStatus::Ready);
+                    self.cached_assets.insert(&handle, asset.clone());
+                    self.load(asset, handle);
                 }
             }
             CommandEvent::RequestCreateModel(model_comp) => {
-                if self.cached_assets.contains_key(&model_comp.model_path) {
+                if self.cached_assets.contains_path(&model_comp.model_path) {
                     return;
                 }
 
-                let task = self.get(&format!("{} model", model_comp.model_path));
-
-                let cmd = Command {
-                    app: "asset_server".into(),
-                    command_type: CommandType::Get,
-                    processed: true,
-                    args: None,
-                    task,
-                };
-
+                let cmd = self.get(&model_comp.model_path, "model");
                 self.commands.push(cmd);
             }
             CommandEvent::ChangedAssets(paths) => {
                 info!("Changed asset: {paths:?}");
                 for path in paths {
-                    if let Some(asset) = self.cached_assets.get(path) {
+                    let Some(handle) = self.cached_assets.handle(path) else {
+                        continue;
+                    };
+
+                    // Stays outdated until the new version is received
+                    handle.set_status(AssetStatus::Outdated);
+                    if let Some(asset) = self.cached_assets.get(&handle) {
                         self.changed_assets
                             .push((path.clone(), asset.asset_type.clone()));
                     }
@@ -150,50 +416,24 @@ impl App for AssetServer {
         }
     }
 
-    fn update(&mut self, delta_time: f32) -> Vec<Command> {
-        self.time_elapsed += delta_time;
-
-        if self.time_elapsed > 10.0 {
-            if self.mode != ServerMode::Local {
-                let task = self.get("get changed");
-                let cmd = Command {
-                    app: "asset_server".into(),
-                    command_type: CommandType::Get,
-                    processed: true,
-                    args: None,
-                    task,
-                };
-
-                self.time_elapsed = 0.0;
-                self.commands.push(cmd);
-            }
+    fn update(&mut self, _delta_time: f32) -> Vec<Command> {
+        // On the wall clock, the changes are still checked while the simulation is paused
+        if self.last_check.elapsed().as_secs_f32() >= CHECK_INTERVAL {
+            self.last_check = web_time::Instant::now();
+            self.commands.extend(self.check());
         }
 
         for (path, asset_type) in &self.changed_assets {
-            let asset_type = match asset_type {
-                AssetType::Material => "material",
-                AssetType::String => "text",
-                AssetType::Shader => "shader",
-                AssetType::Texture => "texture",
-                AssetType::Font => "font",
-                AssetType::Mesh => "mesh",
-                AssetType::Model => "model",
-                AssetType::Unknown => "idk bruv",
-            };
-
-            let task = self.get(format!("{} {}", path, asset_type).as_str());
-            let cmd = Command {
-                app: "asset_server".into(),
-                command_type: CommandType::Get,
-                processed: true,
-                args: None,
-                task,
-            };
-
+            let cmd = self.get(path, asset_type.as_str());
             self.commands.push(cmd);
         }
         self.changed_assets.clear();
 
+        for (path, _) in self.cached_assets.free_unused() {
+            debug!("Released asset {path}");
+            self.watch(&path, false);
+        }
+
         self.commands.drain(..).collect()
     }
 
@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use async_std::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info, warn};
+
+use crate::assets::{
+    protocol::{
+        read_frame, write_frame, Hello, ProtocolError, Request, RequestKind, Response, Status,
+        PROTOCOL_VERSION,
+    },
+    AssetType,
+};
+
+/// Modification time of every file under a root folder, by path relative to the root.
+type FileTimes = HashMap<String, SystemTime>;
+
+/// Serves the files under a root folder to engines in server mode, over the asset protocol (see `protocol`).
+///
+/// Each client is told which files were added or modified since its previous `changed` request
+/// (or since the server started, for the first one), whichever connection it sends them on.
+/// Only the files under the root are served, paths and symbolic links leading out of it are rejected.
+///
+/// # Example
+///
+/// ```ignore
+/// let server = AssetFileServer::bind("127.0.0.1:0", "assets/").await?;
+/// let address = server.local_addr()?;
+/// async_std::task::spawn(server.run());
+///
+/// let engine = EngineBuilder::new()
+///     .configure(|config| {
+///         config.asset_mode = AssetMode::Server;
+///         config.asset_address = address.to_string();
+///     })
+///     .build();
+/// ```
+pub struct AssetFileServer {
+    listener: TcpListener,
+    root: PathBuf,
+    changes: Arc<Mutex<ChangeTracker>>,
+}
+
+/// The files each client was told about in its previous `changed` request.
+struct ChangeTracker {
+    /// The files when the server started, compared to on the first request of a client.
+    initial: FileTimes,
+    /// By client id.
+    known: HashMap<String, FileTimes>,
+}
+
+impl AssetFileServer {
+    /// Listens on `address` (Ex: `127.0.0.1:8080`, port 0 for any free port) to serve the files under `root`.
+    ///
+    /// # Returns
+    ///
+    /// An io error if `root` does not exist.
+    pub async fn bind(address: &str, root: impl Into<PathBuf>) -> Result<Self, ProtocolError> {
+        let root = root.into().canonicalize()?;
+        let changes = ChangeTracker {
+            initial: scan(&root),
+            known: HashMap::new(),
+        };
+
+        Ok(AssetFileServer {
+            listener: TcpListener::bind(address).await?,
+            root,
+            changes: Arc::new(Mutex::new(changes)),
+        })
+    }
+
+    /// The address the server listens on, with the port picked by the system if it was bound to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr, ProtocolError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Accepts connections until the listener fails, each one is served by its own task.
+    pub async fn run(self) -> Result<(), ProtocolError> {
+        info!(
+            "Serving {} on {}",
+            self.root.display(),
+            self.listener.local_addr()?
+        );
+
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            let root = self.root.clone();
+            let changes = self.changes.clone();
+
+            async_std::task::spawn(async move {
+                debug!("Client {peer} connected");
+
+                match serve_client(stream, root, changes).await {
+                    Ok(()) => debug!("Client {peer} disconnected"),
+                    Err(err) => warn!("Client {peer}: {err}"),
+                }
+            });
+        }
+    }
+}
+
+/// Answers the requests of one client until it disconnects.
+async fn serve_client(
+    mut stream: TcpStream,
+    root: PathBuf,
+    changes: Arc<Mutex<ChangeTracker>>,
+) -> Result<(), ProtocolError> {
+    let Some(hello) = read_frame(&mut stream).await? else {
+        return Ok(());
+    };
+    let hello = Hello::decode(&hello)?;
+
+    let reply = Hello::reply_to(&hello);
+    write_frame(&mut stream, &reply.encode()).await?;
+    if reply.status != Some(Status::Ok) {
+        return Err(ProtocolError::UnsupportedVersion {
+            ours: PROTOCOL_VERSION,
+            theirs: hello.version,
+        });
+    }
+
+    while let Some(payload) = read_frame(&mut stream).await? {
+        let response = match Request::decode(&payload) {
+            Ok(request) => answer(&root, request, &changes).await,
+            // Without its id the request cannot be answered, the stream can no longer be trusted
+            Err(err) => {
+                write_frame(
+                    &mut stream,
+                    &Response::error(0, Status::BadRequest, &err.to_string()).encode(),
+                )
+                .await?;
+                return Err(err);
+            }
+        };
+
+        write_response(&mut stream, response).await?;
+    }
+
+    Ok(())
+}
+
+/// Sends `response`, or an error in its place if it does not fit in a frame (Ex: a huge file).
+async fn write_response(stream: &mut TcpStream, response: Response) -> Result<(), ProtocolError> {
+    match write_frame(stream, &response.encode()).await {
+        Err(ProtocolError::FrameTooLarge(size)) => {
+            let message = format!("the response of {size} bytes is too large to be sent");
+            let response = Response::error(response.id, Status::ServerError, &message);
+            write_frame(stream, &response.encode()).await
+        }
+        result => result,
+    }
+}
+
+async fn answer(root: &Path, request: Request, changes: &Mutex<ChangeTracker>) -> Response {
+    match request.kind {
+        RequestKind::Get => {
+            debug!("get {} {}", request.path, request.asset_type.as_str());
+
+            let Some(full_path) = resolve(root, &request.path) else {
+                return Response::error(
+                    request.id,
+                    Status::BadRequest,
+                    &format!("invalid path {:?}", request.path),
+                );
+            };
+
+            // Symbolic links are followed, as long as they stay under the root
+            let full_path = match async_std::fs::canonicalize(&full_path).await {
+                Ok(full_path) if full_path.starts_with(root) => full_path,
+                Ok(_) => {
+                    return Response::error(
+                        request.id,
+                        Status::BadRequest,
+                        &format!("invalid path {:?}", request.path),
+                    )
+                }
+                Err(_) => full_path.into(),
+            };
+
+            match async_std::fs::read(&full_path).await {
+                Ok(body) => Response::ok(request.id, content_type(&request.path), body),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Response::error(
+                    request.id,
+                    Status::NotFound,
+                    &format!("File not found: {}", request.path),
+                ),
+                Err(err) => {
+                    error!("Could not read {}: {err}", full_path.display());
+                    Response::error(request.id, Status::ServerError, &err.to_string())
+                }
+            }
+        }
+        RequestKind::Changed => {
+            let files = scan(root);
+            let mut changes = changes.lock().unwrap_or_else(PoisonError::into_inner);
+            let known_files = changes.known.get(&request.path).unwrap_or(&changes.initial);
+
+            let mut changed: Vec<&str> = files
+                .iter()
+                .filter(|(path, modified)| known_files.get(*path) != Some(modified))
+                .map(|(path, _)| path.as_str())
+                .collect();
+            changed.sort_unstable();
+
+            let body = changed.join("\n").into_bytes();
+            changes.known.insert(request.path, files);
+
+            Response::ok(request.id, AssetType::String, body)
+        }
+    }
+}
+
+/// The file at `path` under `root`, none if `path` leaves `root` (Ex: `../secret`, `/etc/passwd`).
+fn resolve(root: &Path, path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    let inside_root = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+    (inside_root && !path.as_os_str().is_empty()).then(|| root.join(path))
+}
+
+/// The type of the asset guessed from the extension of its path.
+pub fn content_type(path: &str) -> AssetType {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "wgsl" => AssetType::Shader,
+        "png" | "jpg" | "jpeg" | "bmp" | "tga" | "hdr" | "dds" | "ktx2" => AssetType::Texture,
+        "ttf" | "otf" => AssetType::Font,
+        "gltf" | "glb" | "obj" => AssetType::Model,
+        "txt" | "json" | "cfg" | "toml" | "md" => AssetType::String,
+        _ => AssetType::Unknown,
+    }
+}
+
+/// The modification time of every file under `root`, unreadable folders are skipped.
+fn scan(root: &Path) -> FileTimes {
+    let mut files = FileTimes::new();
+    let mut folders = vec![root.to_path_buf()];
+
+    while let Some(folder) = folders.pop() {
+        let Ok(entries) = std::fs::read_dir(&folder) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+
+            if metadata.is_dir() {
+                folders.push(entry.path());
+                continue;
+            }
+
+            let Ok(relative) = entry.path().strip_prefix(root).map(Path::to_path_buf) else {
+                continue;
+            };
+            let path = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            files.insert(path, metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH));
+        }
+    }
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use super::*;
+    use crate::{
+        assets::{asset_cmd::AssetCommand, protocol::AssetConnections, AssetStatus},
+        core::{command_result::CommandFailure, events::CommandEvent},
+    };
+
+    /// The events of `get_from_server` for `<address> <args>`.
+    async fn get(
+        connections: &AssetConnections,
+        address: SocketAddr,
+        args: &str,
+    ) -> Result<Vec<CommandEvent>, CommandFailure> {
+        let task = AssetCommand::get_from_server(format!("{address} {args}"), connections.clone());
+        Ok(task.await?.events)
+    }
+
+    /// The paths of `changed_from_server`.
+    async fn changed(connections: &AssetConnections, address: SocketAddr) -> Vec<String> {
+        let task = AssetCommand::changed_from_server(address.to_string(), connections.clone());
+        match &task.await.unwrap().events[..] {
+            [CommandEvent::ChangedAssets(paths)] => paths.clone(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn serves_files_and_changes_per_client() {
+        let root = std::env::temp_dir().join(format!("sunset-file-server-{}", std::process::id()));
+        let outside = root.with_extension("outside");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("shaders")).unwrap();
+        fs::write(root.join("shaders/a.wgsl"), "a").unwrap();
+        fs::write(&outside, "secret").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&outside, root.join("escape.txt")).unwrap();
+
+        async_std::task::block_on(async {
+            let server = AssetFileServer::bind("127.0.0.1:0", &root).await.unwrap();
+            let address = server.local_addr().unwrap();
+            async_std::task::spawn(server.run());
+
+            let engine = AssetConnections::default();
+            let other_engine = AssetConnections::default();
+
+            let events = get(&engine, address, "shaders/a.wgsl shader")
+                .await
+                .unwrap();
+            assert!(matches!(&events[..], [CommandEvent::Asset(asset)] if asset.data == b"a"));
+            let events = get(&engine, address, "shaders/missing.wgsl shader")
+                .await
+                .unwrap();
+            assert!(matches!(&events[..], [CommandEvent::Asset(asset)]
+                if asset.status == AssetStatus::NotFound && asset.asset_type == AssetType::Shader));
+            assert!(get(&engine, address, "../outside shader").await.is_err());
+            #[cfg(unix)]
+            assert!(get(&engine, address, "escape.txt string").await.is_err());
+
+            assert!(changed(&engine, address).await.is_empty());
+
+            let file = fs::File::options()
+                .write(true)
+                .open(root.join("shaders/a.wgsl"))
+                .unwrap();
+            file.set_modified(SystemTime::now() + Duration::from_secs(60))
+                .unwrap();
+
+            // Both connections of the pool share the client id of the engine
+            let (first, second) = futures::join!(
+                changed(&engine, address),
+                get(&engine, address, "shaders/a.wgsl shader"),
+            );
+            second.unwrap();
+            assert_eq!(first, ["shaders/a.wgsl"]);
+            assert!(changed(&engine, address).await.is_empty());
+
+            assert_eq!(changed(&other_engine, address).await, ["shaders/a.wgsl"]);
+        });
+
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_file(&outside).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, Mutex, Weak},
+};
+
+use crate::assets::AssetStatus;
+
+/// Stable id of an asset in its `HandleRegistry`, the same for every handle to its path while one of them is alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetId(pub u64);
+
+impl fmt::Display for AssetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// The handles alive in an engine, by path, shared by its apps (Ex: a scene's model component and the renderer)
+/// through `EngineProxy::handles`. Cloning `HandleRegistry` gives another handle to the same registry.
+///
+/// # Example
+///
+/// ```ignore
+/// let handle: Handle<SunModel> = elp.handles().handle("models/cube.glb");
+/// assert_eq!(handle.id(), elp.handles().handle::<Asset>("models/cube.glb").id());
+/// ```
+#[derive(Clone, Default)]
+pub struct HandleRegistry {
+    inner: Arc<Mutex<RegistryInner>>,
+}
+
+#[derive(Default)]
+struct RegistryInner {
+    handles: HashMap<String, Weak<HandleInner>>,
+    last_id: u64,
+}
+
+impl HandleRegistry {
+    /// The handle of the asset at `path`, with a new id and `AssetStatus::Pending` if no handle to it is alive.
+    pub fn handle<T>(&self, path: &str) -> Handle<T> {
+        let mut registry = self.inner.lock().unwrap();
+
+        let inner = match registry.handles.get(path).and_then(Weak::upgrade) {
+            Some(inner) => inner,
+            None => {
+                registry.last_id += 1;
+                let inner = Arc::new(HandleInner {
+                    id: AssetId(registry.last_id),
+                    path: path.to_owned(),
+                    status: Mutex::new(AssetStatus::Pending),
+                    registry: Arc::downgrade(&self.inner),
+                });
+                registry
+                    .handles
+                    .insert(path.to_owned(), Arc::downgrade(&inner));
+                inner
+            }
+        };
+
+        Handle {
+            inner,
+            marker: PhantomData,
+        }
+    }
+
+    /// Number of assets with a handle alive.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+struct HandleInner {
+    id: AssetId,
+    path: String,
+    status: Mutex<AssetStatus>,
+    registry: Weak<Mutex<RegistryInner>>,
+}
+
+impl Drop for HandleInner {
+    fn drop(&mut self) {
+        let Some(registry) = self.registry.upgrade() else {
+            return;
+        };
+        let handles = &mut registry.lock().unwrap().handles;
+
+        // The path may already have a new handle, created after the last one of this asset dropped
+        if handles
+            .get(&self.path)
+            .is_some_and(|handle| handle.strong_count() == 0)
+        {
+            handles.remove(&self.path);
+        }
+    }
+}
+
+/// A reference to the asset at a path, read as a `T` by its holder (Ex: `Handle<SunModel>` in a `ModelComponent`).
+///
+/// Every handle to a path shares the same id, load status and reference count, whatever its `T`.
+/// The stores holding the copies of the asset (see `Assets`) release them once the last handle drops.
+///
+/// # Example
+///
+/// ```ignore
+/// let handle: Handle<SunModel> = elp.handles().handle("models/cube.glb");
+/// if handle.status() == AssetStatus::Ready {
+///     let model = sun.models.get(&handle);
+/// }
+/// ```
+pub struct Handle<T> {
+    inner: Arc<HandleInner>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub fn id(&self) -> AssetId {
+        self.inner.id
+    }
+
+    pub fn path(&self) -> &str {
+        &self.inner.path
+    }
+
+    pub fn status(&self) -> AssetStatus {
+        self.inner.status.lock().unwrap().clone()
+    }
+
+    pub fn set_status(&self, status: AssetStatus) {
+        *self.inner.status.lock().unwrap() = status;
+    }
+
+    /// Number of handles to the asset, whatever they read it as.
+    pub fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.inner)
+    }
+
+    /// The same asset read as a `U` (Ex: the `Handle<Asset>` of the file of a `Handle<SunModel>`).
+    pub fn cast<U>(&self) -> Handle<U> {
+        Handle {
+            inner: Arc::clone(&self.inner),
+            marker: PhantomData,
+        }
+    }
+
+    fn downgrade(&self) -> Weak<HandleInner> {
+        Arc::downgrade(&self.inner)
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        self.cast()
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner.id == other.inner.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("id", &self.inner.id)
+            .field("path", &self.inner.path)
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+struct AssetEntry<T> {
+    value: T,
+    path: String,
+    handle: Weak<HandleInner>,
+}
+
+/// The copies of the assets an app made (Ex: the GPU models of the renderer), by asset id.
+///
+/// The store does not keep its assets alive: `free_unused` releases the ones without any handle left.
+pub struct Assets<T> {
+    entries: HashMap<AssetId, AssetEntry<T>>,
+    ids: HashMap<String, AssetId>,
+}
+
+impl<T> Default for Assets<T> {
+    fn default() -> Self {
+        Assets {
+            entries: HashMap::new(),
+            ids: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Assets<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores (or replaces, Ex: once reloaded) the copy of the asset of `handle`.
+    ///
+    /// # Returns
+    ///
+    /// The replaced copy, if any.
+    pub fn insert(&mut self, handle: &Handle<T>, value: T) -> Option<T> {
+        self.ids.insert(handle.path().to_owned(), handle.id());
+
+        self.entries
+            .insert(
+                handle.id(),
+                AssetEntry {
+                    value,
+                    path: handle.path().to_owned(),
+                    handle: handle.downgrade(),
+                },
+            )
+            .map(|entry| entry.value)
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> Option<&T> {
+        self.entries.get(&handle.id()).map(|entry| &entry.value)
+    }
+
+    pub fn get_mut(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        self.entries
+            .get_mut(&handle.id())
+            .map(|entry| &mut entry.value)
+    }
+
+    pub fn get_by_path(&self, path: &str) -> Option<&T> {
+        let id = self.ids.get(path)?;
+        self.entries.get(id).map(|entry| &entry.value)
+    }
+
+    pub fn contains_path(&self, path: &str) -> bool {
+        self.ids.contains_key(path)
+    }
+
+    /// A new handle to the stored asset at `path`, none if it is not stored or was released.
+    pub fn handle(&self, path: &str) -> Option<Handle<T>> {
+        let id = self.ids.get(path)?;
+        let inner = self.entries.get(id)?.handle.upgrade()?;
+
+        Some(Handle {
+            inner,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn remove(&mut self, handle: &Handle<T>) -> Option<T> {
+        let entry = self.entries.remove(&handle.id())?;
+        if self.ids.get(&entry.path) == Some(&handle.id()) {
+            self.ids.remove(&entry.path);
+        }
+
+        Some(entry.value)
+    }
+
+    /// The stored assets with their path.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.entries
+            .values()
+            .map(|entry| (entry.path.as_str(), &entry.value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes the assets whose last handle dropped.
+    ///
+    /// # Returns
+    ///
+    /// The released copies with their path, to free what they hold elsewhere (Ex: a bind group).
+    pub fn free_unused(&mut self) -> Vec<(String, T)> {
+        let unused: Vec<AssetId> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.handle.strong_count() == 0)
+            .map(|(id, _)| *id)
+            .collect();
+
+        unused
+            .into_iter()
+            .filter_map(|id| {
+                let entry = self.entries.remove(&id)?;
+                if self.ids.get(&entry.path) == Some(&id) {
+                    self.ids.remove(&entry.path);
+                }
+
+                Some((entry.path, entry.value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registries_are_independent() {
+        let engine = HandleRegistry::default();
+        let other_engine = HandleRegistry::default();
+
+        let model: Handle<String> = engine.handle("models/duck.glb");
+        let file: Handle<()> = engine.handle("models/duck.glb");
+        assert_eq!(model.id(), file.id());
+        assert_eq!(model.ref_count(), 2);
+
+        other_engine
+            .handle::<String>("models/duck.glb")
+            .set_status(AssetStatus::NotFound);
+        assert_eq!(model.status(), AssetStatus::Pending);
+        assert!(other_engine.is_empty());
+
+        drop((model, file));
+        assert!(engine.is_empty());
+    }
+
+    #[test]
+    fn stores_release_assets_without_handles() {
+        let registry = HandleRegistry::default();
+        let mut assets = Assets::new();
+
+        let kept: Handle<u32> = registry.handle("kept");
+        assets.insert(&kept, 1);
+        assets.insert(&registry.handle("dropped"), 2);
+
+        assert_eq!(assets.free_unused(), vec![("dropped".to_owned(), 2)]);
+        assert_eq!(assets.get(&kept), Some(&1));
+        assert_eq!(assets.handle("kept"), Some(kept));
+    }
+}
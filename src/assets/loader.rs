@@ -0,0 +1,220 @@
+use std::{any::Any, sync::Arc};
+
+use crate::{
+    assets::{handle::Handle, Asset},
+    core::command_queue::AsyncTask,
+};
+
+/// A decoded asset, downcast by the apps receiving it (see `LoadedAsset::downcast_ref`).
+pub type LoadedValue = Arc<dyn Any + Send + Sync>;
+
+/// Decodes the received assets of a format (Ex: glb files into a `GlbModel`), off the main thread.
+///
+/// The asset server runs the loader of every asset it receives, chosen by the type the asset was
+/// requested as and otherwise by its extension, and sends the result as a `CommandEvent::LoadedAsset`.
+/// New formats (Ex: audio, JSON data, scenes) only need a loader registered with `AssetServer::register_loader`
+/// or `EngineBuilder::asset_loader`, and an app downcasting what it outputs.
+///
+/// # Example
+///
+/// ```ignore
+/// struct WavLoader;
+///
+/// impl AssetLoader for WavLoader {
+///     fn asset_type(&self) -> &str {
+///         "audio"
+///     }
+///
+///     fn extensions(&self) -> &[&str] {
+///         &["wav"]
+///     }
+///
+///     fn load(&self, asset: Asset) -> AsyncTask<anyhow::Result<LoadedValue>> {
+///         Box::pin(async move { Ok(Arc::new(Sound::from_wav(&asset.data)?) as LoadedValue) })
+///     }
+/// }
+/// ```
+pub trait AssetLoader: Send + Sync {
+    /// Name of the type of asset it outputs (Ex: `font`, or a type unknown to `AssetType` like `audio`).
+    fn asset_type(&self) -> &str;
+
+    /// Extensions of the files it reads, in lowercase without the dot (Ex: `["ttf", "otf"]`).
+    fn extensions(&self) -> &[&str];
+
+    /// Decodes `asset`, ran as a command task.
+    fn load(&self, asset: Asset) -> AsyncTask<anyhow::Result<LoadedValue>>;
+}
+
+/// An asset decoded by an `AssetLoader`, received as a `CommandEvent::LoadedAsset`.
+#[derive(Clone)]
+pub struct LoadedAsset {
+    pub path: String,
+    /// Keeps the asset alive until the event is handled, the key of its decoded copies (Ex: `Handle<SunModel>`).
+    pub handle: Handle<Asset>,
+    /// `AssetLoader::asset_type` of the loader that decoded it.
+    pub asset_type: String,
+    value: LoadedValue,
+}
+
+impl LoadedAsset {
+    pub fn new(handle: Handle<Asset>, asset_type: &str, value: LoadedValue) -> Self {
+        LoadedAsset {
+            path: handle.path().to_owned(),
+            handle,
+            asset_type: asset_type.to_owned(),
+            value,
+        }
+    }
+
+    /// The decoded asset, if it is a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+}
+
+impl std::fmt::Debug for LoadedAsset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoadedAsset")
+            .field("path", &self.path)
+            .field("asset_type", &self.asset_type)
+            .finish()
+    }
+}
+
+/// The loaders of an asset server, the last one registered for a type or an extension takes precedence.
+#[derive(Clone, Default)]
+pub struct AssetLoaders {
+    loaders: Vec<Arc<dyn AssetLoader>>,
+}
+
+impl AssetLoaders {
+    /// The loaders of the formats the engine reads: wgsl shaders, fonts, glb models and text.
+    pub fn builtin() -> Self {
+        use crate::renderer::loaders::{FontLoader, GlbLoader, WgslLoader};
+
+        let mut loaders = AssetLoaders::default();
+        loaders.register(TextLoader);
+        loaders.register(WgslLoader);
+        loaders.register(FontLoader);
+        loaders.register(GlbLoader);
+
+        loaders
+    }
+
+    pub fn register(&mut self, loader: impl AssetLoader + 'static) {
+        self.loaders.push(Arc::new(loader));
+    }
+
+    pub fn register_arc(&mut self, loader: Arc<dyn AssetLoader>) {
+        self.loaders.push(loader);
+    }
+
+    /// The loader of `asset`: the one outputting the type it was requested as, otherwise the one reading its extension.
+    pub fn find(&self, asset: &Asset) -> Option<Arc<dyn AssetLoader>> {
+        let asset_type = asset.asset_type.as_str();
+        let extension = asset
+            .path
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let by_type = self
+            .loaders
+            .iter()
+            .rev()
+            .find(|loader| loader.asset_type() == asset_type);
+        let by_extension = || {
+            self.loaders
+                .iter()
+                .rev()
+                .find(|loader| loader.extensions().contains(&extension.as_str()))
+        };
+
+        by_type.or_else(by_extension).cloned()
+    }
+
+    /// One `<type>: <extensions>` line per loader, in the order they were registered.
+    pub fn describe(&self) -> String {
+        self.loaders
+            .iter()
+            .map(|loader| format!("{}: {}", loader.asset_type(), loader.extensions().join(" ")))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Reads text files (Ex: JSON data) as a `String`.
+pub struct TextLoader;
+
+impl AssetLoader for TextLoader {
+    fn asset_type(&self) -> &str {
+        "string"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["txt", "json", "cfg", "toml", "md"]
+    }
+
+    fn load(&self, asset: Asset) -> AsyncTask<anyhow::Result<LoadedValue>> {
+        Box::pin(async move { Ok(Arc::new(String::from_utf8(asset.data)?) as LoadedValue) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::{AssetStatus, AssetType};
+
+    fn asset(path: &str, asset_type: AssetType) -> Asset {
+        Asset {
+            asset_type,
+            status: AssetStatus::Ready,
+            data: vec![],
+            name: path.to_owned(),
+            path: path.to_owned(),
+        }
+    }
+
+    fn found(loaders: &AssetLoaders, asset: &Asset) -> Option<String> {
+        loaders
+            .find(asset)
+            .map(|loader| loader.asset_type().to_owned())
+    }
+
+    #[test]
+    fn finds_the_loader_of_the_requested_type_first() {
+        let loaders = AssetLoaders::builtin();
+
+        let shader_in_text_file = asset("shaders/basic.txt", AssetType::Shader);
+        assert_eq!(
+            found(&loaders, &shader_in_text_file).as_deref(),
+            Some("shader")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_extension() {
+        let loaders = AssetLoaders::builtin();
+
+        let model = asset("models/Duck.GLB", AssetType::Unknown);
+        assert_eq!(found(&loaders, &model).as_deref(), Some("model"));
+        let texture_named_json = asset("data/level.json", AssetType::Texture);
+        assert_eq!(
+            found(&loaders, &texture_named_json).as_deref(),
+            Some("string")
+        );
+    }
+
+    #[test]
+    fn finds_no_loader_for_unknown_formats() {
+        let loaders = AssetLoaders::builtin();
+
+        assert!(loaders
+            .find(&asset("sounds/hit.wav", AssetType::Unknown))
+            .is_none());
+        assert!(loaders.find(&asset("README", AssetType::Unknown)).is_none());
+        assert!(AssetLoaders::default()
+            .find(&asset("shaders/basic.wgsl", AssetType::Shader))
+            .is_none());
+    }
+}
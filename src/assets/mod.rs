@@ -1,5 +1,12 @@
 pub mod asset_cmd;
 pub mod asset_server;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod file_server;
+pub mod handle;
+pub mod loader;
+pub mod protocol;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watcher;
 
 #[derive(Debug, Clone)]
 pub struct Asset {
@@ -23,6 +30,62 @@ pub enum AssetType {
     //...model, texture, audio...
 }
 
+impl AssetType {
+    /// Every asset type but `Unknown`.
+    pub const ALL: [AssetType; 7] = [
+        AssetType::String,
+        AssetType::Shader,
+        AssetType::Texture,
+        AssetType::Font,
+        AssetType::Mesh,
+        AssetType::Material,
+        AssetType::Model,
+    ];
+
+    /// Name of the type in commands (Ex: `asset_server get shaders/default.wgsl shader`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AssetType::String => "string",
+            AssetType::Shader => "shader",
+            AssetType::Texture => "texture",
+            AssetType::Font => "font",
+            AssetType::Mesh => "mesh",
+            AssetType::Material => "material",
+            AssetType::Model => "model",
+            AssetType::Unknown => "unknown",
+        }
+    }
+
+    /// The type named `name` in any case, `Unknown` if there is none.
+    pub fn from_name(name: &str) -> Self {
+        AssetType::ALL
+            .into_iter()
+            .find(|asset_type| asset_type.as_str().eq_ignore_ascii_case(name))
+            .unwrap_or(AssetType::Unknown)
+    }
+
+    /// Code of the type in the asset protocol, see `protocol::Request`.
+    pub fn code(&self) -> u8 {
+        match self {
+            AssetType::Unknown => 0,
+            AssetType::String => 1,
+            AssetType::Shader => 2,
+            AssetType::Texture => 3,
+            AssetType::Font => 4,
+            AssetType::Mesh => 5,
+            AssetType::Material => 6,
+            AssetType::Model => 7,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Self {
+        AssetType::ALL
+            .into_iter()
+            .find(|asset_type| asset_type.code() == code)
+            .unwrap_or(AssetType::Unknown)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum AssetStatus {
     Ready,
@@ -0,0 +1,871 @@
+use thiserror::Error;
+
+use crate::assets::AssetType;
+
+/// First bytes of the handshake of both sides.
+pub const PROTOCOL_MAGIC: [u8; 4] = *b"SNAS";
+
+/// Wire protocol between the asset server and its clients.
+///
+/// Every message is a frame: a `u32` payload length followed by the payload.
+/// Over a WebSocket, each binary message holds exactly one frame.
+/// Numbers are big-endian, strings are UTF-8.
+///
+/// ```text
+/// Handshake, the first frame each way:
+///   client: "SNAS" | u16 version
+///   server: "SNAS" | u16 version | u8 status (Ok, or UnsupportedVersion before closing)
+///
+/// Requests (client to server), any number per connection:
+///   u32 id | u8 kind (1 get, 2 changed) | u8 asset type | u16 path length | path
+///
+/// Responses (server to client), one per request, in the order of the requests:
+///   u32 id | u8 status | u8 content type | u64 body size | body
+/// ```
+///
+/// The path of a `changed` request is the id of the client, the same for all its connections
+/// (Ex: a random one picked when the engine starts). The body of its response lists the changed paths, one per line.
+/// The body of a failed request is the error message.
+///
+/// Both sides of a connection need to speak the same version.
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// Largest frame accepted, in bytes (Ex: to not allocate a corrupted length).
+pub const MAX_FRAME_SIZE: u32 = 512 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum ProtocolError {
+    #[error("Asset protocol io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("not an asset server (bad handshake)")]
+    BadMagic,
+    #[error("asset protocol version {theirs} is not supported, this build speaks version {ours}")]
+    UnsupportedVersion { ours: u16, theirs: u16 },
+    #[error("truncated message")]
+    Truncated,
+    #[error("frame of {0} bytes is too large")]
+    FrameTooLarge(u64),
+    #[error("unknown status code {0}")]
+    UnknownStatus(u8),
+    #[error("unknown request kind {0}")]
+    UnknownRequest(u8),
+    #[error("invalid UTF-8 in path")]
+    InvalidPath,
+    #[error("path of {0} bytes is too long")]
+    PathTooLong(usize),
+    #[error("expected the response to request {expected}, got {got}")]
+    UnexpectedResponse { expected: u32, got: u32 },
+    #[error("connection closed before the response")]
+    Closed,
+    #[error("websocket error: {0}")]
+    WebSocket(String),
+}
+
+/// Outcome of a request, sent in every response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok = 0,
+    NotFound = 1,
+    BadRequest = 2,
+    ServerError = 3,
+    UnsupportedVersion = 4,
+}
+
+impl Status {
+    pub fn from_code(code: u8) -> Result<Self, ProtocolError> {
+        Ok(match code {
+            0 => Status::Ok,
+            1 => Status::NotFound,
+            2 => Status::BadRequest,
+            3 => Status::ServerError,
+            4 => Status::UnsupportedVersion,
+            _ => return Err(ProtocolError::UnknownStatus(code)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    /// The content of the asset at the request path.
+    Get = 1,
+    /// The paths of the assets that changed since the previous `Changed` request with the same client id.
+    Changed = 2,
+}
+
+impl RequestKind {
+    pub fn from_code(code: u8) -> Result<Self, ProtocolError> {
+        match code {
+            1 => Ok(RequestKind::Get),
+            2 => Ok(RequestKind::Changed),
+            _ => Err(ProtocolError::UnknownRequest(code)),
+        }
+    }
+}
+
+/// First frame sent by each side of a connection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hello {
+    pub version: u16,
+    /// Only sent by the server, `Status::UnsupportedVersion` if it does not speak the client's version.
+    pub status: Option<Status>,
+}
+
+impl Hello {
+    /// The handshake of a client speaking `PROTOCOL_VERSION`.
+    pub fn client() -> Self {
+        Hello {
+            version: PROTOCOL_VERSION,
+            status: None,
+        }
+    }
+
+    /// The answer of a server to the handshake of a client.
+    pub fn reply_to(client: &Hello) -> Self {
+        Hello {
+            version: PROTOCOL_VERSION,
+            status: Some(if client.version == PROTOCOL_VERSION {
+                Status::Ok
+            } else {
+                Status::UnsupportedVersion
+            }),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = PROTOCOL_MAGIC.to_vec();
+        payload.extend_from_slice(&self.version.to_be_bytes());
+        if let Some(status) = self.status {
+            payload.push(status as u8);
+        }
+
+        payload
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Self, ProtocolError> {
+        let mut reader = Reader::new(payload);
+        if reader.bytes(PROTOCOL_MAGIC.len())? != PROTOCOL_MAGIC {
+            return Err(ProtocolError::BadMagic);
+        }
+
+        let version = reader.u16()?;
+        let status = match reader.is_empty() {
+            true => None,
+            false => Some(Status::from_code(reader.u8()?)?),
+        };
+
+        Ok(Hello { version, status })
+    }
+
+    /// Checks the handshake received by a client.
+    pub fn check(&self) -> Result<(), ProtocolError> {
+        match self.status {
+            Some(Status::Ok) if self.version == PROTOCOL_VERSION => Ok(()),
+            _ => Err(ProtocolError::UnsupportedVersion {
+                ours: PROTOCOL_VERSION,
+                theirs: self.version,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    /// Chosen by the client, sent back in the response.
+    pub id: u32,
+    pub kind: RequestKind,
+    /// Type the client reads the asset as.
+    pub asset_type: AssetType,
+    /// Relative to the asset root of the server, the id of the client for `Changed`.
+    pub path: String,
+}
+
+impl Request {
+    /// # Returns
+    ///
+    /// `ProtocolError::PathTooLong` if the path does not fit in a u16 length.
+    pub fn encode(&self) -> Result<Vec<u8>, ProtocolError> {
+        let path = self.path.as_bytes();
+        let len = u16::try_from(path.len()).map_err(|_| ProtocolError::PathTooLong(path.len()))?;
+
+        let mut payload = Vec::with_capacity(8 + path.len());
+        payload.extend_from_slice(&self.id.to_be_bytes());
+        payload.push(self.kind as u8);
+        payload.push(self.asset_type.code());
+        payload.extend_from_slice(&len.to_be_bytes());
+        payload.extend_from_slice(path);
+
+        Ok(payload)
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Self, ProtocolError> {
+        let mut reader = Reader::new(payload);
+
+        let id = reader.u32()?;
+        let kind = RequestKind::from_code(reader.u8()?)?;
+        let asset_type = AssetType::from_code(reader.u8()?);
+        let len = reader.u16()? as usize;
+        let path = std::str::from_utf8(reader.bytes(len)?)
+            .map_err(|_| ProtocolError::InvalidPath)?
+            .to_owned();
+
+        Ok(Request {
+            id,
+            kind,
+            asset_type,
+            path,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// Id of the request this answers.
+    pub id: u32,
+    pub status: Status,
+    /// Type of the asset as known by the server, `AssetType::Unknown` if it cannot tell.
+    pub content_type: AssetType,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn ok(id: u32, content_type: AssetType, body: Vec<u8>) -> Self {
+        Response {
+            id,
+            status: Status::Ok,
+            content_type,
+            body,
+        }
+    }
+
+    /// A failed request, `message` tells why.
+    pub fn error(id: u32, status: Status, message: &str) -> Self {
+        Response {
+            id,
+            status,
+            content_type: AssetType::String,
+            body: message.as_bytes().to_vec(),
+        }
+    }
+
+    /// The body as text (Ex: the error message or the changed paths).
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(14 + self.body.len());
+        payload.extend_from_slice(&self.id.to_be_bytes());
+        payload.push(self.status as u8);
+        payload.push(self.content_type.code());
+        payload.extend_from_slice(&(self.body.len() as u64).to_be_bytes());
+        payload.extend_from_slice(&self.body);
+
+        payload
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Self, ProtocolError> {
+        let mut reader = Reader::new(payload);
+
+        let id = reader.u32()?;
+        let status = Status::from_code(reader.u8()?)?;
+        let content_type = AssetType::from_code(reader.u8()?);
+        let size = reader.u64()?;
+        if size > MAX_FRAME_SIZE as u64 {
+            return Err(ProtocolError::FrameTooLarge(size));
+        }
+        let body = reader.bytes(size as usize)?.to_vec();
+
+        Ok(Response {
+            id,
+            status,
+            content_type,
+            body,
+        })
+    }
+}
+
+/// Prefixes `payload` with its length.
+///
+/// # Returns
+///
+/// `ProtocolError::FrameTooLarge` if `payload` is larger than `MAX_FRAME_SIZE`, the other side would reject it.
+pub fn frame(payload: &[u8]) -> Result<Vec<u8>, ProtocolError> {
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_SIZE)
+        .ok_or(ProtocolError::FrameTooLarge(payload.len() as u64))?;
+
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(payload);
+
+    Ok(frame)
+}
+
+/// The payload of a whole frame (Ex: a WebSocket message).
+pub fn unframe(frame: &[u8]) -> Result<&[u8], ProtocolError> {
+    let mut reader = Reader::new(frame);
+    let len = reader.u32()?;
+    if len > MAX_FRAME_SIZE {
+        return Err(ProtocolError::FrameTooLarge(len as u64));
+    }
+
+    reader.bytes(len as usize)
+}
+
+/// Reads the next frame of a stream.
+///
+/// # Returns
+///
+/// The payload of the frame, none if the stream was closed between two frames.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn read_frame<R: async_std::io::Read + Unpin>(
+    reader: &mut R,
+) -> Result<Option<Vec<u8>>, ProtocolError> {
+    use async_std::io::ReadExt;
+
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_SIZE {
+        return Err(ProtocolError::FrameTooLarge(len as u64));
+    }
+
+    let mut payload = vec![0; len as usize];
+    reader
+        .read_exact(&mut payload)
+        .await
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => ProtocolError::Truncated,
+            _ => err.into(),
+        })?;
+
+    Ok(Some(payload))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn write_frame<W: async_std::io::Write + Unpin>(
+    writer: &mut W,
+    payload: &[u8],
+) -> Result<(), ProtocolError> {
+    use async_std::io::WriteExt;
+
+    writer.write_all(&frame(payload)?).await?;
+    writer.flush().await?;
+
+    Ok(())
+}
+
+/// A client connection to an asset server, past its handshake.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut connection = AssetConnection::connect("127.0.0.1:8080").await?;
+/// let shader = connection.request(RequestKind::Get, "shaders/default.wgsl", AssetType::Shader).await?;
+/// let changed = connection.request(RequestKind::Changed, "", AssetType::String).await?;
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub struct AssetConnection {
+    stream: async_std::net::TcpStream,
+    address: String,
+    next_id: u32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AssetConnection {
+    pub async fn connect(address: &str) -> Result<Self, ProtocolError> {
+        let mut stream = async_std::net::TcpStream::connect(address).await?;
+
+        write_frame(&mut stream, &Hello::client().encode()).await?;
+        let hello = read_frame(&mut stream)
+            .await?
+            .ok_or(ProtocolError::Truncated)?;
+        Hello::decode(&hello)?.check()?;
+
+        Ok(AssetConnection {
+            stream,
+            address: address.to_owned(),
+            next_id: 0,
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Sends a request without waiting for its response, see `receive`.
+    ///
+    /// # Returns
+    ///
+    /// The id of the request.
+    pub async fn send(
+        &mut self,
+        kind: RequestKind,
+        path: &str,
+        asset_type: AssetType,
+    ) -> Result<u32, ProtocolError> {
+        self.next_id = self.next_id.wrapping_add(1);
+
+        let request = Request {
+            id: self.next_id,
+            kind,
+            asset_type,
+            path: path.to_owned(),
+        };
+        write_frame(&mut self.stream, &request.encode()?).await?;
+
+        Ok(request.id)
+    }
+
+    /// The response to the oldest request that was not answered yet.
+    pub async fn receive(&mut self) -> Result<Response, ProtocolError> {
+        let payload = read_frame(&mut self.stream)
+            .await?
+            .ok_or(ProtocolError::Truncated)?;
+
+        Response::decode(&payload)
+    }
+
+    /// Sends a request and waits for its response.
+    pub async fn request(
+        &mut self,
+        kind: RequestKind,
+        path: &str,
+        asset_type: AssetType,
+    ) -> Result<Response, ProtocolError> {
+        let id = self.send(kind, path, asset_type).await?;
+        let response = self.receive().await?;
+
+        if response.id != id {
+            return Err(ProtocolError::UnexpectedResponse {
+                expected: id,
+                got: response.id,
+            });
+        }
+
+        Ok(response)
+    }
+}
+
+/// Idle connections to asset servers, reused by the next requests.
+/// Cloning `AssetConnections` gives another handle to the same connections.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
+pub struct AssetConnections {
+    idle: std::sync::Arc<std::sync::Mutex<Vec<AssetConnection>>>,
+    client_id: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for AssetConnections {
+    fn default() -> Self {
+        AssetConnections {
+            idle: Default::default(),
+            client_id: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AssetConnections {
+    /// The id sent in `Changed` requests, the asset server tracks the changes per client id.
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Sends a request on an idle connection to `address`, or on a new one if there is none.
+    /// A reused connection the server closed in the meantime is replaced by a new one.
+    pub async fn request(
+        &self,
+        address: &str,
+        kind: RequestKind,
+        path: &str,
+        asset_type: AssetType,
+    ) -> Result<Response, ProtocolError> {
+        if let Some(mut connection) = self.take(address) {
+            if let Ok(response) = connection.request(kind, path, asset_type.clone()).await {
+                self.give_back(connection);
+                return Ok(response);
+            }
+        }
+
+        let mut connection = AssetConnection::connect(address).await?;
+        let response = connection.request(kind, path, asset_type).await?;
+        self.give_back(connection);
+
+        Ok(response)
+    }
+
+    /// Closes the idle connections.
+    pub fn clear(&self) {
+        self.idle.lock().unwrap().clear();
+    }
+
+    fn take(&self, address: &str) -> Option<AssetConnection> {
+        let mut idle = self.idle.lock().unwrap();
+        let index = idle
+            .iter()
+            .position(|connection| connection.address == address)?;
+
+        Some(idle.swap_remove(index))
+    }
+
+    fn give_back(&self, connection: AssetConnection) {
+        self.idle.lock().unwrap().push(connection);
+    }
+}
+
+/// Callback of a request sent over a WebSocket, called once with its response.
+#[cfg(target_arch = "wasm32")]
+type OnResponse = Box<dyn FnOnce(Result<Response, ProtocolError>)>;
+
+/// WebSockets to asset servers, one per address, shared by every request.
+/// Requests sent before a socket is open are queued, responses are matched to their request by id.
+/// Cloning `AssetConnections` gives another handle to the same sockets.
+#[cfg(target_arch = "wasm32")]
+#[derive(Clone)]
+pub struct AssetConnections {
+    sockets: std::rc::Rc<std::cell::RefCell<std::collections::HashMap<String, WsConnection>>>,
+    client_id: String,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Default for AssetConnections {
+    fn default() -> Self {
+        AssetConnections {
+            sockets: Default::default(),
+            client_id: format!("{:016x}", (js_sys::Math::random() * u64::MAX as f64) as u64),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+type WsConnection = std::rc::Rc<std::cell::RefCell<WsState>>;
+
+#[cfg(target_arch = "wasm32")]
+struct WsState {
+    ws: web_sys::WebSocket,
+    handshake_done: bool,
+    next_id: u32,
+    /// Frames waiting for the socket to open, the handshake first.
+    queued: Vec<Vec<u8>>,
+    /// Requests waiting for their response, by id.
+    pending: std::collections::HashMap<u32, OnResponse>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl AssetConnections {
+    /// The id sent in `Changed` requests, the asset server tracks the changes per client id.
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Sends a request on the socket to `address`, opened if there is none or if the server closed it.
+    pub async fn request(
+        &self,
+        address: &str,
+        kind: RequestKind,
+        path: &str,
+        asset_type: AssetType,
+    ) -> Result<Response, ProtocolError> {
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        self.send(address, kind, path, asset_type, move |response| {
+            let _ = sender.send(response);
+        })?;
+
+        receiver.await.map_err(|_| ProtocolError::Closed)?
+    }
+
+    /// Closes the sockets, their pending requests fail.
+    pub fn clear(&self) {
+        for (_, connection) in self.sockets.borrow_mut().drain() {
+            let _ = connection.borrow().ws.close();
+        }
+    }
+
+    fn send(
+        &self,
+        address: &str,
+        kind: RequestKind,
+        path: &str,
+        asset_type: AssetType,
+        on_response: impl FnOnce(Result<Response, ProtocolError>) + 'static,
+    ) -> Result<(), ProtocolError> {
+        let connection = self.connection(address)?;
+        let mut state = connection.borrow_mut();
+
+        state.next_id = state.next_id.wrapping_add(1);
+        let request = Request {
+            id: state.next_id,
+            kind,
+            asset_type,
+            path: path.to_owned(),
+        };
+        let frame = frame(&request.encode()?)?;
+
+        if state.ws.ready_state() == web_sys::WebSocket::OPEN {
+            state
+                .ws
+                .send_with_u8_array(&frame)
+                .map_err(|err| ProtocolError::WebSocket(format!("{err:?}")))?;
+        } else {
+            state.queued.push(frame);
+        }
+        state.pending.insert(request.id, Box::new(on_response));
+
+        Ok(())
+    }
+
+    fn connection(&self, address: &str) -> Result<WsConnection, ProtocolError> {
+        let mut sockets = self.sockets.borrow_mut();
+        if let Some(connection) = sockets.get(address) {
+            if connection.borrow().ws.ready_state() <= web_sys::WebSocket::OPEN {
+                return Ok(connection.clone());
+            }
+        }
+
+        let connection = WsState::open(address)?;
+        sockets.insert(address.to_owned(), connection.clone());
+
+        Ok(connection)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WsState {
+    /// Opens a socket to `address`, its handshake is sent once open.
+    fn open(address: &str) -> Result<WsConnection, ProtocolError> {
+        use wasm_bindgen::prelude::*;
+
+        let url = format!("wss://{address}");
+        let ws = web_sys::WebSocket::new(&url)
+            .map_err(|err| ProtocolError::WebSocket(format!("{err:?}")))?;
+        ws.set_binary_type(web_sys::BinaryType::Arraybuffer);
+
+        let connection = std::rc::Rc::new(std::cell::RefCell::new(WsState {
+            ws: ws.clone(),
+            handshake_done: false,
+            next_id: 0,
+            queued: vec![frame(&Hello::client().encode())?],
+            pending: Default::default(),
+        }));
+
+        // The callbacks only keep a weak reference, the socket owns them
+        let weak = std::rc::Rc::downgrade(&connection);
+        let onopen = Closure::<dyn FnMut()>::new(move || {
+            let Some(connection) = weak.upgrade() else {
+                return;
+            };
+            let mut state = connection.borrow_mut();
+            for frame in std::mem::take(&mut state.queued) {
+                if let Err(err) = state.ws.send_with_u8_array(&frame) {
+                    let err = ProtocolError::WebSocket(format!("{err:?}"));
+                    drop(state);
+                    WsState::fail(&connection, err);
+                    return;
+                }
+            }
+        });
+        ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+        onopen.forget();
+
+        let weak = std::rc::Rc::downgrade(&connection);
+        let onmessage = Closure::<dyn FnMut(_)>::new(move |e: web_sys::MessageEvent| {
+            let Some(connection) = weak.upgrade() else {
+                return;
+            };
+            let Ok(buffer) = e.data().dyn_into::<js_sys::ArrayBuffer>() else {
+                tracing::debug!("message event, received Unknown: {:?}", e.data());
+                return;
+            };
+            let data = js_sys::Uint8Array::new(&buffer).to_vec();
+
+            let handshake_done = connection.borrow().handshake_done;
+            let result = unframe(&data).and_then(|payload| match handshake_done {
+                true => Response::decode(payload).map(Some),
+                false => Hello::decode(payload)?.check().map(|_| None),
+            });
+
+            match result {
+                Ok(Some(response)) => {
+                    let on_response = connection.borrow_mut().pending.remove(&response.id);
+                    match on_response {
+                        Some(on_response) => on_response(Ok(response)),
+                        None => tracing::warn!("Response to an unknown request {}", response.id),
+                    }
+                }
+                Ok(None) => connection.borrow_mut().handshake_done = true,
+                Err(err) => WsState::fail(&connection, err),
+            }
+        });
+        ws.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        onmessage.forget();
+
+        let weak = std::rc::Rc::downgrade(&connection);
+        let onclose = Closure::<dyn FnMut()>::new(move || {
+            if let Some(connection) = weak.upgrade() {
+                WsState::fail(&connection, ProtocolError::Closed);
+            }
+        });
+        ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+        onclose.forget();
+
+        Ok(connection)
+    }
+
+    /// Closes the socket, its pending requests fail with `err`.
+    fn fail(connection: &WsConnection, err: ProtocolError) {
+        let pending = {
+            let mut state = connection.borrow_mut();
+            let _ = state.ws.close();
+            state.queued.clear();
+            std::mem::take(&mut state.pending)
+        };
+
+        let message = err.to_string();
+        for (_, on_response) in pending {
+            on_response(Err(ProtocolError::WebSocket(message.clone())));
+        }
+    }
+}
+
+/// Reads the big-endian fields of a payload.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ProtocolError> {
+        if self.data.len() < len {
+            return Err(ProtocolError::Truncated);
+        }
+
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ProtocolError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ProtocolError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, ProtocolError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, ProtocolError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let client = Hello::client();
+        assert_eq!(Hello::decode(&client.encode()).unwrap(), client);
+
+        let server = Hello::reply_to(&client);
+        assert_eq!(Hello::decode(&server.encode()).unwrap(), server);
+        server.check().unwrap();
+
+        let request = Request {
+            id: 7,
+            kind: RequestKind::Get,
+            asset_type: AssetType::Shader,
+            path: "shaders/dé.wgsl".into(),
+        };
+        let payload = request.encode().unwrap();
+        assert_eq!(Request::decode(&payload).unwrap(), request);
+
+        let response = Response::ok(7, AssetType::Texture, vec![1, 2, 3]);
+        let framed = frame(&response.encode()).unwrap();
+        assert_eq!(
+            Response::decode(unframe(&framed).unwrap()).unwrap(),
+            response
+        );
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        let framed = frame(&Response::error(1, Status::NotFound, "missing").encode()).unwrap();
+        for len in [0, 3, framed.len() - 1] {
+            assert!(matches!(
+                unframe(&framed[..len]),
+                Err(ProtocolError::Truncated)
+            ));
+        }
+
+        let oversized = (MAX_FRAME_SIZE + 1).to_be_bytes();
+        assert!(matches!(
+            unframe(&oversized),
+            Err(ProtocolError::FrameTooLarge(_))
+        ));
+
+        assert!(matches!(
+            Hello::decode(b"HTTP/1.1"),
+            Err(ProtocolError::BadMagic)
+        ));
+
+        let mut response = Response::ok(1, AssetType::String, vec![]).encode();
+        response[4] = 42;
+        assert!(matches!(
+            Response::decode(&response),
+            Err(ProtocolError::UnknownStatus(42))
+        ));
+
+        let mut request = Request {
+            id: 1,
+            kind: RequestKind::Changed,
+            asset_type: AssetType::String,
+            path: String::new(),
+        }
+        .encode()
+        .unwrap();
+        request[4] = 9;
+        assert!(matches!(
+            Request::decode(&request),
+            Err(ProtocolError::UnknownRequest(9))
+        ));
+    }
+
+    #[test]
+    fn oversized_paths_are_not_sent() {
+        let request = Request {
+            id: 1,
+            kind: RequestKind::Get,
+            asset_type: AssetType::String,
+            path: "é".repeat(u16::MAX as usize / 2 + 1),
+        };
+
+        assert!(matches!(
+            request.encode(),
+            Err(ProtocolError::PathTooLong(len)) if len == u16::MAX as usize + 1
+        ));
+    }
+}
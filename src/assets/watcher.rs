@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, SystemTime},
+};
+
+use tracing::{debug, error};
+
+use crate::core::{events::CommandEvent, proxy::EngineProxy};
+
+/// How often the watched files are checked, short enough for an edit to show up within a couple of frames.
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_millis(30);
+
+/// Last known modification time of each watched file, by full path, with the asset path it was requested as.
+type WatchedFiles = HashMap<PathBuf, (String, Option<SystemTime>)>;
+
+/// Watches the files of the assets read in local mode and sends a `CommandEvent::ChangedAssets`
+/// as soon as some of them are modified, so the asset server reads them again.
+///
+/// The files are polled for their modification time by a background thread,
+/// which stops once the watcher is dropped or the engine exits.
+///
+/// # Example
+///
+/// ```ignore
+/// let watcher = AssetWatcher::start(proxy, DEFAULT_WATCH_INTERVAL);
+/// watcher.watch("assets/shaders/default.wgsl".into(), "shaders/default.wgsl");
+/// ```
+pub struct AssetWatcher {
+    files: Arc<Mutex<WatchedFiles>>,
+}
+
+impl AssetWatcher {
+    /// Starts polling the watched files every `interval`, the changes are sent through `proxy`.
+    pub fn start(proxy: EngineProxy, interval: Duration) -> Self {
+        let files = Arc::new(Mutex::new(WatchedFiles::new()));
+        let watched = Arc::downgrade(&files);
+
+        let spawned = std::thread::Builder::new()
+            .name("Asset Watcher".into())
+            .spawn(move || {
+                while proxy.is_running() {
+                    std::thread::sleep(interval);
+
+                    let Some(changed) = poll(&watched) else {
+                        break;
+                    };
+                    if changed.is_empty() {
+                        continue;
+                    }
+
+                    debug!("Modified assets: {changed:?}");
+                    if proxy
+                        .send_event(CommandEvent::ChangedAssets(changed))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+
+        if let Err(err) = spawned {
+            error!("Could not spawn the asset watcher thread: {err}");
+        }
+
+        AssetWatcher { files }
+    }
+
+    /// Watches the file at `full_path`, reported as changed under `asset_path`.
+    /// Its current modification time is the reference, watching a file again resets it.
+    pub fn watch(&self, full_path: PathBuf, asset_path: &str) {
+        let modified = modified(&full_path);
+        self.files
+            .lock()
+            .unwrap()
+            .insert(full_path, (asset_path.to_owned(), modified));
+    }
+
+    pub fn unwatch(&self, full_path: &Path) {
+        self.files.lock().unwrap().remove(full_path);
+    }
+}
+
+/// The asset paths of the watched files modified since the last poll, none once the watcher is dropped.
+fn poll(watched: &Weak<Mutex<WatchedFiles>>) -> Option<Vec<String>> {
+    let files = watched.upgrade()?;
+    let mut files = files.lock().unwrap();
+
+    let mut changed = vec![];
+    for (full_path, (asset_path, known)) in files.iter_mut() {
+        let modified = modified(full_path);
+
+        // A file being replaced can briefly be missing, it is reported once it is back
+        if modified.is_some() && modified != *known {
+            changed.push(asset_path.clone());
+        }
+        *known = modified;
+    }
+
+    Some(changed)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,34 @@
+/// Serves a folder to engines in server mode (`asset_mode = server`, `asset_address = <address>`).
+///
+/// Usage: `sunset-asset-server [--root <folder>] [--address <host:port>] [--log-level <level>]`,
+/// serving `assets/` on `127.0.0.1:8080` by default.
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use sunset::{assets::file_server::AssetFileServer, core::script::flag_values};
+
+    let flag = |flag: &str, default: &str| {
+        flag_values(flag)
+            .pop()
+            .unwrap_or_else(|| default.to_owned())
+    };
+
+    let root = flag("--root", "assets/");
+    let address = flag("--address", "127.0.0.1:8080");
+    let level = flag("--log-level", "info")
+        .parse()
+        .unwrap_or(tracing::Level::INFO);
+
+    tracing_subscriber::fmt().with_max_level(level).init();
+
+    let result = async_std::task::block_on(async {
+        AssetFileServer::bind(&address, root).await?.run().await
+    });
+
+    if let Err(err) = result {
+        tracing::error!("Asset server stopped: {err}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,327 @@
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::core::{
+    command_queue::Command,
+    command_registry::{join_args, split_args},
+};
+
+/// File the aliases are kept in across runs when the config does not say otherwise.
+pub const DEFAULT_ALIASES_FILE: &str = "sunset.aliases";
+
+/// Number of aliases an alias can go through before its expansion is considered a loop.
+pub const MAX_ALIAS_DEPTH: usize = 16;
+
+#[derive(Error, Debug)]
+pub enum AliasError {
+    #[error("Alias io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("line {line}: {reason}")]
+    Invalid { line: usize, reason: String },
+    #[error("invalid alias name \"{0}\", expected a single word without '$' or '='")]
+    InvalidName(String),
+    #[error("alias {alias} expects a value for ${index}")]
+    MissingArgument { alias: String, index: usize },
+    #[error("alias {0} expands to itself")]
+    Recursive(String),
+}
+
+/// Named CLI lines, run by typing their name followed by their parameters
+/// (Ex: `cli alias cam3d = default_scene add --name $1 --camera 3D 1.8 45.0 0 0 4 0.0001 1000`, then `cam3d Main`).
+///
+/// In the aliased line, `$1`..`$9` are replaced by the parameters and `$*` by all of them.
+/// A line without any `$` gets the parameters appended instead.
+/// An app inserted under the same name as an alias takes precedence over it.
+///
+/// Every change is saved to the aliases file, if any, one `<name> = <line>` per line.
+#[derive(Debug, Default)]
+pub struct Aliases {
+    aliases: BTreeMap<String, String>,
+    path: Option<String>,
+}
+
+impl Aliases {
+    /// Aliases kept in the file at `path`, the file is created by the first alias defined.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &str) -> Result<Self, AliasError> {
+        let aliases = match std::fs::read_to_string(path) {
+            Ok(text) => Aliases::parse(&text)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Aliases {
+            aliases,
+            path: Some(path.to_owned()),
+        })
+    }
+
+    /// Parses the `<name> = <line>` lines of an aliases file, lines starting with '#' are ignored.
+    pub fn parse(text: &str) -> Result<BTreeMap<String, String>, AliasError> {
+        let mut aliases = BTreeMap::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |reason: String| AliasError::Invalid {
+                line: i + 1,
+                reason,
+            };
+
+            let (name, aliased) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected <name> = <line>".into()))?;
+            let name = name.trim();
+            check_name(name).map_err(|err| invalid(err.to_string()))?;
+
+            aliases.insert(name.to_owned(), aliased.trim().to_owned());
+        }
+
+        Ok(aliases)
+    }
+
+    /// Defines (or redefines) the alias `name` running `line`.
+    pub fn define(&mut self, name: &str, line: &str) -> Result<(), AliasError> {
+        check_name(name)?;
+
+        self.aliases.insert(name.to_owned(), line.trim().to_owned());
+        self.save()
+    }
+
+    /// # Returns
+    ///
+    /// The line of the removed alias, if `name` was defined.
+    pub fn remove(&mut self, name: &str) -> Result<Option<String>, AliasError> {
+        let removed = self.aliases.remove(name);
+        if removed.is_some() {
+            self.save()?;
+        }
+
+        Ok(removed)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.aliases.get(name).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// Path of the file the aliases are saved to, if any.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Replaces an aliased command by the command its alias runs, going through the aliases it uses.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - A command that was not processed yet, its `app` is the alias name and its `args` the parameters
+    /// * `is_app` - Whether an app is inserted under the given name, apps are never expanded
+    ///
+    /// # Returns
+    ///
+    /// `cmd` unchanged if it does not use an alias.
+    pub fn expand(
+        &self,
+        mut cmd: Command,
+        is_app: impl Fn(&str) -> bool,
+    ) -> Result<Command, AliasError> {
+        for _ in 0..MAX_ALIAS_DEPTH {
+            let Some(aliased) = self.aliases.get(&cmd.app).filter(|_| !is_app(&cmd.app)) else {
+                return Ok(cmd);
+            };
+
+            let params = split_args(cmd.args.as_deref().unwrap_or_default());
+            let line = substitute(&cmd.app, aliased, &params)?;
+
+            let (app, args) = line.split_once(' ').unwrap_or((&line, ""));
+            cmd.app = app.to_owned();
+            cmd.args = Some(args.trim().to_owned());
+        }
+
+        Err(AliasError::Recursive(cmd.app))
+    }
+
+    /// One `<name> = <line>` per alias, sorted by name.
+    pub fn describe(&self) -> String {
+        self.aliases
+            .iter()
+            .map(|(name, line)| format!("{name} = {line}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn save(&self) -> Result<(), AliasError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &self.path {
+            let text = format!(
+                "# Aliases defined with `cli alias <name> = <line>`\n{}\n",
+                self.describe()
+            );
+            std::fs::write(path, text)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check_name(name: &str) -> Result<(), AliasError> {
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '$' || c == '=') {
+        return Err(AliasError::InvalidName(name.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Replaces `$1`..`$9` and `$*` in `line` by `params`, or appends them if `line` has no parameter.
+/// The parameters are quoted again if needed (Ex: `"My Cube"`), see `join_args`.
+fn substitute(alias: &str, line: &str, params: &[String]) -> Result<String, AliasError> {
+    if !line.contains('$') {
+        if params.is_empty() {
+            return Ok(line.to_owned());
+        }
+        return Ok(format!("{line} {}", join_args(params)));
+    }
+
+    let mut expanded = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('$', Some('*')) => {
+                chars.next();
+                expanded.push_str(&join_args(params));
+            }
+            ('$', Some(digit @ '1'..='9')) => {
+                let index = digit.to_digit(10).unwrap() as usize;
+                chars.next();
+
+                let param = params
+                    .get(index - 1)
+                    .ok_or_else(|| AliasError::MissingArgument {
+                        alias: alias.to_owned(),
+                        index,
+                    })?;
+                expanded.push_str(&join_args(&[param]));
+            }
+            (c, _) => expanded.push(c),
+        }
+    }
+
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::cli::parse_cli_line;
+
+    fn aliases(lines: &[(&str, &str)]) -> Aliases {
+        let mut aliases = Aliases::default();
+        for (name, line) in lines {
+            aliases.define(name, line).unwrap();
+        }
+
+        aliases
+    }
+
+    fn expand(aliases: &Aliases, line: &str) -> Result<(String, String), AliasError> {
+        let cmd = aliases.expand(parse_cli_line(line), |name| name == "default_scene")?;
+        Ok((cmd.app, cmd.args.unwrap_or_default()))
+    }
+
+    #[test]
+    fn parameters_are_substituted_or_appended() {
+        let aliases = aliases(&[
+            ("cube", "default_scene add --name $1 --model $2"),
+            ("add", "default_scene add"),
+            ("every", "default_scene add $*"),
+        ]);
+
+        assert_eq!(
+            expand(&aliases, "cube Duck models/duck.glb").unwrap(),
+            (
+                "default_scene".into(),
+                "add --name Duck --model models/duck.glb".into()
+            )
+        );
+        assert_eq!(
+            expand(&aliases, "add --name Cube").unwrap(),
+            ("default_scene".into(), "add --name Cube".into())
+        );
+        assert_eq!(
+            expand(&aliases, "every --name Cube").unwrap(),
+            ("default_scene".into(), "add --name Cube".into())
+        );
+    }
+
+    #[test]
+    fn quoted_parameters_stay_quoted() {
+        let aliases = aliases(&[("cube", "default_scene add --name $1")]);
+
+        assert_eq!(
+            expand(&aliases, "cube \"My Cube\"").unwrap(),
+            ("default_scene".into(), "add --name \"My Cube\"".into())
+        );
+    }
+
+    #[test]
+    fn aliases_expand_through_other_aliases_but_not_apps() {
+        let aliases = aliases(&[
+            ("duck", "cube Duck"),
+            ("cube", "default_scene add --name $1"),
+            ("default_scene", "cli time"),
+        ]);
+
+        assert_eq!(
+            expand(&aliases, "duck").unwrap(),
+            ("default_scene".into(), "add --name Duck".into())
+        );
+        assert_eq!(
+            expand(&aliases, "default_scene list").unwrap(),
+            ("default_scene".into(), "list".into())
+        );
+    }
+
+    #[test]
+    fn invalid_expansions_are_errors() {
+        let aliases = aliases(&[
+            ("cube", "default_scene add --name $2"),
+            ("ping", "pong"),
+            ("pong", "ping"),
+        ]);
+
+        assert!(matches!(
+            expand(&aliases, "cube Duck"),
+            Err(AliasError::MissingArgument { index: 2, .. })
+        ));
+        assert!(matches!(
+            expand(&aliases, "ping"),
+            Err(AliasError::Recursive(_))
+        ));
+        assert!(matches!(
+            Aliases::default().define("two words", "cli time"),
+            Err(AliasError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn alias_files_are_parsed() {
+        let parsed = Aliases::parse("# comment\n\ncube = default_scene add --name $1\n").unwrap();
+        assert_eq!(
+            parsed.get("cube").map(String::as_str),
+            Some("default_scene add --name $1")
+        );
+
+        assert!(matches!(
+            Aliases::parse("cube\n"),
+            Err(AliasError::Invalid { line: 1, .. })
+        ));
+    }
+}
@@ -1,10 +1,12 @@
 use std::any::Any;
 
-use tracing::{error, info};
-use winit::event_loop::EventLoopProxy;
-
-use super::events::CommandEvent;
-use crate::core::command_queue::*;
+use super::events::{CommandEvent, EventKind};
+use crate::core::{
+    command_queue::*,
+    command_registry::*,
+    command_result::{CommandFailure, CommandResult},
+    proxy::EngineProxy,
+};
 
 use async_trait::async_trait;
 
@@ -13,13 +15,28 @@ use async_trait::async_trait;
 pub trait App {
     fn get_name(&self) -> String;
 
+    /// Names of the apps (as inserted into `State`) this app has to run after.
+    /// Applies to `init`, `update` and every `process_*` call. Apps that are not inserted are ignored.
+    fn runs_after(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Apps without an ordering constraint between them run in ascending priority, then in insertion order.
+    fn priority(&self) -> i32 {
+        0
+    }
+
     /// Initializes the application with the provided initial commands.
     ///
     /// # Arguments
     ///
     /// * `init_commands` - A vector of `Command` objects representing the initial commands.
     ///
-    fn init(&mut self, elp: EventLoopProxy<CommandEvent>);
+    fn init(&mut self, elp: EngineProxy);
+
+    /// Tears the application down. Called when the engine exits (`CommandEvent::Exit`),
+    /// when the app is stopped or removed, and before it is restarted (followed by `init`).
+    fn shutdown(&mut self) {}
 
     /// Queues commands to be processed by the application duricng the current frame.
     ///
@@ -29,13 +46,32 @@ pub trait App {
     ///
     fn update(&mut self, delta_time: f32) -> Vec<Command>;
 
+    /// Advances the simulation of the application by one fixed tick of the engine `Clock`.
+    /// Runs zero or more times per frame, before `update`, and not at all while the clock is paused.
+    ///
+    /// # Returns
+    ///
+    /// A vector of `Command` objects representing the commands to be queued from the app.
+    ///
+    fn fixed_update(&mut self, _fixed_delta: f32) -> Vec<Command> {
+        vec![]
+    }
+
+    /// Declares the subcommands (and their arguments) this app accepts.
+    /// Commands are parsed and validated against these before reaching `process_command`,
+    /// and `<app> help` is generated from them.
+    fn commands(&self) -> Vec<CommandDesc> {
+        vec![]
+    }
+
     /// Processes a single command.
     ///
     /// # Arguments
     ///
     /// * `cmd` - The `Command` object to be processed (comes from the CLI App with only the argumets provided).
+    /// * `args` - The arguments of `cmd` parsed against the matching `CommandDesc` from `commands`.
     ///
-    async fn process_command(&mut self, cmd: Command);
+    async fn process_command(&mut self, cmd: Command, args: ParsedArgs);
 
     async fn process_window_event(
         &mut self,
@@ -45,6 +81,22 @@ pub trait App {
     ) {
     }
 
+    /// Kinds of `CommandEvent` handed to `process_user_event`, the other events never reach the app.
+    /// Read once when the app is inserted (or replaced).
+    ///
+    /// Defaults to `EventKind::All`, apps handling no event should return an empty list.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// fn subscriptions(&self) -> Vec<EventKind> {
+    ///     vec![EventKind::Asset, EventKind::of::<MyEvent>()]
+    /// }
+    /// ```
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![EventKind::All]
+    }
+
     async fn process_user_event(&mut self, _event: &CommandEvent, _delta_time: f32) {}
 
     async fn process_device_event(
@@ -55,13 +107,15 @@ pub trait App {
     ) {
     }
 
-    fn unsupported(args: &str) -> Option<Task<Vec<CommandEvent>>>
+    /// Task failing with `CommandFailure::Unsupported`, for the commands an app does not handle.
+    fn unsupported(args: &str) -> Option<Task<CommandResult>>
     where
         Self: Sized,
     {
-        error!("Unsupported arguments: {args}");
-        info!("type help for supported commands");
-        None
+        let args = args.to_owned();
+        Some(Box::new(move || {
+            Err(CommandFailure::Unsupported(args.clone()))
+        }))
     }
 
     /// Strips the App object from all of its implementaions and returns it as an Any object.
@@ -73,10 +127,10 @@ pub trait App {
     ///
     /// # Example
     ///
-    /// ```
+    /// ```ignore
     /// match event {
     ///     CommandEvent::OpenWindow(props) => {
-    ///         let mut state_lock = State::write();
+    ///         let mut state_lock = engine.write().await;
     ///         let windower = state_lock
     ///             .apps
     ///             .get_mut("windower")
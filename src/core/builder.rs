@@ -0,0 +1,170 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    assets::{asset_server::AssetServer, loader::AssetLoader},
+    core::{
+        app::App,
+        config::EngineConfig,
+        engine::{Engine, Startup},
+        script::Script,
+    },
+};
+
+/// Sets up an `Engine` for a host application: the config it starts with, which built-in apps it runs
+/// (see `default_apps`), the host's own apps and the commands to run once they are initialized.
+///
+/// Everything the engine needs (trace subscriber, runtimes, event loop) is set up when it runs.
+///
+/// # Example
+///
+/// ```ignore
+/// EngineBuilder::new()
+///     .builtin_apps(["windower", "sun", "asset_server"])
+///     .app("my_app", MyApp::default())
+///     .command("my_app spawn --count 10")
+///     .run_blocking();
+/// ```
+pub struct EngineBuilder {
+    config: EngineConfig,
+    apps: Vec<(String, Box<dyn App>)>,
+    commands: Vec<String>,
+    loaders: Vec<Arc<dyn AssetLoader>>,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        EngineBuilder::from_config(EngineConfig::default())
+    }
+}
+
+impl EngineBuilder {
+    /// Starts from the default config, running every built-in app.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from `config` (Ex: `EngineConfig::load()` to read the config file and command line).
+    pub fn from_config(config: EngineConfig) -> Self {
+        EngineBuilder {
+            config,
+            apps: vec![],
+            commands: vec![],
+            loaders: vec![],
+        }
+    }
+
+    /// Changes the config set so far.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// builder.configure(|config| config.asset_mode = AssetMode::Local)
+    /// ```
+    pub fn configure(mut self, configure: impl FnOnce(&mut EngineConfig)) -> Self {
+        configure(&mut self.config);
+        self
+    }
+
+    /// Only runs the given built-in apps (Ex: `["asset_server", "cli"]`), see `default_apps` for their names.
+    pub fn builtin_apps<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.config.apps = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Runs none of the built-in apps, only the ones added with `app`.
+    pub fn without_builtin_apps(mut self) -> Self {
+        self.config.apps.clear();
+        self
+    }
+
+    /// Inserts `app` under `name` next to the built-in apps.
+    pub fn app(self, name: &str, app: impl App + 'static) -> Self {
+        self.boxed_app(name, Box::new(app))
+    }
+
+    pub fn boxed_app(mut self, name: &str, app: Box<dyn App>) -> Self {
+        self.apps.push((name.to_owned(), app));
+        self
+    }
+
+    /// Runs the CLI line (Ex: `default_scene add --name Cube`) once the apps are initialized.
+    /// The startup commands are the lines of a `Script`: they can be quoted, commented or use `set` variables,
+    /// and run one after the other, stopping at the first one that fails.
+    pub fn command(mut self, line: &str) -> Self {
+        self.commands.push(line.to_owned());
+        self
+    }
+
+    /// Registers `loader` with the built-in asset server (Ex: to decode a new format), see `AssetLoader`.
+    pub fn asset_loader(mut self, loader: impl AssetLoader + 'static) -> Self {
+        self.loaders.push(Arc::new(loader));
+        self
+    }
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    pub fn build(mut self) -> Engine {
+        self.insert_asset_loaders();
+
+        let startup = Startup {
+            apps: self.apps,
+            script: (!self.commands.is_empty())
+                .then(|| Script::parse(&self.commands.join("\n"), &HashMap::new())),
+        };
+
+        Engine::with_startup(self.config, startup)
+    }
+
+    /// Builds the engine and runs it inside a winit event loop until it exits.
+    pub async fn run(self) {
+        self.build().run().await;
+    }
+
+    /// Same as `run`, for hosts without an async runtime.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_blocking(self) {
+        pollster::block_on(self.run());
+    }
+
+    /// Builds the engine without windows or a GPU: the windowed built-in apps are left out (see `headless_apps`).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn build_headless(
+        self,
+        clock: crate::core::headless::HeadlessClock,
+    ) -> crate::core::headless::HeadlessRunner {
+        let mut builder = self;
+        builder.insert_asset_loaders();
+
+        let apps = crate::core::default_apps::headless_apps(&builder.config);
+        let engine = builder.build();
+
+        crate::core::headless::HeadlessRunner::with_engine(engine, apps, clock).await
+    }
+
+    /// Replaces the built-in asset server by one with the registered loaders.
+    fn insert_asset_loaders(&mut self) {
+        if self.loaders.is_empty() {
+            return;
+        }
+
+        let Some(index) = self
+            .config
+            .apps
+            .iter()
+            .position(|app| app == "asset_server")
+        else {
+            tracing::warn!("Asset loaders registered without the asset_server app");
+            self.loaders.clear();
+            return;
+        };
+        self.config.apps.remove(index);
+
+        let mut server = AssetServer::from_config(&self.config);
+        for loader in self.loaders.drain(..) {
+            server.loaders.register_arc(loader);
+        }
+        self.apps.push(("asset_server".into(), Box::new(server)));
+    }
+}
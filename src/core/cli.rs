@@ -1,47 +1,143 @@
 use async_trait::async_trait;
 use tracing::{error, info};
-use winit::event_loop::EventLoopProxy;
 
 use crate::core::{
+    alias::Aliases,
     app::*,
     command_queue::*,
-    events::CommandEvent,
-    state::{is_running, State},
+    command_registry::*,
+    command_result::{CommandFailure, CommandOutput, CommandReport, CommandResult},
+    events::{CommandEvent, EventKind},
+    proxy::EngineProxy,
+    tasks::TaskId,
+    time::{Clock, TimeControl},
 };
 
 pub struct CLI {
     pub commands: Vec<Command>,
-    pub proxy: Option<EventLoopProxy<CommandEvent>>,
+    pub proxy: Option<EngineProxy>,
 }
 
 impl CLI {
-    pub fn process_cli_command(&mut self, mut cmd: Command) {
-        let args = cmd.args.clone().unwrap();
-        let vec_args: Vec<&str> = args.split(' ').collect();
-
-        let task = match args.to_ascii_lowercase().as_str() {
+    pub fn process_cli_command(&mut self, mut cmd: Command, args: ParsedArgs) {
+        let task = match args.command() {
             "exit" => CLI::exit(self),
-            "load" => CLI::load_dynamic(vec_args[1]),
-            _ => CLI::unsupported(args.as_str()),
+            "load" => CLI::send_events(vec![CommandEvent::LoadPlugin(
+                args.get_str("path").unwrap().to_owned(),
+            )]),
+            "exec" => CLI::send_events(vec![CommandEvent::ExecScript(
+                args.get_str("path").unwrap().to_owned(),
+            )]),
+            "record" => CLI::send_events(vec![CommandEvent::StartRecording(
+                args.get_str("path").unwrap().to_owned(),
+            )]),
+            "record_stop" => CLI::send_events(vec![CommandEvent::StopRecording]),
+            "pause" => CLI::send_events(vec![CommandEvent::Time(TimeControl::Pause)]),
+            "resume" => CLI::send_events(vec![CommandEvent::Time(TimeControl::Resume)]),
+            "step" => CLI::send_events(vec![CommandEvent::Time(TimeControl::Step(
+                args.get_u32("ticks").unwrap(),
+            ))]),
+            "timescale" => match Clock::validate_time_scale(args.get_f32("scale").unwrap()) {
+                Ok(scale) => CLI::send_events(vec![CommandEvent::Time(TimeControl::Scale(scale))]),
+                Err(err) => CLI::fail(err.to_string()),
+            },
+            "time" => CLI::send_events(vec![CommandEvent::Time(TimeControl::Status)]),
+            "unload" => CLI::send_events(vec![CommandEvent::UnloadPlugin(
+                args.get_str("name").unwrap().to_owned(),
+            )]),
+            "apps" => CLI::send_events(vec![CommandEvent::ListApps]),
+            "stop" => CLI::send_events(vec![CommandEvent::StopApp(
+                args.get_str("name").unwrap().to_owned(),
+            )]),
+            "restart" => CLI::send_events(vec![CommandEvent::RestartApp(
+                args.get_str("name").unwrap().to_owned(),
+            )]),
+            "tasks" => CLI::send_events(vec![CommandEvent::ListTasks]),
+            "cancel" => CLI::send_events(vec![CommandEvent::CancelTask(TaskId(
+                args.get_uint("id").unwrap(),
+            ))]),
+            "alias" => self.alias(
+                args.get_str("name").unwrap(),
+                args.get_list("command").unwrap(),
+            ),
+            "unalias" => self.unalias(args.get_str("name").unwrap()),
+            "aliases" => self.list_aliases(),
+            _ => CLI::unsupported(cmd.args.as_deref().unwrap_or_default()),
         };
 
         cmd.processed = true;
         cmd.task = task;
-        cmd.args = Some(args);
 
         self.commands.push(cmd);
     }
 
-    // TODO: load dyn lib
-    fn load_dynamic(_path: &str) -> Option<Task<Vec<CommandEvent>>> {
-        None
+    fn send_events(events: Vec<CommandEvent>) -> Option<Task<CommandResult>> {
+        Some(Box::new(move || Ok(events.clone().into())))
+    }
+
+    fn fail(message: String) -> Option<Task<CommandResult>> {
+        Some(Box::new(move || {
+            Err(CommandFailure::Failed(message.clone()))
+        }))
+    }
+
+    /// Runs `edit` on the aliases of the engine, replying with its result.
+    fn with_aliases(
+        &self,
+        edit: impl FnOnce(&mut Aliases) -> CommandResult,
+    ) -> Option<Task<CommandResult>> {
+        let result = match &self.proxy {
+            Some(proxy) => edit(&mut proxy.aliases().lock().unwrap()),
+            None => Err(CommandFailure::Failed("the CLI is not initialized".into())),
+        };
+
+        Some(Box::new(move || result.clone()))
+    }
+
+    fn alias(&self, name: &str, command: &[String]) -> Option<Task<CommandResult>> {
+        // `alias cam3d = default_scene ...` reads better, the '=' is optional
+        let command = command.strip_prefix(&["=".to_owned()]).unwrap_or(command);
+        let line = join_args(command);
+
+        self.with_aliases(|aliases| {
+            if line.is_empty() {
+                return Err(CommandFailure::Failed(format!(
+                    "alias {name} needs a command to run"
+                )));
+            }
+
+            aliases
+                .define(name, &line)
+                .map(|()| CommandOutput::message(format!("alias {name} = {line}")))
+                .map_err(|err| CommandFailure::Failed(err.to_string()))
+        })
     }
 
-    fn exit(&self) -> Option<Task<Vec<CommandEvent>>> {
+    fn unalias(&self, name: &str) -> Option<Task<CommandResult>> {
+        self.with_aliases(|aliases| match aliases.remove(name) {
+            Ok(Some(line)) => Ok(CommandOutput::message(format!(
+                "removed alias {name} = {line}"
+            ))),
+            Ok(None) => Err(CommandFailure::Failed(format!("no alias named {name}"))),
+            Err(err) => Err(CommandFailure::Failed(err.to_string())),
+        })
+    }
+
+    fn list_aliases(&self) -> Option<Task<CommandResult>> {
+        self.with_aliases(|aliases| {
+            Ok(CommandOutput::message(if aliases.is_empty() {
+                "no aliases".to_owned()
+            } else {
+                format!("aliases:\n{}", aliases.describe())
+            }))
+        })
+    }
+
+    fn exit(&self) -> Option<Task<CommandResult>> {
         let cmd = move || {
             let event = CommandEvent::Exit;
 
-            vec![event]
+            Ok(vec![event].into())
         };
 
         Some(Box::new(cmd))
@@ -53,7 +149,7 @@ impl App for CLI {
     fn get_name(&self) -> String {
         "CLI".into()
     }
-    fn init(&mut self, elp: EventLoopProxy<CommandEvent>) {
+    fn init(&mut self, elp: EngineProxy) {
         self.proxy = Some(elp);
     }
 
@@ -61,8 +157,77 @@ impl App for CLI {
         self.commands.drain(0..self.commands.len()).collect()
     }
 
-    async fn process_command(&mut self, cmd: Command) {
-        self.process_cli_command(cmd);
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![]
+    }
+
+    fn commands(&self) -> Vec<CommandDesc> {
+        vec![
+            CommandDesc::new("exit", "Shuts down the engine"),
+            CommandDesc::new("load", "Loads a plugin library and inserts its apps").arg(
+                ArgDesc::positional("path", ArgType::String, "Path to the library"),
+            ),
+            CommandDesc::new(
+                "unload",
+                "Removes the apps of a plugin and closes its library",
+            )
+            .arg(ArgDesc::positional(
+                "name",
+                ArgType::String,
+                "Name the plugin was exported with",
+            )),
+            CommandDesc::new(
+                "exec",
+                "Runs the CLI lines of a script file, stops at the first error",
+            )
+            .arg(ArgDesc::positional(
+                "path",
+                ArgType::String,
+                "Path to the script",
+            )),
+            CommandDesc::new("record", "Records the commands and events of every frame").arg(
+                ArgDesc::positional("path", ArgType::String, "Path to the recording"),
+            ),
+            CommandDesc::new("record_stop", "Stops recording the session"),
+            CommandDesc::new("pause", "Pauses the simulation clock"),
+            CommandDesc::new("resume", "Resumes the simulation clock"),
+            CommandDesc::new("step", "Pauses the simulation clock and runs a number of ticks")
+                .arg(
+                    ArgDesc::positional("ticks", ArgType::U32, "Number of ticks")
+                        .with_default("1"),
+                ),
+            CommandDesc::new("timescale", "Sets the speed of the simulation clock").arg(
+                ArgDesc::positional("scale", ArgType::Float, "Ex: 0.5 for half speed"),
+            ),
+            CommandDesc::new("time", "Shows the state of the simulation clock"),
+            CommandDesc::new("apps", "Lists the running and stopped apps"),
+            CommandDesc::new("stop", "Shuts down an app until it is restarted").arg(
+                ArgDesc::positional("name", ArgType::String, "Name of the app"),
+            ),
+            CommandDesc::new("restart", "Restarts a stopped or running app").arg(
+                ArgDesc::positional("name", ArgType::String, "Name of the app"),
+            ),
+            CommandDesc::new("tasks", "Lists the running async command tasks"),
+            CommandDesc::new("alias", "Names a CLI line, kept across runs")
+                .arg(ArgDesc::positional("name", ArgType::String, "Name of the alias"))
+                .arg(ArgDesc::rest(
+                    "command",
+                    "[=] <app> <args...>, $1..$9 are replaced by the parameters of the alias and $* by all of them",
+                )),
+            CommandDesc::new("unalias", "Removes an alias").arg(ArgDesc::positional(
+                "name",
+                ArgType::String,
+                "Name of the alias",
+            )),
+            CommandDesc::new("aliases", "Lists the aliases"),
+            CommandDesc::new("cancel", "Cancels a running async command task").arg(
+                ArgDesc::positional("id", ArgType::UInt, "Id of the task, as listed by tasks"),
+            ),
+        ]
+    }
+
+    async fn process_command(&mut self, cmd: Command, args: ParsedArgs) {
+        self.process_cli_command(cmd, args);
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -87,7 +252,12 @@ pub fn get_cli_command() -> Command {
 
     info!("Command: {}", command);
 
-    let args: Vec<&str> = command.split(' ').collect();
+    parse_cli_line(&command)
+}
+
+/// Turns an `app args...` line into a command for `app`, its arguments are parsed once dispatched.
+pub fn parse_cli_line(line: &str) -> Command {
+    let args: Vec<&str> = line.split(' ').collect();
 
     Command {
         processed: false,
@@ -95,20 +265,34 @@ pub fn get_cli_command() -> Command {
         command_type: CommandType::TBD,
         args: Some(args[1..].join(" ")),
         task: None,
+        future: None,
+        timeout: None,
+        reply_to: None,
     }
 }
 
-pub async fn run_cli() {
-    while is_running() {
-        let next_command = get_cli_command();
-        info!("Command: {:?}", next_command);
+pub async fn run_cli(proxy: EngineProxy) {
+    // Results come back once the command ran, while the CLI thread already waits on the next line
+    let (replies, reports) = std::sync::mpsc::channel::<CommandReport>();
+    std::thread::Builder::new()
+        .name("CLI replies".into())
+        .spawn(move || {
+            for report in reports {
+                match report.result {
+                    Ok(_) => info!("{}", report.describe()),
+                    Err(_) => error!("{}", report.describe()),
+                }
+            }
+        })
+        .expect("Could not spawn the CLI replies thread");
 
-        let mut state_lock = State::write().await;
+    while proxy.is_running() {
+        let next_command = get_cli_command().reply_to(replies.clone());
+        info!("Command: {:?}", next_command);
 
-        if let Some(app) = state_lock.apps.get_mut(&next_command.app) {
-            app.process_command(next_command).await;
-        } else {
-            error!("No app found with name: {}", next_command.app);
+        if proxy.send_command(next_command).is_err() {
+            error!("Engine is no longer receiving commands!");
+            break;
         }
     }
 }
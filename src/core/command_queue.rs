@@ -1,5 +1,11 @@
-use crate::core::events::CommandEvent;
-use std::{collections::VecDeque, fmt::Debug};
+use crate::core::{
+    command_result::{reply, CommandOutput, CommandResult, ReplySender},
+    events::CommandEvent,
+    profiler::{Profiler, ScopeKind},
+    proxy::EngineProxy,
+    tasks::TaskTracker,
+};
+use std::{collections::VecDeque, fmt::Debug, future::Future, pin::Pin, time::Duration};
 
 // TODO: Context needs to be defined in the app itself
 pub struct Context;
@@ -10,6 +16,13 @@ pub type Task<T> = Box<dyn FnMut() -> T + Send + Sync>;
 #[cfg(target_arch = "wasm32")]
 pub type Task<T> = Box<dyn FnMut() -> T + Send>;
 
+/// Work of a command that waits on something (Ex: I/O), ran off the event loop by the `TaskTracker`.
+#[cfg(not(target_arch = "wasm32"))]
+pub type AsyncTask<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+#[cfg(target_arch = "wasm32")]
+pub type AsyncTask<T> = Pin<Box<dyn Future<Output = T>>>;
+
 //#[derive(Reflect)]
 #[derive(PartialEq, Eq, Debug)]
 pub enum CommandType {
@@ -40,7 +53,13 @@ pub struct Command {
     pub processed: bool,
 
     pub args: Option<String>,
-    pub task: Option<Task<Vec<CommandEvent>>>,
+    pub task: Option<Task<CommandResult>>,
+    pub future: Option<AsyncTask<CommandResult>>,
+    /// Cancels `future` if it has not completed in time.
+    pub timeout: Option<Duration>,
+    /// Issuer of the command (Ex: the CLI or a script), it receives the result of the command
+    /// once it ran or failed to be dispatched. Results nobody waits for are logged.
+    pub reply_to: Option<ReplySender>,
 }
 
 impl Command {
@@ -48,7 +67,7 @@ impl Command {
         app: &str,
         command_type: CommandType,
         args: Option<String>,
-        task: Option<Task<Vec<CommandEvent>>>,
+        task: Option<Task<CommandResult>>,
     ) -> Command {
         Command {
             processed: task.is_some(),
@@ -56,13 +75,55 @@ impl Command {
             command_type,
             args,
             task,
+            future: None,
+            timeout: None,
+            reply_to: None,
         }
     }
 
-    pub fn from_args(
-        args: Vec<&str>,
-        elp: winit::event_loop::EventLoopProxy<CommandEvent>,
+    /// A processed command running `future` on the `TaskTracker` instead of the event loop thread.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let cmd = Command::from_future("asset_server", CommandType::Get, None, Box::pin(async move {
+    ///     let data = async_std::fs::read(path)
+    ///         .await
+    ///         .map_err(|err| CommandFailure::Failed(err.to_string()))?;
+    ///     Ok(vec![CommandEvent::Asset(...)].into())
+    /// }))
+    /// .with_timeout(Duration::from_secs(5));
+    /// ```
+    pub fn from_future(
+        app: &str,
+        command_type: CommandType,
+        args: Option<String>,
+        future: AsyncTask<CommandResult>,
     ) -> Command {
+        Command {
+            processed: true,
+            app: app.to_owned(),
+            command_type,
+            args,
+            task: None,
+            future: Some(future),
+            timeout: None,
+            reply_to: None,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Command {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends the result of the command to `reply_to`, see `CommandReport`.
+    pub fn reply_to(mut self, reply_to: ReplySender) -> Command {
+        self.reply_to = Some(reply_to);
+        self
+    }
+
+    pub fn from_args(args: Vec<&str>, elp: EngineProxy) -> Command {
         match args[0] {
             "close" | "exit" => Command::exit(elp),
             _ => Command {
@@ -71,11 +132,14 @@ impl Command {
                 command_type: CommandType::Other,
                 args: Some(args[1..].join(" ")),
                 task: None,
+                future: None,
+                timeout: None,
+                reply_to: None,
             },
         }
     }
 
-    pub fn exit(elp: winit::event_loop::EventLoopProxy<CommandEvent>) -> Command {
+    pub fn exit(elp: EngineProxy) -> Command {
         elp.send_event(CommandEvent::Exit).unwrap();
         Command {
             processed: true,
@@ -83,6 +147,9 @@ impl Command {
             command_type: CommandType::Close,
             args: None,
             task: None,
+            future: None,
+            timeout: None,
+            reply_to: None,
         }
     }
 }
@@ -93,6 +160,7 @@ impl Debug for Command {
             .field("App", &self.app)
             .field("Type", &self.command_type)
             .field("args", &self.args)
+            .field("async", &self.future.is_some())
             .finish()
     }
 }
@@ -110,6 +178,7 @@ impl IntoCommand for Command {
 #[derive(Default)]
 pub struct CommandQueue {
     commands: VecDeque<Command>,
+    tasks: TaskTracker,
 }
 
 impl CommandQueue {
@@ -122,24 +191,64 @@ impl CommandQueue {
 
         CommandQueue {
             commands: commands.into(),
+            tasks: TaskTracker::default(),
         }
     }
 
+    /// Runs the blocking tasks of the queued commands and spawns their async ones.
+    /// The result of every command is sent to its issuer, see `Command::reply_to`.
     // TODO: Pass an RwLockGuard to all tasks?
-    pub fn execute(&mut self, elp: winit::event_loop::EventLoopProxy<CommandEvent>) {
+    pub fn execute(&mut self, elp: EngineProxy, profiler: &Profiler) {
         for _ in 0..self.commands.len() {
             let command = self.commands.pop_front();
             if let Some(command) = command {
-                if let Some(mut task) = command.task {
-                    let events = task();
-                    for event in events {
-                        elp.send_event(event).expect("Could not send event T-T");
-                    }
+                let Command {
+                    app,
+                    args,
+                    task,
+                    future,
+                    timeout,
+                    reply_to,
+                    ..
+                } = command;
+
+                let has_future = future.is_some();
+                if let Some(future) = future {
+                    self.tasks.spawn(
+                        &app,
+                        args.clone(),
+                        future,
+                        timeout,
+                        reply_to.clone(),
+                        elp.clone(),
+                    );
                 }
+
+                let result = match task {
+                    Some(mut task) => {
+                        let _scope = profiler.scope(ScopeKind::Task, &app);
+                        task()
+                    }
+                    // The async task reports the result once it ends
+                    None if has_future => continue,
+                    None => Ok(CommandOutput::default()),
+                };
+
+                reply(
+                    reply_to.as_ref(),
+                    &app,
+                    args.as_deref(),
+                    send_events(result, &elp),
+                );
             }
         }
     }
 
+    /// The async tasks spawned by this queue.
+    pub fn tasks(&self) -> &TaskTracker {
+        &self.tasks
+    }
+
     pub fn add_command(&mut self, command: impl IntoCommand) {
         self.commands.push_back(command.into_command());
     }
@@ -153,3 +262,22 @@ impl CommandQueue {
         }
     }
 }
+
+/// Sends the events of a successful `result` into the engine.
+///
+/// # Returns
+///
+/// `result` without its events.
+pub(crate) fn send_events(mut result: CommandResult, elp: &EngineProxy) -> CommandResult {
+    if let Ok(output) = &mut result {
+        for event in output.events.drain(..) {
+            // Async tasks can end after the engine exited
+            if elp.send_event(event).is_err() {
+                tracing::error!("Could not send event, the engine is no longer running");
+                break;
+            }
+        }
+    }
+
+    result
+}
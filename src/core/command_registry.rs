@@ -0,0 +1,611 @@
+use std::{collections::HashMap, fmt::Write};
+
+use thiserror::Error;
+
+/// The type an argument value is parsed into before it reaches the app.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgType {
+    String,
+    Int,
+    UInt,
+    /// An unsigned integer in the range of a `u32` (Ex: a window size), stored as an `ArgValue::UInt`.
+    U32,
+    Float,
+    Bool,
+    /// Collects every remaining token (positional) or every token up to the next `--option`.
+    List,
+}
+
+impl std::fmt::Display for ArgType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgType::String => write!(f, "string"),
+            ArgType::Int => write!(f, "int"),
+            ArgType::UInt => write!(f, "uint"),
+            ArgType::U32 => write!(f, "u32"),
+            ArgType::Float => write!(f, "float"),
+            ArgType::Bool => write!(f, "bool"),
+            ArgType::List => write!(f, "list"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    String(String),
+    Int(i64),
+    UInt(u64),
+    Float(f32),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    /// Filled in declaration order from the bare tokens of the command.
+    Positional,
+    /// Given by name as `--<name> <values...>`.
+    Option,
+    /// The last positional argument, collects every remaining token including the `--options`
+    /// (Ex: a command to run later).
+    Rest,
+}
+
+/// Declaration of a single named argument of a subcommand.
+#[derive(Debug, Clone)]
+pub struct ArgDesc {
+    pub name: String,
+    pub arg_type: ArgType,
+    pub kind: ArgKind,
+    pub required: bool,
+    pub default: Option<String>,
+    pub description: String,
+}
+
+impl ArgDesc {
+    /// A required positional argument.
+    pub fn positional(name: &str, arg_type: ArgType, description: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            arg_type,
+            kind: ArgKind::Positional,
+            required: true,
+            default: None,
+            description: description.to_owned(),
+        }
+    }
+
+    /// An optional `--name <values...>` argument.
+    pub fn option(name: &str, arg_type: ArgType, description: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            arg_type,
+            kind: ArgKind::Option,
+            required: false,
+            default: None,
+            description: description.to_owned(),
+        }
+    }
+
+    /// A required list of every token after the previous positional arguments, `--options` included.
+    pub fn rest(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            arg_type: ArgType::List,
+            kind: ArgKind::Rest,
+            required: true,
+            default: None,
+            description: description.to_owned(),
+        }
+    }
+
+    /// Makes the argument optional, using `default` when it is not provided.
+    pub fn with_default(mut self, default: &str) -> Self {
+        self.required = false;
+        self.default = Some(default.to_owned());
+        self
+    }
+
+    /// Makes the argument optional without a default value.
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    fn usage(&self) -> String {
+        let value = match self.arg_type {
+            ArgType::List => format!("{}...", self.name),
+            _ => self.name.clone(),
+        };
+
+        match (self.kind, self.required, &self.default) {
+            (ArgKind::Positional | ArgKind::Rest, true, _) => format!("<{value}>"),
+            (ArgKind::Positional | ArgKind::Rest, false, Some(default)) => {
+                format!("[{value}={default}]")
+            }
+            (ArgKind::Positional | ArgKind::Rest, false, None) => format!("[{value}]"),
+            (ArgKind::Option, true, _) => format!("--{} <{value}>", self.name),
+            (ArgKind::Option, false, _) => format!("[--{} <{value}>]", self.name),
+        }
+    }
+
+    fn parse_value(&self, values: &[&str]) -> Result<ArgValue, CommandError> {
+        let invalid = |value: &str| CommandError::InvalidValue {
+            arg: self.name.clone(),
+            expected: self.arg_type,
+            value: value.to_owned(),
+        };
+
+        if self.arg_type == ArgType::List {
+            return Ok(ArgValue::List(
+                values.iter().map(|value| value.to_string()).collect(),
+            ));
+        }
+
+        // A bare boolean option (`--flag`) is treated as `true`
+        if values.is_empty() && self.arg_type == ArgType::Bool && self.kind == ArgKind::Option {
+            return Ok(ArgValue::Bool(true));
+        }
+
+        let value = match values {
+            [value] => *value,
+            [] => return Err(CommandError::MissingArgument(self.name.clone())),
+            _ => return Err(CommandError::TooManyValues(self.name.clone())),
+        };
+
+        match self.arg_type {
+            ArgType::String => Ok(ArgValue::String(value.to_owned())),
+            ArgType::Int => value.parse().map(ArgValue::Int).map_err(|_| invalid(value)),
+            ArgType::UInt => value
+                .parse()
+                .map(ArgValue::UInt)
+                .map_err(|_| invalid(value)),
+            ArgType::U32 => value
+                .parse::<u32>()
+                .map(|value| ArgValue::UInt(value.into()))
+                .map_err(|_| invalid(value)),
+            ArgType::Float => value
+                .parse()
+                .map(ArgValue::Float)
+                .map_err(|_| invalid(value)),
+            ArgType::Bool => match value.to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Ok(ArgValue::Bool(true)),
+                "false" | "0" | "no" | "off" => Ok(ArgValue::Bool(false)),
+                _ => Err(invalid(value)),
+            },
+            ArgType::List => unreachable!(),
+        }
+    }
+}
+
+/// Declaration of a subcommand an app accepts (Ex: `windower open <name> [width] [height]`).
+#[derive(Debug, Clone)]
+pub struct CommandDesc {
+    pub name: String,
+    pub description: String,
+    pub args: Vec<ArgDesc>,
+}
+
+impl CommandDesc {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_ascii_lowercase(),
+            description: description.to_owned(),
+            args: vec![],
+        }
+    }
+
+    pub fn arg(mut self, arg: ArgDesc) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.clone();
+        for arg in &self.args {
+            usage.push(' ');
+            usage.push_str(&arg.usage());
+        }
+        usage
+    }
+
+    /// Parses the tokens following the subcommand name according to the declared arguments.
+    pub fn parse(&self, tokens: &[&str]) -> Result<ParsedArgs, CommandError> {
+        let mut values: HashMap<String, ArgValue> = HashMap::new();
+
+        let mut positionals = self
+            .args
+            .iter()
+            .filter(|arg| matches!(arg.kind, ArgKind::Positional | ArgKind::Rest))
+            .peekable();
+
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = tokens[i];
+
+            if let Some(desc) = positionals.next_if(|arg| arg.kind == ArgKind::Rest) {
+                values.insert(desc.name.clone(), desc.parse_value(&tokens[i..])?);
+                break;
+            }
+
+            if let Some(option_name) = token.strip_prefix("--") {
+                let desc = self
+                    .args
+                    .iter()
+                    .find(|arg| arg.kind == ArgKind::Option && arg.name == option_name)
+                    .ok_or_else(|| CommandError::UnknownOption(option_name.to_owned()))?;
+
+                let end = tokens[i + 1..]
+                    .iter()
+                    .position(|t| t.starts_with("--"))
+                    .map_or(tokens.len(), |pos| i + 1 + pos);
+
+                values.insert(desc.name.clone(), desc.parse_value(&tokens[i + 1..end])?);
+                i = end;
+                continue;
+            }
+
+            let desc = positionals
+                .next()
+                .ok_or_else(|| CommandError::TooManyArguments(token.to_owned()))?;
+
+            if desc.arg_type == ArgType::List {
+                let end = tokens[i..]
+                    .iter()
+                    .position(|t| t.starts_with("--"))
+                    .map_or(tokens.len(), |pos| i + pos);
+
+                values.insert(desc.name.clone(), desc.parse_value(&tokens[i..end])?);
+                i = end;
+            } else {
+                values.insert(desc.name.clone(), desc.parse_value(&[token])?);
+                i += 1;
+            }
+        }
+
+        for desc in &self.args {
+            if values.contains_key(&desc.name) {
+                continue;
+            }
+
+            if let Some(default) = &desc.default {
+                let default_tokens: Vec<&str> = default.split_whitespace().collect();
+                values.insert(desc.name.clone(), desc.parse_value(&default_tokens)?);
+            } else if desc.required {
+                return Err(CommandError::MissingArgument(desc.name.clone()));
+            }
+        }
+
+        Ok(ParsedArgs {
+            command: self.name.clone(),
+            values,
+        })
+    }
+}
+
+/// Arguments of a command after being validated against its `CommandDesc`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedArgs {
+    pub command: String,
+    pub values: HashMap<String, ArgValue>,
+}
+
+impl ParsedArgs {
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ArgValue> {
+        self.values.get(name)
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(ArgValue::String(value)) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        match self.values.get(name) {
+            Some(ArgValue::Int(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_uint(&self, name: &str) -> Option<u64> {
+        match self.values.get(name) {
+            Some(ArgValue::UInt(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of an `ArgType::U32` argument, none for a `UInt` one out of range.
+    pub fn get_u32(&self, name: &str) -> Option<u32> {
+        self.get_uint(name)
+            .and_then(|value| u32::try_from(value).ok())
+    }
+
+    pub fn get_f32(&self, name: &str) -> Option<f32> {
+        match self.values.get(name) {
+            Some(ArgValue::Float(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.values.get(name) {
+            Some(ArgValue::Bool(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_list(&self, name: &str) -> Option<&[String]> {
+        match self.values.get(name) {
+            Some(ArgValue::List(value)) => Some(value.as_slice()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CommandError {
+    #[error("unknown command <{0}>")]
+    UnknownCommand(String),
+    #[error("unknown option --{0}")]
+    UnknownOption(String),
+    #[error("missing argument <{0}>")]
+    MissingArgument(String),
+    #[error("unexpected argument: {0}")]
+    TooManyArguments(String),
+    #[error("argument <{0}> takes a single value")]
+    TooManyValues(String),
+    #[error("invalid value for <{arg}>: expected {expected}, got {value:?}")]
+    InvalidValue {
+        arg: String,
+        expected: ArgType,
+        value: String,
+    },
+}
+
+/// What a raw argument string resolved to after being checked against an app's commands.
+#[derive(Debug, PartialEq)]
+pub enum ParsedCommand {
+    Help,
+    Command(ParsedArgs),
+}
+
+/// The set of subcommands registered by a single app.
+#[derive(Debug, Clone, Default)]
+pub struct CommandRegistry {
+    pub app: String,
+    pub commands: Vec<CommandDesc>,
+}
+
+impl CommandRegistry {
+    pub fn new(app: &str, commands: Vec<CommandDesc>) -> Self {
+        Self {
+            app: app.to_owned(),
+            commands,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandDesc> {
+        let name = name.to_ascii_lowercase();
+        self.commands.iter().find(|desc| desc.name == name)
+    }
+
+    /// Parses `args` (everything after the app name, see `split_args`) into a validated command.
+    /// An empty string or `help` resolves to `ParsedCommand::Help`.
+    pub fn parse(&self, args: &str) -> Result<ParsedCommand, CommandError> {
+        let tokens = split_args(args);
+        let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+
+        let Some((name, rest)) = tokens.split_first() else {
+            return Ok(ParsedCommand::Help);
+        };
+
+        if let Some(desc) = self.get(name) {
+            return desc.parse(rest).map(ParsedCommand::Command);
+        }
+
+        if name.eq_ignore_ascii_case("help") {
+            return Ok(ParsedCommand::Help);
+        }
+
+        Err(CommandError::UnknownCommand(name.to_string()))
+    }
+
+    pub fn help(&self) -> String {
+        let mut help = format!("{} commands:", self.app);
+
+        for desc in &self.commands {
+            let _ = write!(help, "\n  {} -> {}", desc.usage(), desc.description);
+            for arg in &desc.args {
+                let _ = write!(
+                    help,
+                    "\n      {} ({}): {}",
+                    arg.name, arg.arg_type, arg.description
+                );
+            }
+        }
+        let _ = write!(help, "\n  help -> Shows this message");
+
+        help
+    }
+}
+
+/// Splits the arguments of a command into tokens: whitespace separates them, except inside `"..."` or `'...'`
+/// (Ex: `--name "My Cube"` is `--name` and `My Cube`). The quotes are removed, an unterminated quote runs to the end.
+pub fn split_args(args: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut in_token = false;
+    let mut quote = None;
+
+    for c in args.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => token.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_token = true;
+            }
+            None if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut token));
+                    in_token = false;
+                }
+            }
+            None => {
+                token.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if in_token {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+/// Joins tokens into the arguments of a command, quoting the ones `split_args` would not give back as is.
+pub fn join_args<S: AsRef<str>>(tokens: &[S]) -> String {
+    tokens
+        .iter()
+        .map(|token| {
+            let token = token.as_ref();
+            let needs_quotes = token.is_empty()
+                || token.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '#');
+
+            match (needs_quotes, token.contains('"')) {
+                (false, _) => token.to_owned(),
+                (true, false) => format!("\"{token}\""),
+                (true, true) => format!("'{token}'"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open() -> CommandDesc {
+        CommandDesc::new("open", "Opens a window")
+            .arg(ArgDesc::positional("name", ArgType::String, "Window name"))
+            .arg(ArgDesc::positional("width", ArgType::U32, "Window width").with_default("1024"))
+            .arg(ArgDesc::option("title", ArgType::List, "Window title"))
+            .arg(ArgDesc::option("fullscreen", ArgType::Bool, "Fullscreen"))
+    }
+
+    #[test]
+    fn parses_positionals_options_and_defaults() {
+        let args = open()
+            .parse(&["main", "--title", "Hello", "world", "--fullscreen"])
+            .unwrap();
+
+        assert_eq!(args.get_str("name"), Some("main"));
+        assert_eq!(args.get_u32("width"), Some(1024));
+        assert_eq!(
+            args.get_list("title"),
+            Some(["Hello".to_owned(), "world".to_owned()].as_slice())
+        );
+        assert_eq!(args.get_bool("fullscreen"), Some(true));
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        let desc = open();
+
+        assert_eq!(
+            desc.parse(&[]),
+            Err(CommandError::MissingArgument("name".into()))
+        );
+        assert_eq!(
+            desc.parse(&["main", "--size"]),
+            Err(CommandError::UnknownOption("size".into()))
+        );
+        assert_eq!(
+            desc.parse(&["main", "10", "20"]),
+            Err(CommandError::TooManyArguments("20".into()))
+        );
+        assert!(matches!(
+            desc.parse(&["main", "-1"]),
+            Err(CommandError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn u32_values_out_of_range_are_usage_errors() {
+        assert_eq!(
+            open().parse(&["main", "4294967296"]),
+            Err(CommandError::InvalidValue {
+                arg: "width".into(),
+                expected: ArgType::U32,
+                value: "4294967296".into(),
+            })
+        );
+        assert_eq!(
+            open()
+                .parse(&["main", "4294967295"])
+                .unwrap()
+                .get_u32("width"),
+            Some(u32::MAX)
+        );
+    }
+
+    #[test]
+    fn rest_collects_every_remaining_token() {
+        let desc = CommandDesc::new("after", "Runs a line later")
+            .arg(ArgDesc::positional("delay", ArgType::Float, "Seconds"))
+            .arg(ArgDesc::rest("line", "Line to run"));
+
+        let args = desc
+            .parse(&["1.5", "scene", "add", "--name", "Cube"])
+            .unwrap();
+        assert_eq!(args.get_f32("delay"), Some(1.5));
+        assert_eq!(
+            args.get_list("line").unwrap().join(" "),
+            "scene add --name Cube"
+        );
+    }
+
+    #[test]
+    fn registry_resolves_help_and_unknown_commands() {
+        let registry = CommandRegistry::new("windower", vec![open()]);
+
+        assert_eq!(registry.parse(""), Ok(ParsedCommand::Help));
+        assert_eq!(registry.parse("help"), Ok(ParsedCommand::Help));
+        assert!(matches!(
+            registry.parse("OPEN main"),
+            Ok(ParsedCommand::Command(args)) if args.command() == "open"
+        ));
+        assert_eq!(
+            registry.parse("close main"),
+            Err(CommandError::UnknownCommand("close".into()))
+        );
+    }
+
+    #[test]
+    fn quoted_arguments_are_single_tokens() {
+        assert_eq!(
+            split_args(r#"add --name "My Cube" --color '#ff0000'  x"#),
+            ["add", "--name", "My Cube", "--color", "#ff0000", "x"]
+        );
+        assert_eq!(
+            split_args(r#"say "" 'unterminated quote"#),
+            ["say", "", "unterminated quote"]
+        );
+
+        let tokens = ["scene", "My Cube", "#1", "", "say \"hi\""];
+        assert_eq!(split_args(&join_args(&tokens)), tokens);
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::mpsc::Sender;
+
+use thiserror::Error;
+
+use crate::core::{events::CommandEvent, state::DispatchError};
+
+/// What a processed command produced: the result of its task, routed back to whoever issued it.
+pub type CommandResult = Result<CommandOutput, CommandFailure>;
+
+/// Receives the `CommandReport` of a command, see `Command::reply_to`.
+pub type ReplySender = Sender<CommandReport>;
+
+/// Success payload of a command.
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    /// Sent into the engine once the command ran.
+    pub events: Vec<CommandEvent>,
+    /// Reply for the issuer (Ex: the generated help or a listing), printed by the CLI and the console.
+    pub message: Option<String>,
+}
+
+impl CommandOutput {
+    pub fn message(message: impl Into<String>) -> Self {
+        CommandOutput {
+            events: vec![],
+            message: Some(message.into()),
+        }
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+impl From<Vec<CommandEvent>> for CommandOutput {
+    fn from(events: Vec<CommandEvent>) -> Self {
+        CommandOutput {
+            events,
+            message: None,
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CommandFailure {
+    /// The command never reached its app (Ex: unknown app or invalid arguments).
+    #[error(transparent)]
+    Dispatch(#[from] DispatchError),
+    #[error("unsupported arguments \"{0}\", type help for supported commands")]
+    Unsupported(String),
+    /// The arguments of a command built in code are missing a value (Ex: an asset request without a type).
+    #[error("invalid arguments: {0}")]
+    InvalidArgs(String),
+    #[error("{0}")]
+    Failed(String),
+    #[error("cancelled")]
+    Cancelled,
+    #[error("timed out")]
+    TimedOut,
+}
+
+/// The result of a command together with the command it answers.
+#[derive(Debug, Clone)]
+pub struct CommandReport {
+    pub app: String,
+    pub args: Option<String>,
+    pub result: CommandResult,
+}
+
+impl CommandReport {
+    /// `<app> <args>: ok` or `<app> <args>: error: <reason>`, followed by the reply message if any.
+    pub fn describe(&self) -> String {
+        let command = format!("{} {}", self.app, self.args.as_deref().unwrap_or_default());
+        let command = command.trim_end();
+
+        match &self.result {
+            Ok(CommandOutput {
+                message: Some(message),
+                ..
+            }) => format!("{command}: ok\n{message}"),
+            Ok(_) => format!("{command}: ok"),
+            Err(err) => format!("{command}: error: {err}"),
+        }
+    }
+}
+
+/// Sends `result` to the issuer of a command, or logs it if nobody is waiting for it.
+pub fn reply(reply_to: Option<&ReplySender>, app: &str, args: Option<&str>, result: CommandResult) {
+    let report = CommandReport {
+        app: app.to_owned(),
+        args: args.map(str::to_owned),
+        result,
+    };
+
+    match reply_to {
+        // The issuer may have gone away (Ex: a disconnected console client)
+        Some(reply_to) => {
+            let _ = reply_to.send(report);
+        }
+        None => match &report.result {
+            Ok(CommandOutput {
+                message: Some(message),
+                ..
+            }) => tracing::info!("{message}"),
+            Ok(_) => {}
+            Err(_) => tracing::error!("{}", report.describe()),
+        },
+    }
+}
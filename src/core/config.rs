@@ -0,0 +1,520 @@
+use thiserror::Error;
+use tracing::Level;
+
+use crate::core::script::strip_comment;
+
+/// Config file read by `EngineConfig::load` when no `--config <file>` is given.
+pub const DEFAULT_CONFIG_PATH: &str = "sunset.cfg";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Config io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("line {line}: {reason}")]
+    Invalid { line: usize, reason: String },
+    #[error("unknown setting {0}")]
+    UnknownKey(String),
+    #[error("invalid value \"{value}\" for {key}: {reason}")]
+    InvalidValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
+}
+
+/// Where the asset server reads assets from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetMode {
+    /// Files under `EngineConfig::asset_root`.
+    Local,
+    /// The remote asset server at `EngineConfig::asset_address`.
+    Server,
+}
+
+/// A window opened by the windower on startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowConfig {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Startup settings of an engine, applied before its apps are initialized.
+///
+/// Read from a line based file of `<setting> = <value>` entries, every setting can be overridden
+/// on the command line with `--<setting> <value>` (underscores written as dashes):
+///
+/// ```text
+/// # A '#' starting a line or a word begins a comment
+/// apps = windower sun asset_server default_scene
+/// asset_mode = local
+/// asset_root = assets/
+/// window = Sandbox 1920 1080
+/// window = Preview 800 600
+/// log_level = info
+/// log_filters = sunset::renderer=debug, sunset::assets=warn
+/// ```
+///
+/// `cargo run -- --config dev.cfg --asset-mode local --log-level debug`
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    /// Names of the apps inserted on startup, see `default_apps`.
+    pub apps: Vec<String>,
+    pub asset_mode: AssetMode,
+    /// Folder the assets are read from in local mode.
+    pub asset_root: String,
+    /// Address of the asset server (Ex: `127.0.0.1:8080`).
+    pub asset_address: String,
+    /// Windows opened by the windower on startup, `window` can be given several times.
+    pub windows: Vec<WindowConfig>,
+    /// Default level of the log lines, info unless set.
+    pub log_level: Level,
+    /// Levels of single modules, `<module>=<level>` (Ex: `sunset::renderer=debug`), see `set_log_filter`.
+    pub log_filters: Vec<String>,
+    /// File the log lines are appended to.
+    pub log_file: Option<String>,
+    /// Size in bytes after which the log file is rotated.
+    pub log_file_size: u64,
+    /// Number of rotated log files kept.
+    pub log_file_keep: usize,
+    /// Number of recent log lines kept in memory, see `recent_logs`.
+    pub log_history: usize,
+    /// Whether the CLI lines typed on stdin are run (Ex: off when embedded in an application with its own input).
+    pub stdin_cli: bool,
+    /// Worker threads of the runtime the stdin CLI runs on.
+    pub cli_threads: usize,
+    /// Worker threads of the runtime the event loop blocks on.
+    pub event_loop_threads: usize,
+    /// Worker threads of the runtime command tasks run on, see `TaskTracker`.
+    pub task_threads: usize,
+    /// Address of the remote console, none if it should not be started (Ex: `127.0.0.1:7979`).
+    pub console: Option<String>,
+    /// Whether the remote console accepts clients from other machines, loopback ones only by default.
+    pub console_allow_remote: bool,
+    /// File the CLI aliases are kept in across runs, none to forget them on exit, see `Aliases`.
+    pub aliases_file: Option<String>,
+    /// Scripts run once the apps are initialized, see `Script`. `exec` can be given several times,
+    /// `exec = none` runs none (Ex: to start from an empty scene).
+    pub exec: Vec<String>,
+    /// Errors met by `load`, not a setting: they are logged by the engine once its logger is installed.
+    pub load_errors: Vec<String>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let asset_address = "as-http.angel-sunset.app:8080";
+        #[cfg(target_arch = "wasm32")]
+        let asset_address = "as-wss.angel-sunset.app";
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let apps = [
+            "windower",
+            "sun",
+            "asset_server",
+            "default_scene",
+            "profile",
+            "schedule",
+            "log",
+            "cli",
+        ];
+        #[cfg(target_arch = "wasm32")]
+        let apps = [
+            "windower",
+            "sun",
+            "asset_server",
+            "default_scene",
+            "profile",
+            "schedule",
+            "cli",
+        ];
+
+        EngineConfig {
+            apps: apps.map(String::from).to_vec(),
+            asset_mode: AssetMode::Server,
+            asset_root: "assets/".into(),
+            asset_address: asset_address.into(),
+            windows: vec![WindowConfig {
+                name: "Sandbox".into(),
+                width: 1920,
+                height: 1080,
+            }],
+            log_level: Level::INFO,
+            log_filters: vec![],
+            log_file: None,
+            log_file_size: 10 * 1024 * 1024,
+            log_file_keep: 3,
+            log_history: 1000,
+            stdin_cli: true,
+            cli_threads: 8,
+            event_loop_threads: 16,
+            task_threads: 4,
+            console: None,
+            console_allow_remote: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            aliases_file: Some(crate::core::alias::DEFAULT_ALIASES_FILE.into()),
+            #[cfg(target_arch = "wasm32")]
+            aliases_file: None,
+            exec: vec![crate::gallery::scene::DEFAULT_SCENE_SCRIPT.into()],
+            load_errors: vec![],
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Every setting, in the order they are documented by `describe`.
+    pub const KEYS: [&'static str; 19] = [
+        "apps",
+        "asset_mode",
+        "asset_root",
+        "asset_address",
+        "window",
+        "log_level",
+        "log_filters",
+        "log_file",
+        "log_file_size",
+        "log_file_keep",
+        "log_history",
+        "stdin_cli",
+        "cli_threads",
+        "event_loop_threads",
+        "task_threads",
+        "console",
+        "console_allow_remote",
+        "aliases_file",
+        "exec",
+    ];
+
+    /// Reads the config file given with `--config <file>` (or `sunset.cfg` if it exists)
+    /// and applies the command line overrides on top of it.
+    ///
+    /// Errors fall back to the default settings, the engine should still start with a broken config.
+    /// They are kept in `load_errors` as nothing can be logged before the engine installs its logger.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> Self {
+        use crate::core::script::flag_values;
+
+        let mut errors = vec![];
+
+        let mut config = match flag_values("--config").last() {
+            Some(path) => EngineConfig::from_file(path).unwrap_or_else(|err| {
+                errors.push(format!("Could not read config {path}: {err}"));
+                EngineConfig::default()
+            }),
+            None if std::path::Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                EngineConfig::from_file(DEFAULT_CONFIG_PATH).unwrap_or_else(|err| {
+                    errors.push(format!(
+                        "Could not read config {DEFAULT_CONFIG_PATH}: {err}"
+                    ));
+                    EngineConfig::default()
+                })
+            }
+            None => EngineConfig::default(),
+        };
+
+        for key in EngineConfig::KEYS {
+            let values = flag_values(&format!("--{}", key.replace('_', "-")));
+            if values.is_empty() {
+                continue;
+            }
+
+            if let Err(err) = config.set_all(key, &values) {
+                errors.push(format!("Ignored command line setting: {err}"));
+            }
+        }
+
+        config.load_errors = errors;
+        config
+    }
+
+    /// There are no config files on the web.
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> Self {
+        EngineConfig::default()
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, ConfigError> {
+        EngineConfig::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses a config file, the settings it does not mention keep their default value.
+    pub fn parse(source: &str) -> Result<Self, ConfigError> {
+        let mut config = EngineConfig::default();
+        let mut windows = vec![];
+        let mut scripts: Option<Vec<String>> = None;
+
+        for (i, text) in source.lines().enumerate() {
+            let line = i + 1;
+
+            let text = strip_comment(text).trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            let (key, value) = text.split_once('=').ok_or_else(|| ConfigError::Invalid {
+                line,
+                reason: "expected \"<setting> = <value>\"".into(),
+            })?;
+            let (key, value) = (key.trim(), value.trim());
+
+            let invalid = |err: ConfigError| ConfigError::Invalid {
+                line,
+                reason: err.to_string(),
+            };
+
+            // Windows and scripts listed in the file replace the default ones
+            if key == "window" {
+                windows.push(parse_window(value).map_err(invalid)?);
+            } else if key == "exec" {
+                let scripts = scripts.get_or_insert_with(Vec::new);
+                if value != "none" {
+                    scripts.push(value.to_owned());
+                }
+            } else {
+                config.set(key, value).map_err(invalid)?;
+            }
+        }
+
+        if !windows.is_empty() {
+            config.windows = windows;
+        }
+        if let Some(scripts) = scripts {
+            config.exec = scripts;
+        }
+
+        Ok(config)
+    }
+
+    /// Sets one setting from its text value, `window` adds a window and `exec` a script.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = |reason: &str| ConfigError::InvalidValue {
+            key: key.to_owned(),
+            value: value.to_owned(),
+            reason: reason.to_owned(),
+        };
+        let threads = || match value.parse::<usize>() {
+            Ok(0) | Err(_) => Err(invalid("expected a thread count above 0")),
+            Ok(threads) => Ok(threads),
+        };
+
+        match key {
+            "apps" => self.apps = value.split_whitespace().map(str::to_owned).collect(),
+            "asset_mode" => {
+                self.asset_mode = match value {
+                    "local" => AssetMode::Local,
+                    "server" => AssetMode::Server,
+                    _ => return Err(invalid("expected local or server")),
+                }
+            }
+            "asset_root" => self.asset_root = value.to_owned(),
+            "asset_address" => self.asset_address = value.to_owned(),
+            "window" => self.windows.push(parse_window(value)?),
+            "log_level" => {
+                self.log_level = value
+                    .parse()
+                    .map_err(|_| invalid("expected trace, debug, info, warn or error"))?
+            }
+            "log_filters" => {
+                self.log_filters = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|filter| !filter.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            }
+            "log_file" => {
+                self.log_file = match value {
+                    "" | "none" => None,
+                    path => Some(path.to_owned()),
+                }
+            }
+            "log_file_size" => {
+                self.log_file_size = value
+                    .parse()
+                    .map_err(|_| invalid("expected a size in bytes"))?
+            }
+            "log_file_keep" => {
+                self.log_file_keep = value
+                    .parse()
+                    .map_err(|_| invalid("expected a number of files"))?
+            }
+            "log_history" => {
+                self.log_history = value
+                    .parse()
+                    .map_err(|_| invalid("expected a number of lines"))?
+            }
+            "stdin_cli" => {
+                self.stdin_cli = value
+                    .parse()
+                    .map_err(|_| invalid("expected true or false"))?
+            }
+            "cli_threads" => self.cli_threads = threads()?,
+            "event_loop_threads" => self.event_loop_threads = threads()?,
+            "task_threads" => self.task_threads = threads()?,
+            "console" => {
+                self.console = match value {
+                    "" | "none" => None,
+                    address => Some(address.to_owned()),
+                }
+            }
+            "console_allow_remote" => {
+                self.console_allow_remote = value
+                    .parse()
+                    .map_err(|_| invalid("expected true or false"))?
+            }
+            "aliases_file" => {
+                self.aliases_file = match value {
+                    "" | "none" => None,
+                    path => Some(path.to_owned()),
+                }
+            }
+            "exec" => match value {
+                "none" => self.exec.clear(),
+                path => self.exec.push(path.to_owned()),
+            },
+            _ => return Err(ConfigError::UnknownKey(key.to_owned())),
+        }
+
+        Ok(())
+    }
+
+    /// Sets a setting given several times (Ex: on the command line), the last value wins
+    /// except for `window` and `exec` where the given windows or scripts replace the configured ones.
+    pub fn set_all(&mut self, key: &str, values: &[String]) -> Result<(), ConfigError> {
+        if key == "window" {
+            self.windows = values
+                .iter()
+                .map(|value| parse_window(value))
+                .collect::<Result<_, _>>()?;
+
+            return Ok(());
+        }
+
+        if key == "exec" {
+            self.exec = values
+                .iter()
+                .filter(|path| *path != "none")
+                .cloned()
+                .collect();
+
+            return Ok(());
+        }
+
+        match values.last() {
+            Some(value) => self.set(key, value),
+            None => Ok(()),
+        }
+    }
+
+    /// The settings in the config file format.
+    pub fn describe(&self) -> String {
+        let mut settings = vec![
+            ("apps", self.apps.join(" ")),
+            (
+                "asset_mode",
+                match self.asset_mode {
+                    AssetMode::Local => "local".into(),
+                    AssetMode::Server => "server".into(),
+                },
+            ),
+            ("asset_root", self.asset_root.clone()),
+            ("asset_address", self.asset_address.clone()),
+        ];
+
+        for window in &self.windows {
+            settings.push((
+                "window",
+                format!("{} {} {}", window.name, window.width, window.height),
+            ));
+        }
+
+        settings.extend([
+            ("log_level", self.log_level.as_str().to_ascii_lowercase()),
+            ("log_filters", self.log_filters.join(", ")),
+            ("log_file", self.log_file.clone().unwrap_or("none".into())),
+            ("log_file_size", self.log_file_size.to_string()),
+            ("log_file_keep", self.log_file_keep.to_string()),
+            ("log_history", self.log_history.to_string()),
+            ("stdin_cli", self.stdin_cli.to_string()),
+            ("cli_threads", self.cli_threads.to_string()),
+            ("event_loop_threads", self.event_loop_threads.to_string()),
+            ("task_threads", self.task_threads.to_string()),
+            ("console", self.console.clone().unwrap_or("none".into())),
+            (
+                "console_allow_remote",
+                self.console_allow_remote.to_string(),
+            ),
+            (
+                "aliases_file",
+                self.aliases_file.clone().unwrap_or("none".into()),
+            ),
+        ]);
+
+        if self.exec.is_empty() {
+            settings.push(("exec", "none".into()));
+        }
+        for path in &self.exec {
+            settings.push(("exec", path.clone()));
+        }
+
+        settings
+            .iter()
+            .map(|(key, value)| format!("{key} = {value}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// `<name> <width> <height>`
+fn parse_window(value: &str) -> Result<WindowConfig, ConfigError> {
+    let invalid = || ConfigError::InvalidValue {
+        key: "window".into(),
+        value: value.to_owned(),
+        reason: "expected \"<name> <width> <height>\"".into(),
+    };
+
+    let mut fields = value.split_whitespace();
+    let (Some(name), Some(width), Some(height), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return Err(invalid());
+    };
+
+    Ok(WindowConfig {
+        name: name.to_owned(),
+        width: width.parse().map_err(|_| invalid())?,
+        height: height.parse().map_err(|_| invalid())?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_start_a_line_or_a_word() {
+        let config = EngineConfig::parse(
+            "# local assets\n\
+             asset_mode = local # not the server\n\
+             asset_root = assets#2/\n\
+             log_level = debug",
+        )
+        .unwrap();
+
+        assert_eq!(config.asset_mode, AssetMode::Local);
+        assert_eq!(config.asset_root, "assets#2/");
+        assert_eq!(config.log_level, Level::DEBUG);
+    }
+
+    #[test]
+    fn invalid_lines_are_reported_with_their_number() {
+        assert!(matches!(
+            EngineConfig::parse("asset_mode = local\nasset_mode"),
+            Err(ConfigError::Invalid { line: 2, .. })
+        ));
+        assert!(matches!(
+            EngineConfig::parse("asset_mode = somewhere"),
+            Err(ConfigError::Invalid { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::channel,
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tracing::{error, info, warn};
+
+use crate::core::{
+    app::*,
+    cli::parse_cli_line,
+    command_queue::*,
+    command_registry::*,
+    command_result::{CommandOutput, CommandReport},
+    events::EventKind,
+    logging::subscribe_logs_with_recent,
+    proxy::EngineProxy,
+};
+
+/// Port the console listens on when none is given.
+pub const DEFAULT_CONSOLE_PORT: u16 = 7979;
+
+/// Number of recent log lines sent to a client when it connects.
+const CONSOLE_LOG_BACKLOG: usize = 50;
+
+type Clients = Arc<Mutex<Vec<(SocketAddr, TcpStream)>>>;
+
+/// Remote console: accepts the same `app args...` lines as the stdin CLI over TCP
+/// and streams the engine's log lines back to every connected client.
+/// The result of each command (`<app> <args>: ok` or `<app> <args>: error: ...`) is sent to the client that issued it.
+///
+/// Only loopback addresses are accepted unless `allow_remote` is set, the console runs no authentication.
+///
+/// # Example
+///
+/// ```ignore
+/// engine.insert_app("console", Box::new(RemoteConsole::default())).await;
+///
+/// // From another terminal
+/// // $ nc 127.0.0.1 7979
+/// // default_scene add --name Cube --model models/test/duck.glb
+/// ```
+pub struct RemoteConsole {
+    pub address: SocketAddr,
+    pub allow_remote: bool,
+
+    commands: Vec<Command>,
+    clients: Clients,
+    listening: Arc<AtomicBool>,
+    listener_thread: Option<JoinHandle<()>>,
+}
+
+impl Default for RemoteConsole {
+    fn default() -> Self {
+        RemoteConsole::new(SocketAddr::from(([127, 0, 0, 1], DEFAULT_CONSOLE_PORT)))
+    }
+}
+
+impl RemoteConsole {
+    pub fn new(address: SocketAddr) -> Self {
+        RemoteConsole {
+            address,
+            allow_remote: false,
+            commands: vec![],
+            clients: Arc::new(Mutex::new(vec![])),
+            listening: Arc::new(AtomicBool::new(false)),
+            listener_thread: None,
+        }
+    }
+
+    /// Accepts clients from other machines, the address has to be bound to a non loopback interface as well.
+    pub fn allow_remote(mut self, allow_remote: bool) -> Self {
+        self.allow_remote = allow_remote;
+        self
+    }
+
+    pub fn process_console_command(&mut self, mut cmd: Command, args: ParsedArgs) {
+        match args.command() {
+            "clients" => {
+                let clients = self.clients.lock().unwrap();
+                let mut lines = vec![format!("{} console clients connected", clients.len())];
+                lines.extend(clients.iter().map(|(address, _)| format!("  {address}")));
+                let message = lines.join("\n");
+
+                cmd.task = Some(Box::new(move || {
+                    Ok(CommandOutput::message(message.clone()))
+                }));
+            }
+            _ => {
+                cmd.task = RemoteConsole::unsupported(cmd.args.as_deref().unwrap_or_default());
+            }
+        }
+
+        cmd.processed = true;
+        self.commands.push(cmd);
+    }
+
+    fn listen(&mut self, proxy: EngineProxy) {
+        if !accepts(self.allow_remote, &self.address) {
+            error!(
+                "Console address {} is not a loopback address, set allow_remote to listen on it",
+                self.address
+            );
+            return;
+        }
+
+        let listener = match TcpListener::bind(self.address) {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Console could not listen on {}: {err}", self.address);
+                return;
+            }
+        };
+
+        // Polled so the thread can notice the console shutting down
+        listener
+            .set_nonblocking(true)
+            .expect("Could not set console listener to non blocking");
+
+        info!("Console listening on {}", self.address);
+
+        self.listening.store(true, Ordering::SeqCst);
+
+        let listening = Arc::clone(&self.listening);
+        let clients = Arc::clone(&self.clients);
+        let allow_remote = self.allow_remote;
+
+        let thread = std::thread::Builder::new()
+            .name("Console".into())
+            .spawn(move || {
+                while listening.load(Ordering::SeqCst) && proxy.is_running() {
+                    match listener.accept() {
+                        Ok((stream, address)) => {
+                            if !accepts(allow_remote, &address) {
+                                warn!("Console refused remote client {address}");
+                                continue;
+                            }
+
+                            accept_client(stream, address, proxy.clone(), Arc::clone(&clients));
+                        }
+                        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                            std::thread::sleep(Duration::from_millis(50));
+                        }
+                        Err(err) => {
+                            error!("Console stopped accepting clients: {err}");
+                            break;
+                        }
+                    }
+                }
+            })
+            .expect("Could not spawn console thread");
+
+        self.listener_thread = Some(thread);
+    }
+}
+
+/// Whether the console may listen on or accept clients from `address`.
+fn accepts(allow_remote: bool, address: &SocketAddr) -> bool {
+    allow_remote || address.ip().is_loopback()
+}
+
+fn accept_client(stream: TcpStream, address: SocketAddr, proxy: EngineProxy, clients: Clients) {
+    if let Err(err) = stream.set_nonblocking(false) {
+        error!("Console client {address}: {err}");
+        return;
+    }
+
+    let (Ok(reader), Ok(mut writer), Ok(mut replies_writer)) =
+        (stream.try_clone(), stream.try_clone(), stream.try_clone())
+    else {
+        error!("Console could not share the stream of client {address}");
+        return;
+    };
+
+    info!("Console client {address} connected");
+    clients.lock().unwrap().push((address, stream));
+
+    // Log lines are streamed until the client disconnects, starting with the most recent ones
+    let (backlog, logs) = subscribe_logs_with_recent(CONSOLE_LOG_BACKLOG);
+    std::thread::spawn(move || {
+        for line in backlog {
+            if writeln!(writer, "{line}").is_err() {
+                return;
+            }
+        }
+
+        for line in logs {
+            if writer.write_all(line.as_bytes()).is_err() {
+                break;
+            }
+        }
+    });
+
+    // The results of the client's commands are only sent to that client
+    let (replies, reports) = channel::<CommandReport>();
+    std::thread::spawn(move || {
+        for report in reports {
+            let reply = format!("{}\n", report.describe());
+            if replies_writer.write_all(reply.as_bytes()).is_err() {
+                break;
+            }
+        }
+    });
+
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            info!("Console {address}: {line}");
+
+            if proxy
+                .send_command(parse_cli_line(line).reply_to(replies.clone()))
+                .is_err()
+            {
+                error!("Engine is no longer receiving commands!");
+                break;
+            }
+        }
+
+        info!("Console client {address} disconnected");
+
+        let mut clients = clients.lock().unwrap();
+        if let Some(index) = clients.iter().position(|(client, _)| *client == address) {
+            let (_, stream) = clients.remove(index);
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    });
+}
+
+#[async_trait(?Send)]
+impl App for RemoteConsole {
+    fn get_name(&self) -> String {
+        "Console".into()
+    }
+
+    fn init(&mut self, elp: EngineProxy) {
+        self.listen(elp);
+    }
+
+    fn update(&mut self, _delta_time: f32) -> Vec<Command> {
+        self.commands.drain(0..self.commands.len()).collect()
+    }
+
+    fn shutdown(&mut self) {
+        self.listening.store(false, Ordering::SeqCst);
+
+        // Waits for the listener to be closed so a restarted console can bind the address again
+        if let Some(thread) = self.listener_thread.take() {
+            let _ = thread.join();
+        }
+
+        for (_, stream) in self.clients.lock().unwrap().drain(..) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![]
+    }
+
+    fn commands(&self) -> Vec<CommandDesc> {
+        vec![CommandDesc::new(
+            "clients",
+            "Lists the connected console clients",
+        )]
+    }
+
+    async fn process_command(&mut self, cmd: Command, args: ParsedArgs) {
+        self.process_console_command(cmd, args);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::engine::Engine;
+
+    #[test]
+    fn refuses_remote_clients_by_default() {
+        let remote = SocketAddr::from(([192, 168, 1, 20], 50000));
+        assert!(!accepts(false, &remote));
+        assert!(accepts(true, &remote));
+
+        assert!(accepts(false, &SocketAddr::from(([127, 0, 0, 1], 50000))));
+        assert!(accepts(
+            false,
+            &SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 50000))
+        ));
+    }
+
+    #[test]
+    fn only_listens_on_every_interface_when_allowed() {
+        let (proxy, _events) = Engine::new().headless_proxy();
+        let address = SocketAddr::from(([0, 0, 0, 0], 0));
+
+        let mut console = RemoteConsole::new(address);
+        console.init(proxy.clone());
+        assert!(!console.listening.load(Ordering::SeqCst));
+
+        let mut console = RemoteConsole::new(address).allow_remote(true);
+        console.init(proxy);
+        assert!(console.listening.load(Ordering::SeqCst));
+        console.shutdown();
+    }
+}
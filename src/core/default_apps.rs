@@ -1,25 +1,72 @@
-use crate::{core::app::App, gallery::scene, renderer, window::windower};
+use crate::{
+    assets::asset_server::AssetServer,
+    core::{
+        app::App, cli::CLI, config::EngineConfig, profile_app::ProfileApp, scheduler::Scheduler,
+    },
+    gallery::scene,
+    renderer,
+    window::windower,
+};
 
-pub fn default_apps() -> Vec<(String, Box<dyn App>)> {
-    let windower = windower::Windower::default();
+/// Apps that need a window or a GPU, left out by `headless_apps`.
+const WINDOWED_APPS: [&str; 2] = ["windower", "sun"];
 
-    let sun = renderer::sun::Sun::default();
-    let scene = scene::Scene::new();
+/// The apps listed in `config.apps`, in order, followed by the remote console if `config.console` is set.
+pub fn default_apps(config: &EngineConfig) -> Vec<(String, Box<dyn App>)> {
+    let mut apps: Vec<(String, Box<dyn App>)> = config
+        .apps
+        .iter()
+        .filter_map(|name| Some((name.clone(), app_from_name(name, config)?)))
+        .collect();
 
-    // Asset server for http requests
+    // Remote console on the given address (Ex: `--console 127.0.0.1:7979`)
     #[cfg(not(target_arch = "wasm32"))]
-    let asset_server =
-        crate::assets::asset_server::AssetServer::new("as-http.angel-sunset.app:8080".into());
+    if let Some(address) = &config.console {
+        match address.parse() {
+            Ok(address) => apps.push((
+                "console".into(),
+                Box::new(
+                    crate::core::console::RemoteConsole::new(address)
+                        .allow_remote(config.console_allow_remote),
+                ),
+            )),
+            Err(err) => tracing::error!("Invalid console address {address}: {err}"),
+        }
+    }
 
-    // Asset server for web requests
-    #[cfg(target_arch = "wasm32")]
-    let asset_server =
-        crate::assets::asset_server::AssetServer::new("as-wss.angel-sunset.app".into());
+    apps
+}
+
+/// The apps of `config.apps` that do not need a window or a GPU (used by the headless runner).
+pub fn headless_apps(config: &EngineConfig) -> Vec<(String, Box<dyn App>)> {
+    config
+        .apps
+        .iter()
+        .filter(|name| !WINDOWED_APPS.contains(&name.as_str()))
+        .filter_map(|name| Some((name.clone(), app_from_name(name, config)?)))
+        .collect()
+}
+
+/// Creates the built-in app inserted under `name` (Ex: `default_scene`).
+fn app_from_name(name: &str, config: &EngineConfig) -> Option<Box<dyn App>> {
+    let app: Box<dyn App> = match name {
+        "windower" => Box::new(windower::Windower::new(config.windows.clone())),
+        "sun" => Box::new(renderer::sun::Sun::default()),
+        "asset_server" => Box::new(AssetServer::from_config(config)),
+        "default_scene" => Box::new(scene::Scene::new()),
+        "cli" => Box::new(CLI {
+            commands: vec![],
+            proxy: None,
+        }),
+        "profile" => Box::new(ProfileApp::default()),
+        "schedule" => Box::new(Scheduler::default()),
+        #[cfg(not(target_arch = "wasm32"))]
+        "log" => Box::new(crate::core::log_app::LogApp::new(config)),
+        _ => {
+            tracing::error!("Unknown app <{name}> in config, expected one of windower, sun, asset_server, default_scene, profile, schedule, log or cli");
+            return None;
+        }
+    };
 
-    vec![
-        ("windower".into(), Box::new(windower)),
-        ("sun".into(), Box::new(sun)),
-        ("asset_server".into(), Box::new(asset_server)),
-        ("default_scene".into(), Box::new(scene)),
-    ]
+    Some(app)
 }
@@ -0,0 +1,818 @@
+use std::sync::{
+    mpsc::{Receiver, Sender},
+    Arc, Mutex,
+};
+
+use async_std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tracing::{debug, error, info};
+
+use std::collections::HashMap;
+
+use crate::assets::handle::HandleRegistry;
+use crate::core::{
+    alias::Aliases,
+    app::App,
+    builder::EngineBuilder,
+    command_queue::Command,
+    command_result::{reply, CommandFailure},
+    config::EngineConfig,
+    default_apps::default_apps,
+    events::CommandEvent,
+    profiler::{ProfileControl, ScopeKind},
+    proxy::{EngineProxy, EngineStatus},
+    script::{Script, ScriptError, ScriptRun},
+    state::{AppError, State},
+    tasks::set_task_threads,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::core::{
+    cli::run_cli,
+    default_apps::headless_apps,
+    headless::replay_headless,
+    recording::{Recorder, RecordingError},
+    script::flag_values,
+};
+#[cfg(target_arch = "wasm32")]
+use crate::gallery::scene::{DEFAULT_SCENE_SCRIPT, DEFAULT_SCENE_SOURCE};
+use crate::prelude::windower::NewWindowProps;
+
+/// An engine instance: owns the `State` (apps, command queue, proxy) and its run flags.
+///
+/// Cloning an `Engine` gives another handle to the same instance, the instance is dropped together with its last handle.
+/// Several engines can live in the same process (Ex: headless integration tests running in parallel),
+/// winit however only allows a single event loop per process, so only one of them can be `run` with windows.
+///
+/// Engines are usually set up with an `EngineBuilder`.
+///
+/// # Example
+///
+/// ```ignore
+/// let engine = Engine::with_config(EngineConfig::load());
+/// engine.insert_app("my_app", Box::new(MyApp::default())).await;
+/// engine.run().await;
+/// ```
+#[derive(Clone)]
+pub struct Engine {
+    state: Arc<RwLock<State>>,
+    status: Arc<EngineStatus>,
+    config: Arc<EngineConfig>,
+    startup: Arc<Mutex<Startup>>,
+    /// CLI aliases, shared with the proxies so the `cli` app can change them.
+    aliases: Arc<Mutex<Aliases>>,
+    /// Asset handles of this engine, shared with the proxies so its apps agree on the asset ids.
+    handles: HandleRegistry,
+
+    command_sender: Sender<Command>,
+    command_receiver: Arc<Mutex<Receiver<Command>>>,
+}
+
+/// Apps and commands handed to an engine before it runs (see `EngineBuilder`), taken when its apps are initialized.
+#[derive(Default)]
+pub(crate) struct Startup {
+    pub apps: Vec<(String, Box<dyn App>)>,
+    /// Parsed by the builder, an invalid script is reported once the engine logs.
+    pub script: Option<Result<Script, ScriptError>>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        let (command_sender, command_receiver) = std::sync::mpsc::channel();
+
+        Self {
+            state: Default::default(),
+            status: Default::default(),
+            config: Default::default(),
+            startup: Default::default(),
+            aliases: Default::default(),
+            handles: Default::default(),
+            command_sender,
+            command_receiver: Arc::new(Mutex::new(command_receiver)),
+        }
+    }
+}
+
+impl Engine {
+    /// An engine with the default settings, see `EngineConfig`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An engine started with `config` (Ex: `EngineConfig::load()` to read the config file and command line).
+    pub fn with_config(config: EngineConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            ..Self::default()
+        }
+    }
+
+    /// Sets up an engine: its config, built-in apps, own apps and startup commands.
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::new()
+    }
+
+    /// An engine started with `config`, inserting `startup.apps` next to the built-in ones
+    /// and running `startup.script` once they are initialized.
+    pub(crate) fn with_startup(config: EngineConfig, startup: Startup) -> Self {
+        let engine = Self::with_config(config);
+        *engine.startup.lock().unwrap() = startup;
+
+        engine
+    }
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    pub async fn read(&self) -> RwLockReadGuard<'_, State> {
+        self.state.read().await
+    }
+
+    pub async fn write(&self) -> RwLockWriteGuard<'_, State> {
+        self.state.write().await
+    }
+
+    pub fn status(&self) -> &EngineStatus {
+        &self.status
+    }
+
+    pub fn initialized(&self) -> bool {
+        self.status.initialized()
+    }
+
+    pub fn finish_init(&self) {
+        self.status.finish_init();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status.is_running()
+    }
+
+    pub fn terminate(&self) {
+        self.status.terminate();
+    }
+
+    /// Creates a proxy for this engine that is not connected to a winit event loop,
+    /// together with the receiving end of the events sent through it.
+    pub fn headless_proxy(&self) -> (EngineProxy, std::sync::mpsc::Receiver<CommandEvent>) {
+        EngineProxy::headless(
+            self.command_sender.clone(),
+            Arc::clone(&self.status),
+            Arc::clone(&self.aliases),
+            self.handles.clone(),
+        )
+    }
+
+    pub async fn get_proxy(&self) -> EngineProxy {
+        self.read().await.event_loop_proxy.clone().unwrap()
+    }
+
+    pub async fn insert_app(&self, app_name: &str, app: Box<dyn App>) -> Result<(), AppError> {
+        self.write().await.insert_app(app_name, app)
+    }
+
+    /// Shuts down and removes the app inserted under `app_name`.
+    pub async fn remove_app(&self, app_name: &str) -> Result<Box<dyn App>, AppError> {
+        self.write().await.remove_app(app_name)
+    }
+
+    /// Replaces the app inserted under `app_name`, returning the old one.
+    pub async fn replace_app(
+        &self,
+        app_name: &str,
+        app: Box<dyn App>,
+    ) -> Result<Box<dyn App>, AppError> {
+        self.write().await.replace_app(app_name, app)
+    }
+
+    /// Starts running `script`: its lines are dispatched one per frame, each once the previous one succeeded.
+    /// How the script ended is logged under `name`.
+    pub async fn exec_script(&self, name: &str, script: Script) {
+        self.write()
+            .await
+            .scripts
+            .push(ScriptRun::new(name, script));
+    }
+
+    /// Reads the script at `path` and starts running it, see `Script`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn exec_script_file(&self, path: &str) -> Result<(), ScriptError> {
+        let script = Script::from_file(path, &HashMap::new())?;
+
+        info!("Running {} commands from script {path}", script.lines.len());
+        self.exec_script(path, script).await;
+
+        Ok(())
+    }
+
+    /// Records the inputs of every following frame to `path`, see `Recorder`.
+    /// A session recorded from startup (`--record <file>`) is replayed exactly with `--replay <file>`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn start_recording(&self, path: &str) -> Result<(), RecordingError> {
+        let recorder = Recorder::create(path)?;
+
+        if let Some(previous) = self.write().await.recorder.replace(recorder) {
+            info!("Stopped recording to {}", previous.path());
+            previous.finish()?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn stop_recording(&self) {
+        let Some(recorder) = self.write().await.recorder.take() else {
+            info!("No session is being recorded");
+            return;
+        };
+
+        let path = recorder.path().to_owned();
+        let frames = recorder.frame();
+        match recorder.finish() {
+            Ok(()) => info!("Recorded {frames} frames to {path}"),
+            Err(err) => error!("Could not finish recording {path}: {err}"),
+        }
+    }
+
+    /// Applies the process wide settings of the config, before anything is logged or spawned.
+    pub(crate) fn apply_config(&self) {
+        init_trace(&self.config);
+        set_task_threads(self.config.task_threads);
+
+        for err in &self.config.load_errors {
+            error!("{err}");
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &self.config.aliases_file {
+            match Aliases::load(path) {
+                Ok(aliases) => *self.aliases.lock().unwrap() = aliases,
+                Err(err) => error!("Could not read aliases from {path}: {err}"),
+            }
+        }
+
+        debug!("Engine config:\n{}", self.config.describe());
+    }
+
+    async fn init(&self) -> winit::event_loop::EventLoop<CommandEvent> {
+        self.apply_config();
+
+        let event_loop = winit::event_loop::EventLoopBuilder::<CommandEvent>::with_user_event()
+            .build()
+            .unwrap();
+
+        let event_loop_proxy = EngineProxy::winit(
+            event_loop.create_proxy(),
+            self.command_sender.clone(),
+            Arc::clone(&self.status),
+            Arc::clone(&self.aliases),
+            self.handles.clone(),
+        );
+
+        self.init_apps(event_loop_proxy.clone(), default_apps(&self.config))
+            .await;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = flag_values("--record").last() {
+            match self.start_recording(path).await {
+                Ok(()) => info!("Recording session to {path}"),
+                Err(err) => error!("Could not record to {path}: {err}"),
+            }
+        }
+
+        event_loop
+    }
+
+    /// Inserts and initializes `apps` and the startup apps, handing each of them `proxy` to send events back into the engine.
+    pub(crate) async fn init_apps(
+        &self,
+        proxy: EngineProxy,
+        mut apps: Vec<(String, Box<dyn App>)>,
+    ) {
+        let startup = std::mem::take(&mut *self.startup.lock().unwrap());
+        apps.extend(startup.apps);
+
+        // Scoped to make sure the lock is dropped
+        {
+            let mut state_lock = self.write().await;
+            state_lock.event_loop_proxy = Some(proxy.clone());
+
+            for app in apps {
+                if let Err(err) = state_lock.insert_app(&app.0, app.1) {
+                    error!("Could not insert app <{}>: {err}", app.0);
+                }
+            }
+
+            for app in state_lock.apps_in_order() {
+                app.init(proxy.clone())
+            }
+        }
+
+        match startup.script {
+            Some(Ok(script)) => {
+                info!("Running {} startup commands", script.lines.len());
+                self.exec_script("startup", script).await;
+            }
+            Some(Err(err)) => error!("Startup commands not run: {err}"),
+            None => {}
+        }
+
+        // Ran as events so they are handled by the event loop (and recorded), after the apps' own startup commands
+        for path in &self.config.exec {
+            if proxy
+                .send_event(CommandEvent::ExecScript(path.clone()))
+                .is_err()
+            {
+                error!("Could not queue startup script {path}");
+            }
+        }
+
+        self.update(0.0).await;
+    }
+
+    /// Runs one frame that took `delta_time` seconds: the fixed ticks of the `Clock` that fit in it
+    /// (`App::fixed_update`), then `App::update`, then the commands they returned.
+    pub async fn update(&self, delta_time: f32) {
+        let profiler = self.read().await.profiler.clone();
+        let _frame = profiler.scope(ScopeKind::Frame, "frame");
+
+        // Commands queued through a proxy (Ex: from the CLI thread) since the last update
+        let received: Vec<Command> = self.command_receiver.lock().unwrap().try_iter().collect();
+
+        // Expanded before being recorded, a replay does not depend on the aliases it runs with
+        let mut received: Vec<Command> = {
+            let state = self.read().await;
+            received
+                .into_iter()
+                .filter_map(|cmd| self.expand_alias(&state, cmd))
+                .collect()
+        };
+
+        // Recorded with the received commands, a replay does not read the scripts again
+        received.extend(self.write().await.step_scripts());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.write()
+            .await
+            .record(|recorder| recorder.record_frame(delta_time, &received));
+
+        let mut frame_commands: Vec<Option<Command>> = received.into_iter().map(Some).collect();
+        {
+            let mut state_lock = self.write().await;
+
+            let ticks = state_lock.clock.advance(delta_time);
+            let fixed_delta = state_lock.clock.fixed_delta();
+
+            for _ in 0..ticks {
+                for (name, app) in state_lock.named_apps_in_order() {
+                    let _scope = profiler.scope(ScopeKind::FixedUpdate, name);
+                    let cmds = app.fixed_update(fixed_delta);
+
+                    for cmd in cmds {
+                        frame_commands.push(Some(cmd));
+                    }
+                }
+            }
+
+            for (name, app) in state_lock.named_apps_in_order() {
+                let _scope = profiler.scope(ScopeKind::Update, name);
+                let cmds = app.update(delta_time);
+
+                for cmd in cmds {
+                    frame_commands.push(Some(cmd));
+                }
+            }
+        }
+
+        {
+            let mut state_lock = self.write().await;
+
+            for command in &mut frame_commands {
+                if !command.as_ref().unwrap().processed {
+                    let Some(cmd) = self.expand_alias(&state_lock, command.take().unwrap()) else {
+                        continue;
+                    };
+                    let app = cmd.app.clone();
+                    let _scope = profiler.scope(ScopeKind::Command, &app);
+                    state_lock.dispatch_command(cmd).await;
+                }
+            }
+        }
+
+        {
+            self.write()
+                .await
+                .command_queue
+                .add_commands(frame_commands);
+        }
+
+        {
+            let elp = self.get_proxy().await;
+            let mut state = self.write().await;
+            state.command_queue.execute(elp.clone(), &profiler);
+
+            #[cfg(not(target_arch = "wasm32"))]
+            for name in state.plugins_to_close() {
+                if elp.send_event(CommandEvent::ClosePlugin(name)).is_err() {
+                    error!("Could not close a plugin, the engine is no longer running");
+                }
+            }
+        }
+    }
+
+    /// Replaces a command using an alias by the command the alias runs, see `Aliases`.
+    /// A command the alias cannot be expanded for is answered with the error and dropped.
+    fn expand_alias(&self, state: &State, cmd: Command) -> Option<Command> {
+        if cmd.processed {
+            return Some(cmd);
+        }
+
+        let app = cmd.app.clone();
+        let args = cmd.args.clone();
+        let reply_to = cmd.reply_to.clone();
+
+        let is_app =
+            |name: &str| state.apps.contains_key(name) || state.stopped_apps.contains_key(name);
+        match self.aliases.lock().unwrap().expand(cmd, is_app) {
+            Ok(cmd) => Some(cmd),
+            Err(err) => {
+                reply(
+                    reply_to.as_ref(),
+                    &app,
+                    args.as_deref(),
+                    Err(CommandFailure::Failed(err.to_string())),
+                );
+                None
+            }
+        }
+    }
+
+    pub async fn process_window_events(
+        &self,
+        event: winit::event::WindowEvent,
+        id: winit::window::WindowId,
+        delta_time: f32,
+    ) {
+        let mut state_lock = self.write().await;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        state_lock.record(|recorder| recorder.record_window_event(&event));
+
+        for app in state_lock.apps_in_order() {
+            app.process_window_event(&event, id, delta_time).await;
+        }
+    }
+
+    /// Hands `event` to the apps subscribed to its kind, then handles the events addressed to the engine.
+    pub async fn process_user_events(&self, event: CommandEvent, delta_time: f32) {
+        let kind = event.kind();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let recording = self.read().await.recorder.is_some();
+        #[cfg(target_arch = "wasm32")]
+        let recording = false;
+
+        // Events no app subscribed to (Ex: the engine events) do not need the write lock
+        if recording || self.read().await.has_subscribers(kind) {
+            let mut state_lock = self.write().await;
+
+            #[cfg(not(target_arch = "wasm32"))]
+            state_lock.record(|recorder| recorder.record_event(&event));
+
+            let profiler = state_lock.profiler.clone();
+            for (name, app) in state_lock.named_subscribers_in_order(kind) {
+                let _scope = profiler.event_scope(name, kind);
+                app.process_user_event(&event, delta_time).await;
+            }
+        }
+
+        self.process_engine_event(&event).await;
+    }
+
+    /// Handles the events addressed to the engine itself rather than to its apps.
+    async fn process_engine_event(&self, event: &CommandEvent) {
+        match event {
+            CommandEvent::ListApps => {
+                info!("{}", self.read().await.describe_apps());
+            }
+            CommandEvent::StopApp(name) => match self.write().await.stop_app(name) {
+                Ok(()) => info!("Stopped app <{name}>"),
+                Err(err) => error!("Could not stop app <{name}>: {err}"),
+            },
+            CommandEvent::RestartApp(name) => match self.write().await.restart_app(name) {
+                Ok(()) => info!("Restarted app <{name}>"),
+                Err(err) => error!("Could not restart app <{name}>: {err}"),
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            CommandEvent::LoadPlugin(path) => match self.write().await.load_plugin(path) {
+                Ok(name) => info!("Loaded plugin <{name}> from {path}"),
+                Err(err) => error!("Could not load plugin {path}: {err}"),
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            CommandEvent::UnloadPlugin(name) => match self.write().await.unload_plugin(name) {
+                Ok(apps) => info!("Unloaded plugin <{name}> and its apps: {apps:?}"),
+                Err(err) => error!("Could not unload plugin <{name}>: {err}"),
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            CommandEvent::ClosePlugin(name) => match self.write().await.close_plugin(name) {
+                Ok(()) => debug!("Closed the library of plugin <{name}>"),
+                Err(err) => error!("Could not close plugin <{name}>: {err}"),
+            },
+            #[cfg(target_arch = "wasm32")]
+            CommandEvent::LoadPlugin(_)
+            | CommandEvent::UnloadPlugin(_)
+            | CommandEvent::ClosePlugin(_) => {
+                error!("Plugins are not supported on the web");
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            CommandEvent::ExecScript(path) => {
+                if let Err(err) = self.exec_script_file(path).await {
+                    error!("Script {path}: {err}");
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            CommandEvent::ExecScript(path) if path == DEFAULT_SCENE_SCRIPT => {
+                match Script::parse(DEFAULT_SCENE_SOURCE, &HashMap::new()) {
+                    Ok(script) => self.exec_script(path, script).await,
+                    Err(err) => error!("Script {path}: {err}"),
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            CommandEvent::ExecScript(_) => {
+                error!("Script files are not supported on the web");
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            CommandEvent::StartRecording(path) => match self.start_recording(path).await {
+                Ok(()) => info!("Recording session to {path}"),
+                Err(err) => error!("Could not record to {path}: {err}"),
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            CommandEvent::StopRecording => self.stop_recording().await,
+            #[cfg(target_arch = "wasm32")]
+            CommandEvent::StartRecording(_) | CommandEvent::StopRecording => {
+                error!("Session recording is not supported on the web");
+            }
+            CommandEvent::Time(control) => {
+                let mut state_lock = self.write().await;
+                match state_lock.clock.apply(*control) {
+                    Ok(()) => info!("Clock: {}", state_lock.clock.describe()),
+                    Err(err) => error!("{err}"),
+                }
+            }
+            CommandEvent::ListTasks => {
+                info!("{}", self.read().await.command_queue.tasks().describe());
+            }
+            CommandEvent::CancelTask(id) => {
+                if self.read().await.command_queue.tasks().cancel(*id) {
+                    info!("Cancelling task {id}");
+                } else {
+                    error!("No running task {id}");
+                }
+            }
+            CommandEvent::TaskFinished(report) => {
+                self.read().await.profiler.record_task(report);
+            }
+            CommandEvent::Profile(control) => self.control_profiler(control).await,
+            CommandEvent::Exit if self.is_running() => {
+                let mut state_lock = self.write().await;
+                state_lock.shutdown_apps();
+
+                let cancelled = state_lock.command_queue.tasks().cancel_all();
+                if cancelled > 0 {
+                    info!("Cancelled {cancelled} running tasks");
+                }
+
+                drop(state_lock);
+                self.terminate();
+            }
+            _ => {}
+        }
+    }
+
+    async fn control_profiler(&self, control: &ProfileControl) {
+        let profiler = self.read().await.profiler.clone();
+
+        match control {
+            ProfileControl::Start => {
+                profiler.start_capture();
+                info!("Profile capture started");
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ProfileControl::Stop(path) => {
+                match profiler.stop_capture().and_then(|capture| {
+                    capture.write(path)?;
+                    Ok(capture)
+                }) {
+                    Ok(capture) => info!(
+                        "Wrote {} profiled scopes ({:.3}s) to {path}",
+                        capture.events.len(),
+                        capture.duration.as_secs_f32()
+                    ),
+                    Err(err) => error!("Could not write profile capture to {path}: {err}"),
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            ProfileControl::Stop(_) => {
+                let _ = profiler.stop_capture();
+                error!("Profile captures cannot be written on the web");
+            }
+            ProfileControl::Stats => info!("{}", profiler.describe()),
+            ProfileControl::Reset => {
+                profiler.reset_stats();
+                info!("Profile statistics cleared");
+            }
+        }
+    }
+
+    pub async fn process_device_events(
+        &self,
+        event: winit::event::DeviceEvent,
+        device_id: winit::event::DeviceId,
+        delta_time: f32,
+    ) {
+        let mut state_lock = self.write().await;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        state_lock.record(|recorder| recorder.record_device_event(&event));
+
+        for app in state_lock.apps_in_order() {
+            app.process_device_event(&event, device_id, delta_time)
+                .await;
+        }
+    }
+
+    pub async fn on_new_window_requested(
+        &self,
+        props: NewWindowProps,
+        window: winit::window::Window,
+    ) {
+        self.write().await.on_new_window_requested(props, window);
+    }
+
+    /// Inserts the default apps and runs the engine inside a winit event loop until it exits.
+    pub async fn run(&self) {
+        let event_loop = self.init().await;
+
+        info!("Initialzied State!");
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.config.stdin_cli {
+            let builder = std::thread::Builder::new().name("CLI".into());
+
+            let runtime_cli = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(self.config.cli_threads)
+                .build()
+                .unwrap();
+
+            let proxy = self.get_proxy().await;
+            builder
+                .spawn(move || {
+                    runtime_cli.block_on(run_cli(proxy));
+                })
+                .unwrap();
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(self.config.event_loop_threads)
+            .build()
+            .unwrap();
+
+        let mut current_time = web_time::Instant::now();
+        let mut delta_time = 0.0;
+
+        event_loop
+            .run(move |event, elwt: &winit::event_loop::EventLoopWindowTarget<CommandEvent>| {
+                if !self.is_running() {
+                    elwt.exit()
+                }
+
+                elwt.set_control_flow(winit::event_loop::ControlFlow::Poll);
+
+                match event {
+                    winit::event::Event::UserEvent(event) => {
+                        cfg_if::cfg_if! {
+                            if #[cfg(not(target_arch = "wasm32"))] {
+                                runtime.block_on(self.process_user_events(event.clone(), delta_time));
+                            }
+                            else {
+                                let engine = self.clone();
+                                let user_event = event.clone();
+                                wasm_bindgen_futures::spawn_local(async move {
+                                    engine.process_user_events(user_event, delta_time).await
+                                });
+                            }
+                        }
+                        if let CommandEvent::RequestNewWindow(props) = event {
+                            let window = winit::window::WindowBuilder::new()
+                                .with_inner_size(winit::dpi::Size::Physical(props.size))
+                                .with_title(props.name.clone())
+                                .build(elwt)
+                                .expect("Could not create new window T-T");
+
+                            cfg_if::cfg_if! {
+                                if #[cfg(not(target_arch = "wasm32"))] {
+                                    runtime.block_on(self.on_new_window_requested(props, window));
+                                }
+                                else {
+                                    let engine = self.clone();
+                                    wasm_bindgen_futures::spawn_local(async move {
+                                        engine.on_new_window_requested(props, window).await
+                                    });
+                                }
+                            }
+                        }
+                    }
+                    winit::event::Event::WindowEvent { window_id, event } => {
+                        cfg_if::cfg_if! {
+                            if #[cfg(not(target_arch = "wasm32"))] {
+                                runtime.block_on(self.process_window_events(event.clone(), window_id, delta_time));
+                            }
+                            else {
+                                let engine = self.clone();
+                                wasm_bindgen_futures::spawn_local(async move {
+                                    engine.process_window_events(event, window_id, delta_time).await
+                                });
+                            }
+                        }
+                    }
+                    winit::event::Event::DeviceEvent { device_id, event } => {
+                        cfg_if::cfg_if! {
+                            if #[cfg(not(target_arch = "wasm32"))] {
+                                runtime.block_on(self.process_device_events(event.clone(), device_id, delta_time));
+                            }
+                            else {
+                                let engine = self.clone();
+                                wasm_bindgen_futures::spawn_local(async move {
+                                    engine.process_device_events(event, device_id, delta_time).await
+                                });
+                            }
+                        }
+                    }
+                    // Once per loop iteration, after every pending event was handled:
+                    // the events above get the delta time of the last frame
+                    winit::event::Event::AboutToWait => {
+                        // Calculate frame time (delta time)
+                        let new_time = web_time::Instant::now();
+                        let frame_time = (new_time - current_time).as_nanos();
+                        delta_time = frame_time as f32 * 0.000000001;
+                        current_time = new_time;
+
+                        #[cfg(target_arch = "wasm32")]
+                        {
+                            if delta_time < 0.0001 {
+                                delta_time = 0.0001;
+                            }
+                        }
+
+                        cfg_if::cfg_if! {
+                            if #[cfg(not(target_arch = "wasm32"))] {
+                                runtime.block_on(self.update(delta_time));
+                            }
+                            else {
+                                let engine = self.clone();
+                                wasm_bindgen_futures::spawn_local(async move {
+                                    engine.update(delta_time).await
+                                });
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            })
+            .unwrap();
+    }
+}
+
+/// Creates an engine with the apps of the config file (see `EngineConfig::load`) and runs it until it exits.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub async fn run() {
+    let config = EngineConfig::load();
+
+    // A recorded session is replayed without windows, frame by frame
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = flag_values("--replay").last() {
+        let apps = headless_apps(&config);
+        replay_headless(Engine::with_config(config), apps, path).await;
+        return;
+    }
+
+    EngineBuilder::from_config(config).run().await;
+}
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn init_trace(config: &EngineConfig) {
+    console_error_panic_hook::set_once();
+    tracing_wasm::set_as_global_default_with_config(
+        tracing_wasm::WASMLayerConfigBuilder::new()
+            .set_max_level(config.log_level)
+            .build(),
+    );
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn init_trace(config: &EngineConfig) {
+    crate::core::logging::init_logging(config);
+}
@@ -1,5 +1,14 @@
-use std::sync::Arc;
+use std::{
+    any::{Any, TypeId},
+    sync::Arc,
+};
 
+use crate::assets::loader::LoadedAsset;
+use crate::core::{
+    profiler::ProfileControl,
+    tasks::{TaskId, TaskReport},
+    time::TimeControl,
+};
 use crate::prelude::{
     model_component::ModelComponent, pipeline::PipelineDesc, sun::RenderFrameDesc,
     windower::NewWindowProps, Asset, ChangeComponentState,
@@ -19,11 +28,166 @@ pub enum CommandEvent {
     Asset(Asset),
     RequestCreateModel(ModelComponent),
     ChangedAssets(Vec<String>),
+    /// An asset decoded by an `AssetLoader`, see `AssetServer::register_loader`.
+    LoadedAsset(LoadedAsset),
 
     SignalChange(ChangeComponentState),
 
+    ListApps,
+    StopApp(String),
+    RestartApp(String),
+    LoadPlugin(String),
+    UnloadPlugin(String),
+    /// Closes the library of an unloaded plugin, see `State::plugins_to_close`.
+    ClosePlugin(String),
+    ExecScript(String),
+    StartRecording(String),
+    StopRecording,
+
+    Time(TimeControl),
+
+    ListTasks,
+    CancelTask(TaskId),
+    /// Sent once an async command task ended, see `TaskTracker`.
+    TaskFinished(TaskReport),
+
+    Profile(ProfileControl),
+
+    /// Event of a type defined outside of the engine, see `CustomEvent`.
+    Custom(CustomEvent),
+
+    Exit,
+    None,
+}
+
+/// What an app subscribes to, see `App::subscriptions`: one kind per `CommandEvent` variant,
+/// custom events are told apart by their type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    RequestNewWindow,
+    OnWindowClosed,
+    OnWindowCreated,
+    RequestPipeline,
+    RequestDestroyBuffer,
+    RenderFrame,
+    Asset,
+    RequestCreateModel,
+    ChangedAssets,
+    LoadedAsset,
+    SignalChange,
+    ListApps,
+    StopApp,
+    RestartApp,
+    LoadPlugin,
+    UnloadPlugin,
+    ClosePlugin,
+    ExecScript,
+    StartRecording,
+    StopRecording,
+    Time,
+    ListTasks,
+    CancelTask,
+    TaskFinished,
+    Profile,
+    Custom(TypeId),
     Exit,
     None,
+    /// Every kind, the default of `App::subscriptions`. No event is of this kind.
+    All,
+}
+
+impl EventKind {
+    /// Kind of the custom events carrying a `T`.
+    pub fn of<T: Any>() -> EventKind {
+        EventKind::Custom(TypeId::of::<T>())
+    }
+}
+
+/// Wraps a value of any user defined type so it can travel as a `CommandEvent::Custom`.
+///
+/// # Example
+///
+/// ```ignore
+/// struct Explosion { radius: f32 }
+///
+/// // Sender
+/// proxy.send_event(CommandEvent::custom(Explosion { radius: 2.0 }))?;
+///
+/// // Receiver, subscribed with `EventKind::of::<Explosion>()`
+/// if let Some(explosion) = event.downcast_ref::<Explosion>() { ... }
+/// ```
+#[derive(Clone)]
+pub struct CustomEvent {
+    type_name: &'static str,
+    data: Arc<dyn Any + Send + Sync>,
+}
+
+impl CustomEvent {
+    pub fn new<T: Any + Send + Sync>(data: T) -> Self {
+        CustomEvent {
+            type_name: std::any::type_name::<T>(),
+            data: Arc::new(data),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn kind(&self) -> EventKind {
+        EventKind::Custom(self.data.as_ref().type_id())
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.data.downcast_ref()
+    }
+}
+
+impl CommandEvent {
+    pub fn custom<T: Any + Send + Sync>(data: T) -> Self {
+        CommandEvent::Custom(CustomEvent::new(data))
+    }
+
+    /// The payload of a custom event, if this is one carrying a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        match self {
+            CommandEvent::Custom(custom) => custom.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> EventKind {
+        match self {
+            CommandEvent::RequestNewWindow(_) => EventKind::RequestNewWindow,
+            CommandEvent::OnWindowClosed(_) => EventKind::OnWindowClosed,
+            CommandEvent::OnWindowCreated(_) => EventKind::OnWindowCreated,
+            CommandEvent::RequestPipeline(_) => EventKind::RequestPipeline,
+            CommandEvent::RequestDestroyBuffer(_) => EventKind::RequestDestroyBuffer,
+            CommandEvent::RenderFrame(_) => EventKind::RenderFrame,
+            CommandEvent::Asset(_) => EventKind::Asset,
+            CommandEvent::RequestCreateModel(_) => EventKind::RequestCreateModel,
+            CommandEvent::ChangedAssets(_) => EventKind::ChangedAssets,
+            CommandEvent::LoadedAsset(_) => EventKind::LoadedAsset,
+            CommandEvent::SignalChange(_) => EventKind::SignalChange,
+            CommandEvent::ListApps => EventKind::ListApps,
+            CommandEvent::StopApp(_) => EventKind::StopApp,
+            CommandEvent::RestartApp(_) => EventKind::RestartApp,
+            CommandEvent::LoadPlugin(_) => EventKind::LoadPlugin,
+            CommandEvent::UnloadPlugin(_) => EventKind::UnloadPlugin,
+            CommandEvent::ClosePlugin(_) => EventKind::ClosePlugin,
+            CommandEvent::ExecScript(_) => EventKind::ExecScript,
+            CommandEvent::StartRecording(_) => EventKind::StartRecording,
+            CommandEvent::StopRecording => EventKind::StopRecording,
+            CommandEvent::Time(_) => EventKind::Time,
+            CommandEvent::ListTasks => EventKind::ListTasks,
+            CommandEvent::CancelTask(_) => EventKind::CancelTask,
+            CommandEvent::TaskFinished(_) => EventKind::TaskFinished,
+            CommandEvent::Profile(_) => EventKind::Profile,
+            CommandEvent::Custom(custom) => custom.kind(),
+            CommandEvent::Exit => EventKind::Exit,
+            CommandEvent::None => EventKind::None,
+        }
+    }
 }
 
 impl std::fmt::Debug for CommandEvent {
@@ -42,6 +206,27 @@ impl std::fmt::Debug for CommandEvent {
             }
             CommandEvent::RenderFrame(_) => write!(f, "Event <RenderFrame>"),
             CommandEvent::Asset(asset) => write!(f, "Event <Asset> with: {asset:?}"),
+            CommandEvent::ListApps => write!(f, "Event <ListApps>"),
+            CommandEvent::StopApp(name) => write!(f, "Event <StopApp> with: {name:?}"),
+            CommandEvent::RestartApp(name) => write!(f, "Event <RestartApp> with: {name:?}"),
+            CommandEvent::LoadPlugin(path) => write!(f, "Event <LoadPlugin> with: {path:?}"),
+            CommandEvent::UnloadPlugin(name) => write!(f, "Event <UnloadPlugin> with: {name:?}"),
+            CommandEvent::ClosePlugin(name) => write!(f, "Event <ClosePlugin> with: {name:?}"),
+            CommandEvent::ExecScript(path) => write!(f, "Event <ExecScript> with: {path:?}"),
+            CommandEvent::StartRecording(path) => {
+                write!(f, "Event <StartRecording> with: {path:?}")
+            }
+            CommandEvent::StopRecording => write!(f, "Event <StopRecording>"),
+            CommandEvent::Time(control) => write!(f, "Event <Time> with: {control:?}"),
+            CommandEvent::ListTasks => write!(f, "Event <ListTasks>"),
+            CommandEvent::CancelTask(id) => write!(f, "Event <CancelTask> with: {id}"),
+            CommandEvent::TaskFinished(report) => {
+                write!(f, "Event <TaskFinished> with: {report:?}")
+            }
+            CommandEvent::Profile(control) => write!(f, "Event <Profile> with: {control:?}"),
+            CommandEvent::Custom(custom) => {
+                write!(f, "Event <Custom> with: {}", custom.type_name())
+            }
             CommandEvent::Exit => write!(f, "Event <Exit>"),
             CommandEvent::None => write!(f, "Event <None>"),
             CommandEvent::RequestCreateModel(model_comp) => {
@@ -50,6 +235,13 @@ impl std::fmt::Debug for CommandEvent {
             CommandEvent::ChangedAssets(paths) => {
                 write!(f, "Event <ChangedAssets> with: {paths:?}")
             }
+            CommandEvent::LoadedAsset(loaded) => {
+                write!(
+                    f,
+                    "Event <LoadedAsset> with: {} ({})",
+                    loaded.path, loaded.asset_type
+                )
+            }
             CommandEvent::SignalChange(e) => {
                 write!(f, "Event <SignalChange> with: {e:?}")
             }
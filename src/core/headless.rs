@@ -0,0 +1,401 @@
+use std::sync::mpsc::Receiver;
+
+use tracing::{error, info, warn};
+
+use crate::core::{
+    app::App,
+    engine::Engine,
+    events::CommandEvent,
+    recording::{
+        is_recordable, replay_device_id, replay_window_id, RecordedFrame, RecordedInput, Recording,
+    },
+};
+
+/// How the headless runner advances its frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeadlessClock {
+    /// Frames only advance when the host calls `HeadlessRunner::step` with its own delta time.
+    Manual,
+    /// Every frame advances by `delta_time` seconds.
+    /// When `realtime` is set, `run` sleeps so frames are paced to wall-clock time (Ex: server process),
+    /// otherwise frames run back to back (Ex: CI and integration tests).
+    FixedStep { delta_time: f32, realtime: bool },
+}
+
+/// Drives an `Engine` without a winit event loop, windows or the stdin CLI.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut runner = HeadlessRunner::new(
+///     headless_apps(&EngineConfig::default()),
+///     HeadlessClock::FixedStep { delta_time: 1.0 / 60.0, realtime: false },
+/// )
+/// .await;
+///
+/// runner.run_frames(120).await;
+/// ```
+pub struct HeadlessRunner {
+    engine: Engine,
+    receiver: Receiver<CommandEvent>,
+    clock: HeadlessClock,
+
+    frame: u64,
+    elapsed: f32,
+}
+
+impl HeadlessRunner {
+    /// Creates a new engine with the default settings running `apps`.
+    pub async fn new(apps: Vec<(String, Box<dyn App>)>, clock: HeadlessClock) -> Self {
+        HeadlessRunner::with_engine(Engine::new(), apps, clock).await
+    }
+
+    /// Runs `apps` on top of an existing engine (Ex: one that already had apps inserted by the host).
+    pub async fn with_engine(
+        engine: Engine,
+        apps: Vec<(String, Box<dyn App>)>,
+        clock: HeadlessClock,
+    ) -> Self {
+        engine.apply_config();
+
+        let (proxy, receiver) = engine.headless_proxy();
+
+        // Nothing is rendered in headless mode so there is no renderer to signal the end of initialization
+        engine.finish_init();
+
+        // A fixed step runner runs exactly one simulation tick per step
+        if let HeadlessClock::FixedStep { delta_time, .. } = clock {
+            engine.write().await.clock.set_fixed_delta(delta_time);
+        }
+
+        engine.init_apps(proxy, apps).await;
+
+        info!("Initialzied headless State!");
+
+        HeadlessRunner {
+            engine,
+            receiver,
+            clock,
+            frame: 0,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Number of frames stepped since the runner was created.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Simulated time in seconds since the runner was created.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn clock(&self) -> HeadlessClock {
+        self.clock
+    }
+
+    /// Advances the engine by one frame: dispatches the user events sent since the last frame,
+    /// then updates every app and executes the queued commands.
+    ///
+    /// # Returns
+    ///
+    /// `false` once the engine has been asked to exit.
+    pub async fn step(&mut self, delta_time: f32) -> bool {
+        // Only the events pending at the start of the frame are handled,
+        // events sent while processing them are handled next frame (same as under winit)
+        let events: Vec<CommandEvent> = self.receiver.try_iter().collect();
+
+        self.step_with_events(events, delta_time).await
+    }
+
+    /// Advances the engine by one recorded frame: the recorded events and commands replace
+    /// the recordable ones sent since the last frame (Ex: asset callbacks), see `Recorder`.
+    ///
+    /// # Returns
+    ///
+    /// `false` once the engine has been asked to exit.
+    pub async fn replay_frame(&mut self, frame: &RecordedFrame) -> bool {
+        let events: Vec<CommandEvent> = self
+            .receiver
+            .try_iter()
+            .filter(|event| !is_recordable(event))
+            .collect();
+
+        // The commands dispatched by the scripts are part of the recording
+        self.engine.write().await.scripts.clear();
+
+        for input in &frame.inputs {
+            match input {
+                RecordedInput::Event(event) => {
+                    self.handle_event(event.clone(), frame.delta_time).await
+                }
+                RecordedInput::Window { event, .. } => {
+                    self.engine
+                        .process_window_events(event.clone(), replay_window_id(), frame.delta_time)
+                        .await
+                }
+                RecordedInput::Device { event, .. } => {
+                    self.engine
+                        .process_device_events(event.clone(), replay_device_id(), frame.delta_time)
+                        .await
+                }
+            }
+        }
+
+        let proxy = self.engine.get_proxy().await;
+        for cmd in frame.commands() {
+            if proxy.send_command(cmd).is_err() {
+                error!("Engine is no longer receiving commands!");
+            }
+        }
+
+        self.step_with_events(events, frame.delta_time).await
+    }
+
+    /// Replays every frame of `recording`, stopping early if the engine exits.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the engine exited.
+    pub async fn replay(&mut self, recording: &Recording) -> bool {
+        for frame in &recording.frames {
+            if !self.replay_frame(frame).await {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    async fn step_with_events(&mut self, events: Vec<CommandEvent>, delta_time: f32) -> bool {
+        for event in events {
+            self.handle_event(event, delta_time).await;
+        }
+
+        if !self.engine.is_running() {
+            return false;
+        }
+
+        self.engine.update(delta_time).await;
+
+        self.frame += 1;
+        self.elapsed += delta_time;
+
+        self.engine.is_running()
+    }
+
+    async fn handle_event(&mut self, event: CommandEvent, delta_time: f32) {
+        self.engine
+            .process_user_events(event.clone(), delta_time)
+            .await;
+
+        // Exit and the app management events are handled by the engine itself
+        if let CommandEvent::RequestNewWindow(props) = event {
+            warn!("Headless mode cannot open window <{}>", props.name);
+        }
+    }
+
+    /// Runs `frames` fixed steps back to back (regardless of `realtime`).
+    ///
+    /// # Returns
+    ///
+    /// `false` if the engine exited before all frames were run.
+    pub async fn run_frames(&mut self, frames: u64) -> bool {
+        let HeadlessClock::FixedStep { delta_time, .. } = self.clock else {
+            warn!("run_frames needs a fixed step clock, use step() with a manual clock");
+            return self.engine.is_running();
+        };
+
+        for _ in 0..frames {
+            if !self.step(delta_time).await {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Runs fixed steps until the engine exits (Ex: `cli exit` or a `CommandEvent::Exit`).
+    pub async fn run(&mut self) {
+        let HeadlessClock::FixedStep {
+            delta_time,
+            realtime,
+        } = self.clock
+        else {
+            warn!("run needs a fixed step clock, use step() with a manual clock");
+            return;
+        };
+
+        let frame_duration = std::time::Duration::from_secs_f32(delta_time);
+
+        loop {
+            let frame_start = web_time::Instant::now();
+
+            if !self.step(delta_time).await {
+                break;
+            }
+
+            if realtime {
+                let remaining = frame_duration.saturating_sub(frame_start.elapsed());
+                async_std::task::sleep(remaining).await;
+            }
+        }
+    }
+}
+
+/// Replays the session recorded at `path` on the given apps of `engine`, frame by frame with the recorded delta times.
+pub async fn replay_headless(engine: Engine, apps: Vec<(String, Box<dyn App>)>, path: &str) {
+    let recording = match Recording::from_file(path) {
+        Ok(recording) => recording,
+        Err(err) => {
+            error!("Could not replay {path}: {err}");
+            return;
+        }
+    };
+
+    let mut runner = HeadlessRunner::with_engine(engine, apps, HeadlessClock::Manual).await;
+
+    if runner.replay(&recording).await {
+        info!("Replayed {} frames from {path}", recording.frames.len());
+    } else {
+        info!("Engine exited after {} replayed frames", runner.frame());
+    }
+}
+
+/// Runs the given apps headless with a fixed step clock until the engine exits.
+pub async fn run_headless(apps: Vec<(String, Box<dyn App>)>, delta_time: f32) {
+    let mut runner = HeadlessRunner::new(
+        apps,
+        HeadlessClock::FixedStep {
+            delta_time,
+            realtime: true,
+        },
+    )
+    .await;
+
+    runner.run().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{
+        assets::{asset_server::AssetServer, AssetStatus},
+        core::{
+            cli::parse_cli_line, command_queue::Command, command_registry::ParsedArgs,
+            proxy::EngineProxy,
+        },
+        ecs::model_component::ModelComponent,
+        gallery::scene::Scene,
+    };
+
+    /// Exits the engine as soon as it is initialized.
+    struct ExitApp;
+
+    #[async_trait(?Send)]
+    impl App for ExitApp {
+        fn get_name(&self) -> String {
+            "Exit".into()
+        }
+
+        fn init(&mut self, elp: EngineProxy) {
+            elp.send_event(CommandEvent::Exit).unwrap();
+        }
+
+        fn update(&mut self, _delta_time: f32) -> Vec<Command> {
+            vec![]
+        }
+
+        async fn process_command(&mut self, _cmd: Command, _args: ParsedArgs) {}
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+    }
+
+    #[test]
+    fn runs_a_scene_with_local_assets() {
+        let root = std::env::temp_dir().join(format!("sunset-headless-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("notes.txt"), "hello").unwrap();
+
+        pollster::block_on(async {
+            let apps: Vec<(String, Box<dyn App>)> = vec![
+                (
+                    "asset_server".into(),
+                    Box::new(AssetServer::local(root.to_string_lossy().into_owned())),
+                ),
+                ("default_scene".into(), Box::new(Scene::new())),
+            ];
+            let mut runner = HeadlessRunner::new(
+                apps,
+                HeadlessClock::FixedStep {
+                    delta_time: 1.0 / 60.0,
+                    realtime: false,
+                },
+            )
+            .await;
+
+            let (proxy, _) = runner.engine().headless_proxy();
+            for line in [
+                "asset_server get notes.txt string",
+                "default_scene add --name Duck --model models/missing.glb",
+            ] {
+                proxy.send_command(parse_cli_line(line)).unwrap();
+            }
+
+            // The assets are read by tasks, the frames run until their events are handled
+            let mut loaded = false;
+            for _ in 0..500 {
+                assert!(runner.run_frames(1).await);
+
+                let mut state = runner.engine().write().await;
+                let scene = state.apps.get_mut("default_scene").unwrap();
+                let scene = scene.as_any_mut().downcast_mut::<Scene>().unwrap();
+                let Some(duck) = scene.get_entity_with_name("Duck") else {
+                    continue;
+                };
+                let model = scene.world.get::<ModelComponent>(duck).unwrap();
+                let model_status = model.handle.as_ref().unwrap().status();
+
+                let asset_server = state.apps.get("asset_server").unwrap();
+                let asset_server = asset_server.as_any().downcast_ref::<AssetServer>().unwrap();
+                let notes = asset_server.cached_assets.get_by_path("notes.txt");
+
+                if let (AssetStatus::NotFound, Some(notes)) = (model_status, notes) {
+                    assert_eq!(notes.data, b"hello");
+                    loaded = true;
+                    break;
+                }
+
+                drop(state);
+                std::thread::sleep(Duration::from_millis(2));
+            }
+
+            assert!(loaded, "the assets were not received");
+            assert!(runner.frame() > 1);
+        });
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn run_headless_returns_once_the_engine_exits() {
+        let apps: Vec<(String, Box<dyn App>)> = vec![("exit".into(), Box::new(ExitApp))];
+
+        pollster::block_on(run_headless(apps, 0.01));
+    }
+}
@@ -0,0 +1,148 @@
+use async_trait::async_trait;
+
+use crate::core::{
+    app::*,
+    command_queue::*,
+    command_registry::*,
+    command_result::{CommandFailure, CommandOutput},
+    config::EngineConfig,
+    events::EventKind,
+    logging::{
+        log_file_path, log_filter, recent_logs, reset_log_filter, set_log_filter, start_log_file,
+        stop_log_file,
+    },
+    proxy::EngineProxy,
+};
+
+/// Controls the engine's log at runtime (Ex: `log level sunset::renderer=debug`, `log tail 50`).
+///
+/// # Example
+///
+/// ```ignore
+/// engine.insert_app("log", Box::new(LogApp::new(engine.config()))).await;
+/// ```
+pub struct LogApp {
+    commands: Vec<Command>,
+
+    /// Rotation of the files opened with `log file`, see `start_log_file`.
+    file_size: u64,
+    file_keep: usize,
+}
+
+impl Default for LogApp {
+    fn default() -> Self {
+        LogApp::new(&EngineConfig::default())
+    }
+}
+
+impl LogApp {
+    pub fn new(config: &EngineConfig) -> Self {
+        LogApp {
+            commands: vec![],
+            file_size: config.log_file_size,
+            file_keep: config.log_file_keep,
+        }
+    }
+
+    pub fn process_log_command(&mut self, mut cmd: Command, args: ParsedArgs) {
+        let result = match args.command() {
+            "level" => {
+                let filter = args.get_str("filter").unwrap();
+                set_log_filter(filter)
+                    .and_then(|()| log_filter())
+                    .map(|current| CommandOutput::message(format!("log filter: {current}")))
+                    .map_err(|err| CommandFailure::Failed(err.to_string()))
+            }
+            "reset" => reset_log_filter()
+                .and_then(|()| log_filter())
+                .map(|current| CommandOutput::message(format!("log filter: {current}")))
+                .map_err(|err| CommandFailure::Failed(err.to_string())),
+            "tail" => {
+                let count = args.get_uint("count").unwrap() as usize;
+                Ok(CommandOutput::message(recent_logs(count).join("\n")))
+            }
+            "file" => {
+                let path = args.get_str("path").unwrap();
+                start_log_file(path, self.file_size, self.file_keep)
+                    .map(|()| CommandOutput::message(format!("writing logs to {path}")))
+                    .map_err(|err| CommandFailure::Failed(format!("{path}: {err}")))
+            }
+            "file_stop" => match stop_log_file() {
+                Some(path) => Ok(CommandOutput::message(format!(
+                    "stopped writing logs to {path}"
+                ))),
+                None => Err(CommandFailure::Failed(
+                    "logs are not written to a file".into(),
+                )),
+            },
+            "status" => Ok(CommandOutput::message(format!(
+                "log filter: {}\nlog file: {}",
+                log_filter().unwrap_or_else(|err| err.to_string()),
+                log_file_path().unwrap_or("none".into())
+            ))),
+            _ => Err(CommandFailure::Unsupported(
+                cmd.args.as_deref().unwrap_or_default().to_owned(),
+            )),
+        };
+
+        cmd.processed = true;
+        cmd.task = Some(Box::new(move || result.clone()));
+
+        self.commands.push(cmd);
+    }
+}
+
+#[async_trait(?Send)]
+impl App for LogApp {
+    fn get_name(&self) -> String {
+        "Log".into()
+    }
+
+    fn init(&mut self, _elp: EngineProxy) {}
+
+    fn update(&mut self, _delta_time: f32) -> Vec<Command> {
+        self.commands.drain(..).collect()
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![]
+    }
+
+    fn commands(&self) -> Vec<CommandDesc> {
+        vec![
+            CommandDesc::new(
+                "level",
+                "Changes the level of a module or the default level",
+            )
+            .arg(ArgDesc::positional(
+                "filter",
+                ArgType::String,
+                "<module>=<level> (Ex: sunset::renderer=debug) or <level>, comma separated",
+            )),
+            CommandDesc::new(
+                "reset",
+                "Restores the log levels the engine was started with",
+            ),
+            CommandDesc::new("tail", "Shows the most recent log lines").arg(
+                ArgDesc::positional("count", ArgType::UInt, "Number of lines").with_default("20"),
+            ),
+            CommandDesc::new("file", "Appends the log lines to a file, rotated by size").arg(
+                ArgDesc::positional("path", ArgType::String, "Path to the log file"),
+            ),
+            CommandDesc::new("file_stop", "Stops writing the log lines to a file"),
+            CommandDesc::new("status", "Shows the log levels and the log file"),
+        ]
+    }
+
+    async fn process_command(&mut self, cmd: Command, args: ParsedArgs) {
+        self.process_log_command(cmd, args);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
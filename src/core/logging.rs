@@ -0,0 +1,404 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::Write,
+    str::FromStr,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+};
+
+use once_cell::sync::{Lazy, OnceCell};
+use thiserror::Error;
+use tracing_subscriber::{
+    filter::Targets, fmt::MakeWriter, layer::SubscriberExt, reload, Registry,
+};
+
+use crate::core::config::EngineConfig;
+
+/// Number of recent log lines kept in memory when the config does not say otherwise, see `recent_logs`.
+pub const DEFAULT_LOG_HISTORY: usize = 1000;
+
+// The trace subscriber is global to the process, so are the consumers of its output
+static LOG_SUBSCRIBERS: Lazy<Mutex<Vec<Sender<String>>>> = Lazy::new(|| Mutex::new(vec![]));
+static LOG_HISTORY: Lazy<Mutex<LogHistory>> =
+    Lazy::new(|| Mutex::new(LogHistory::new(DEFAULT_LOG_HISTORY)));
+static LOG_FILE: Lazy<Mutex<Option<LogFile>>> = Lazy::new(|| Mutex::new(None));
+
+// Set by the first engine initializing the trace subscriber
+static LOG_FILTER: OnceCell<LogFilter> = OnceCell::new();
+
+#[derive(Error, Debug)]
+pub enum LogError {
+    #[error("Log io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid log filter \"{0}\", expected <level> or <module>=<level> (Ex: sunset::renderer=debug)")]
+    InvalidFilter(String),
+    #[error("the trace subscriber was not set by the engine")]
+    NotInitialized,
+}
+
+struct LogFilter {
+    handle: reload::Handle<Targets, Registry>,
+    /// Filter set from the config, restored by `reset_log_filter`.
+    initial: Targets,
+}
+
+/// Installs the engine's trace subscriber: every line is formatted once, written to stdout
+/// and, without colors, to the log history, the log file and the log subscribers.
+///
+/// The subscriber is global to the process, only the first call sets it (Ex: several headless engines in one test).
+pub(crate) fn init_logging(config: &EngineConfig) {
+    use tracing_subscriber::fmt;
+
+    let mut filter = Targets::new().with_default(config.log_level);
+    let mut invalid_filters = vec![];
+    for directive in &config.log_filters {
+        match parse_filter(directive) {
+            Ok(directives) => filter = merge_filters(filter, directives),
+            Err(err) => invalid_filters.push(err),
+        }
+    }
+
+    let (filter_layer, handle) = reload::Layer::new(filter.clone());
+
+    let fmt_layer = fmt::layer()
+        .with_target(true)
+        .with_thread_names(true)
+        .with_writer(LogWriter);
+
+    let subscriber = Registry::default().with(filter_layer).with(fmt_layer);
+
+    // A headless engine can be started several times in one process (Ex: integration tests)
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        tracing::warn!("Default trace subscriber was already set!");
+        return;
+    }
+
+    let _ = LOG_FILTER.set(LogFilter {
+        handle,
+        initial: filter,
+    });
+
+    set_log_history(config.log_history);
+
+    for err in invalid_filters {
+        tracing::error!("Ignored log filter from config: {err}");
+    }
+
+    if let Some(path) = &config.log_file {
+        if let Err(err) = start_log_file(path, config.log_file_size, config.log_file_keep) {
+            tracing::error!("Could not write logs to {path}: {err}");
+        }
+    }
+}
+
+/// Changes the level of a module (`sunset::renderer=debug`) or the default level (`info`) from now on.
+/// Several filters can be given at once, separated by commas.
+pub fn set_log_filter(directive: &str) -> Result<(), LogError> {
+    let directives = parse_filter(directive)?;
+    let filter = LOG_FILTER.get().ok_or(LogError::NotInitialized)?;
+
+    filter
+        .handle
+        .modify(|current| *current = merge_filters(current.clone(), directives))
+        .map_err(|_| LogError::NotInitialized)
+}
+
+/// Restores the filter the engine was started with.
+pub fn reset_log_filter() -> Result<(), LogError> {
+    let filter = LOG_FILTER.get().ok_or(LogError::NotInitialized)?;
+
+    filter
+        .handle
+        .reload(filter.initial.clone())
+        .map_err(|_| LogError::NotInitialized)
+}
+
+/// The current filter, Ex: `info,sunset::renderer=debug`.
+pub fn log_filter() -> Result<String, LogError> {
+    let filter = LOG_FILTER.get().ok_or(LogError::NotInitialized)?;
+
+    filter
+        .handle
+        .with_current(|current| current.to_string())
+        .map_err(|_| LogError::NotInitialized)
+}
+
+fn parse_filter(directive: &str) -> Result<Targets, LogError> {
+    let directive = directive.trim();
+    if directive.is_empty() {
+        return Err(LogError::InvalidFilter(directive.to_owned()));
+    }
+
+    Targets::from_str(directive).map_err(|_| LogError::InvalidFilter(directive.to_owned()))
+}
+
+/// `directives` replace the levels `filter` had for the same modules.
+fn merge_filters(mut filter: Targets, directives: Targets) -> Targets {
+    if let Some(level) = directives.default_level() {
+        filter = filter.with_default(level);
+    }
+
+    for (target, level) in directives.iter() {
+        filter = filter.with_target(target, level);
+    }
+
+    filter
+}
+
+/// Receives every formatted log line from now on (Ex: to stream them to a remote console).
+/// The subscription ends when the receiver is dropped.
+pub fn subscribe_logs() -> Receiver<String> {
+    let (sender, receiver) = channel();
+    LOG_SUBSCRIBERS.lock().unwrap().push(sender);
+
+    receiver
+}
+
+/// The last `count` log lines, oldest first, and a `subscribe_logs` receiver of the lines logged after them.
+/// Both are taken at once so no line is missed or received twice.
+pub fn subscribe_logs_with_recent(count: usize) -> (Vec<String>, Receiver<String>) {
+    let history = LOG_HISTORY.lock().unwrap();
+    let skip = history.lines.len().saturating_sub(count);
+    let recent = history.lines.iter().skip(skip).cloned().collect();
+
+    (recent, subscribe_logs())
+}
+
+/// The last `count` log lines, oldest first.
+pub fn recent_logs(count: usize) -> Vec<String> {
+    let history = LOG_HISTORY.lock().unwrap();
+    let skip = history.lines.len().saturating_sub(count);
+
+    history.lines.iter().skip(skip).cloned().collect()
+}
+
+/// Number of log lines kept for `recent_logs`, 0 stops keeping them.
+pub fn set_log_history(capacity: usize) {
+    let mut history = LOG_HISTORY.lock().unwrap();
+    history.capacity = capacity;
+
+    let excess = history.lines.len().saturating_sub(capacity);
+    history.lines.drain(..excess);
+}
+
+/// Appends the log lines to the file at `path` from now on, replacing the current log file.
+///
+/// # Arguments
+///
+/// * `max_size` - Size in bytes after which the file is rotated: `path` is renamed to `path.1`, `path.1` to `path.2`...
+/// * `keep` - Number of rotated files kept, the oldest one is deleted
+pub fn start_log_file(path: &str, max_size: u64, keep: usize) -> Result<(), LogError> {
+    let log_file = LogFile::open(path, max_size, keep)?;
+    *LOG_FILE.lock().unwrap() = Some(log_file);
+
+    Ok(())
+}
+
+/// Stops writing the log lines to a file.
+///
+/// # Returns
+///
+/// The path of the file that was written to, if any.
+pub fn stop_log_file() -> Option<String> {
+    LOG_FILE
+        .lock()
+        .unwrap()
+        .take()
+        .map(|log_file| log_file.path)
+}
+
+/// Path of the file the log lines are written to, if any.
+pub fn log_file_path() -> Option<String> {
+    LOG_FILE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|log_file| log_file.path.clone())
+}
+
+struct LogHistory {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl LogHistory {
+    fn new(capacity: usize) -> Self {
+        LogHistory {
+            lines: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.lines.len() >= self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+}
+
+struct LogFile {
+    path: String,
+    file: File,
+    size: u64,
+    max_size: u64,
+    keep: usize,
+}
+
+impl LogFile {
+    fn open(path: &str, max_size: u64, keep: usize) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let size = file.metadata()?.len();
+
+        Ok(LogFile {
+            path: path.to_owned(),
+            file,
+            size,
+            max_size,
+            keep,
+        })
+    }
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if self.keep == 0 {
+            self.file.set_len(0)?;
+        } else {
+            let _ = std::fs::remove_file(format!("{}.{}", self.path, self.keep));
+            for i in (1..self.keep).rev() {
+                let _ = std::fs::rename(
+                    format!("{}.{i}", self.path),
+                    format!("{}.{}", self.path, i + 1),
+                );
+            }
+            std::fs::rename(&self.path, format!("{}.1", self.path))?;
+
+            self.file = File::create(&self.path)?;
+        }
+
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+/// Writer of the formatted log lines: prints them to stdout, keeps them without colors in the log history
+/// and forwards them to the log file and the log subscribers.
+#[derive(Default)]
+pub(crate) struct LogWriter;
+
+impl<'a> MakeWriter<'a> for LogWriter {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogWriter
+    }
+}
+
+impl Write for LogWriter {
+    // Called once per event with the whole formatted line
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        std::io::stdout().lock().write_all(buf)?;
+
+        let line = strip_ansi(&String::from_utf8_lossy(buf));
+
+        if let Some(log_file) = LOG_FILE.lock().unwrap().as_mut() {
+            // Reported on stderr, logging it would write to the failing file again
+            if let Err(err) = log_file.write(line.as_bytes()) {
+                eprintln!("Could not write to log file {}: {err}", log_file.path);
+            }
+        }
+
+        // The history stays locked until the subscribers got the line, see `subscribe_logs_with_recent`
+        let mut history = LOG_HISTORY.lock().unwrap();
+        {
+            let mut subscribers = LOG_SUBSCRIBERS.lock().unwrap();
+            if !subscribers.is_empty() {
+                subscribers.retain(|subscriber| subscriber.send(line.clone()).is_ok());
+            }
+        }
+        history.push(line.trim_end().to_owned());
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()
+    }
+}
+
+/// `text` without its color escape sequences (`ESC [ ... m`).
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            stripped.push(c);
+            continue;
+        }
+
+        // Skips the parameters up to the final byte of the sequence
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_are_stripped() {
+        assert_eq!(
+            strip_ansi("\u{1b}[2m12:00\u{1b}[0m \u{1b}[32m INFO\u{1b}[0m sunset: ready"),
+            "12:00  INFO sunset: ready"
+        );
+        assert_eq!(strip_ansi("no colors"), "no colors");
+    }
+
+    #[test]
+    fn subscribers_get_each_line_once() {
+        let ours = |line: &String| line.contains("subscribers_get_each_line_once");
+
+        LogWriter
+            .write_all(b"subscribers_get_each_line_once 1\n")
+            .unwrap();
+        let (recent, logs) = subscribe_logs_with_recent(usize::MAX);
+        LogWriter
+            .write_all(b"subscribers_get_each_line_once 2\n")
+            .unwrap();
+
+        let recent: Vec<String> = recent.into_iter().filter(ours).collect();
+        let streamed: Vec<String> = logs.try_iter().filter(ours).collect();
+        assert_eq!(recent, ["subscribers_get_each_line_once 1"]);
+        assert_eq!(streamed, ["subscribers_get_each_line_once 2\n"]);
+    }
+}
@@ -1,7 +1,31 @@
+pub mod alias;
 pub mod app;
+pub mod builder;
 pub mod cli;
 pub mod command_queue;
+pub mod command_registry;
+pub mod command_result;
+pub mod config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod console;
 pub mod default_apps;
+pub mod engine;
 pub mod events;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod log_app;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod logging;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plugin;
+pub mod profile_app;
+pub mod profiler;
+pub mod proxy;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod recording;
+pub mod scheduler;
+pub mod script;
 pub mod state;
-pub mod util;
+pub mod tasks;
+pub mod time;
@@ -0,0 +1,238 @@
+use std::ffi::{c_char, CStr};
+
+use libloading::Library;
+use thiserror::Error;
+
+use crate::core::{app::App, state::AppError};
+
+/// Version of the plugin interface, bumped whenever `PluginDeclaration` or `PluginRegistrar` change.
+pub const PLUGIN_API_VERSION: u32 = 2;
+
+/// Version of the engine crate a plugin was built against, followed by a hash of its sources
+/// (Ex: `0.1.0+5f1c...`) so a plugin built against a modified engine is refused as well.
+pub const CORE_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+", env!("SUNSET_CORE_HASH"));
+
+/// Compiler the engine crate was built with, apps cross the library boundary as trait objects
+/// so engine and plugin have to agree on their layout.
+pub const RUSTC_VERSION: &str = env!("SUNSET_RUSTC_VERSION");
+
+#[doc(hidden)]
+pub const CORE_VERSION_NUL: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "+",
+    env!("SUNSET_CORE_HASH"),
+    "\0"
+);
+
+#[doc(hidden)]
+pub const RUSTC_VERSION_NUL: &str = concat!(env!("SUNSET_RUSTC_VERSION"), "\0");
+
+/// Symbol looked up in every plugin library, see `export_plugin!`.
+pub const PLUGIN_DECLARATION_SYMBOL: &[u8] = b"SUNSET_PLUGIN_DECLARATION\0";
+
+#[derive(Error, Debug)]
+pub enum PluginError {
+    #[error("Could not load plugin library: {0}")]
+    Library(#[from] libloading::Error),
+    /// `plugin` is the path of the library, its name cannot be read with another declaration layout.
+    #[error("Plugin {plugin} was built for plugin API {found}, expected {expected}")]
+    ApiMismatch {
+        plugin: String,
+        expected: u32,
+        found: u32,
+    },
+    #[error("Plugin {plugin} was built against sunset {found}, expected {expected}")]
+    CoreMismatch {
+        plugin: String,
+        expected: String,
+        found: String,
+    },
+    #[error("Plugin {plugin} was built with {found}, expected {expected}")]
+    RustcMismatch {
+        plugin: String,
+        expected: String,
+        found: String,
+    },
+    #[error("Plugin {0} is already loaded")]
+    AlreadyLoaded(String),
+    #[error("No plugin loaded with name: {0}")]
+    NotLoaded(String),
+    #[error("Plugin {0} did not register any app")]
+    NoApps(String),
+    #[error(transparent)]
+    App(#[from] AppError),
+}
+
+/// Entry point exported by a plugin library. Use `export_plugin!` rather than declaring it by hand
+/// so the versions are filled in from the crate the plugin is built against.
+///
+/// `api_version` comes first and the strings are nul-terminated C strings, so a declaration of another
+/// API version can always be told apart before anything else is read from it.
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub api_version: u32,
+    pub core_version: *const c_char,
+    pub rustc_version: *const c_char,
+    pub name: *const c_char,
+    pub register: fn(&mut PluginRegistrar),
+}
+
+// SAFETY: the strings are static and never written to
+unsafe impl Sync for PluginDeclaration {}
+
+/// Collects the apps a plugin registers, they are inserted into the `State` once registration returns.
+#[derive(Default)]
+pub struct PluginRegistrar {
+    pub(crate) apps: Vec<(String, Box<dyn App>)>,
+}
+
+impl PluginRegistrar {
+    pub fn register_app(&mut self, app_name: &str, app: Box<dyn App>) {
+        self.apps.push((app_name.to_ascii_lowercase(), app));
+    }
+}
+
+/// Exports the `PluginDeclaration` of a plugin library.
+///
+/// # Arguments
+///
+/// * `name` - String literal, the name the plugin is unloaded with (Ex: `cli unload my_plugin`)
+/// * `register` - `fn(&mut PluginRegistrar)` registering the apps of the plugin
+///
+/// # Example
+///
+/// ```ignore
+/// fn register(registrar: &mut PluginRegistrar) {
+///     registrar.register_app("my_app", Box::new(MyApp::default()));
+/// }
+///
+/// sunset::export_plugin!("my_plugin", register);
+/// ```
+#[macro_export]
+macro_rules! export_plugin {
+    ($name:literal, $register:expr) => {
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static SUNSET_PLUGIN_DECLARATION: $crate::core::plugin::PluginDeclaration =
+            $crate::core::plugin::PluginDeclaration {
+                api_version: $crate::core::plugin::PLUGIN_API_VERSION,
+                core_version: $crate::core::plugin::CORE_VERSION_NUL.as_ptr().cast(),
+                rustc_version: $crate::core::plugin::RUSTC_VERSION_NUL.as_ptr().cast(),
+                name: concat!($name, "\0").as_ptr().cast(),
+                register: $register,
+            };
+    };
+}
+
+/// A loaded plugin library and the names of the apps it registered.
+///
+/// The apps are trait objects whose code lives in `library`,
+/// they have to be removed from the `State` before the plugin is dropped.
+pub struct Plugin {
+    pub name: String,
+    pub path: String,
+    pub apps: Vec<String>,
+
+    library: Library,
+}
+
+impl Plugin {
+    /// Opens the library at `path` and checks its declaration against the running engine.
+    ///
+    /// # Returns
+    ///
+    /// The plugin together with the registrar holding its apps, not inserted anywhere yet.
+    pub fn load(path: &str) -> Result<(Plugin, PluginRegistrar), PluginError> {
+        // SAFETY: loading a library runs its initializers, plugins are trusted code picked by the user
+        let library = unsafe { Library::new(path)? };
+
+        // SAFETY: the symbol is declared by `export_plugin!`, every version of the declaration
+        // starts with `api_version` which is checked before anything else is read from the library
+        let declaration = unsafe {
+            let symbol = library.get::<*const PluginDeclaration>(PLUGIN_DECLARATION_SYMBOL)?;
+            &**symbol
+        };
+
+        if declaration.api_version != PLUGIN_API_VERSION {
+            return Err(PluginError::ApiMismatch {
+                plugin: path.to_owned(),
+                expected: PLUGIN_API_VERSION,
+                found: declaration.api_version,
+            });
+        }
+
+        // SAFETY: same API version, the strings were declared by `export_plugin!` and the library is loaded
+        let (name, core_version, rustc_version) = unsafe {
+            (
+                read_c_str(declaration.name),
+                read_c_str(declaration.core_version),
+                read_c_str(declaration.rustc_version),
+            )
+        };
+
+        if core_version != CORE_VERSION {
+            return Err(PluginError::CoreMismatch {
+                plugin: name,
+                expected: CORE_VERSION.into(),
+                found: core_version,
+            });
+        }
+
+        if rustc_version != RUSTC_VERSION {
+            return Err(PluginError::RustcMismatch {
+                plugin: name,
+                expected: RUSTC_VERSION.into(),
+                found: rustc_version,
+            });
+        }
+
+        let mut registrar = PluginRegistrar::default();
+        (declaration.register)(&mut registrar);
+
+        if registrar.apps.is_empty() {
+            return Err(PluginError::NoApps(name));
+        }
+
+        let plugin = Plugin {
+            name,
+            path: path.to_owned(),
+            apps: registrar
+                .apps
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect(),
+            library,
+        };
+
+        Ok((plugin, registrar))
+    }
+
+    /// Closes the library, every app it registered must already be dropped.
+    pub fn close(self) -> Result<(), PluginError> {
+        self.library.close()?;
+        Ok(())
+    }
+}
+
+/// A plugin whose apps were removed, kept loaded until what they queued ran (see `State::unload_plugin`).
+pub struct UnloadingPlugin {
+    pub plugin: Plugin,
+    /// Whether the `CommandEvent::ClosePlugin` closing it was sent.
+    pub close_requested: bool,
+}
+
+impl UnloadingPlugin {
+    pub fn new(plugin: Plugin) -> Self {
+        UnloadingPlugin {
+            plugin,
+            close_requested: false,
+        }
+    }
+}
+
+/// # Safety
+///
+/// `ptr` must point to a nul-terminated string that outlives the call.
+unsafe fn read_c_str(ptr: *const c_char) -> String {
+    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+}
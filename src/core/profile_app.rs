@@ -0,0 +1,95 @@
+use async_trait::async_trait;
+
+use crate::core::{
+    app::*,
+    command_queue::*,
+    command_registry::*,
+    command_result::CommandResult,
+    events::{CommandEvent, EventKind},
+    profiler::ProfileControl,
+    proxy::EngineProxy,
+};
+
+/// Controls the engine's `Profiler`: `profile start`, then `profile stop trace.json`
+/// writes everything timed in between in Chrome trace-event JSON, `profile stats` logs the rolling statistics.
+#[derive(Default)]
+pub struct ProfileApp {
+    commands: Vec<Command>,
+}
+
+impl ProfileApp {
+    pub fn process_profile_command(&mut self, mut cmd: Command, args: ParsedArgs) {
+        let control = match args.command() {
+            "start" => Some(ProfileControl::Start),
+            "stop" => Some(ProfileControl::Stop(
+                args.get_str("path").unwrap().to_owned(),
+            )),
+            "stats" => Some(ProfileControl::Stats),
+            "reset" => Some(ProfileControl::Reset),
+            _ => None,
+        };
+
+        cmd.processed = true;
+        cmd.task = match control {
+            Some(control) => ProfileApp::send_control(control),
+            None => ProfileApp::unsupported(cmd.args.as_deref().unwrap_or_default()),
+        };
+
+        self.commands.push(cmd);
+    }
+
+    fn send_control(control: ProfileControl) -> Option<Task<CommandResult>> {
+        Some(Box::new(move || {
+            Ok(vec![CommandEvent::Profile(control.clone())].into())
+        }))
+    }
+}
+
+#[async_trait(?Send)]
+impl App for ProfileApp {
+    fn get_name(&self) -> String {
+        "Profile".into()
+    }
+
+    fn init(&mut self, _elp: EngineProxy) {}
+
+    fn update(&mut self, _delta_time: f32) -> Vec<Command> {
+        self.commands.drain(..).collect()
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![]
+    }
+
+    fn commands(&self) -> Vec<CommandDesc> {
+        vec![
+            CommandDesc::new("start", "Starts recording the timed scopes of every frame"),
+            CommandDesc::new(
+                "stop",
+                "Writes the recorded scopes in Chrome trace-event JSON",
+            )
+            .arg(ArgDesc::positional(
+                "path",
+                ArgType::String,
+                "Path to the trace, opened in chrome://tracing or ui.perfetto.dev",
+            )),
+            CommandDesc::new(
+                "stats",
+                "Shows the time taken by each app's updates, commands, events and tasks",
+            ),
+            CommandDesc::new("reset", "Clears the profile statistics"),
+        ]
+    }
+
+    async fn process_command(&mut self, cmd: Command, args: ParsedArgs) {
+        self.process_profile_command(cmd, args);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,491 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use thiserror::Error;
+use web_time::Instant;
+
+use crate::core::{
+    events::EventKind,
+    tasks::{TaskId, TaskReport},
+};
+
+/// Number of samples the rolling statistics of a scope are computed over.
+pub const PROFILER_WINDOW: usize = 120;
+
+#[derive(Error, Debug)]
+pub enum ProfilerError {
+    #[error("Profile io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("no capture was started, use profile start")]
+    NotCapturing,
+}
+
+/// Controls of the engine `Profiler`, sent as `CommandEvent::Profile` (Ex: by `profile start`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileControl {
+    /// Starts recording every timed scope into a capture.
+    Start,
+    /// Ends the capture and writes it to the given file in Chrome trace-event JSON.
+    Stop(String),
+    /// Logs the rolling statistics of every timed scope.
+    Stats,
+    /// Clears the rolling statistics.
+    Reset,
+}
+
+/// What a timed scope was doing, the category of its trace events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ScopeKind {
+    /// A whole `Engine::update`.
+    Frame,
+    FixedUpdate,
+    Update,
+    /// `App::process_command`, parsing included.
+    Command,
+    /// `App::process_user_event`.
+    Event,
+    /// The blocking task of a command, ran by the `CommandQueue`.
+    Task,
+    /// The async task of a command, from its spawn to its end.
+    AsyncTask,
+}
+
+impl ScopeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScopeKind::Frame => "frame",
+            ScopeKind::FixedUpdate => "fixed_update",
+            ScopeKind::Update => "update",
+            ScopeKind::Command => "command",
+            ScopeKind::Event => "event",
+            ScopeKind::Task => "task",
+            ScopeKind::AsyncTask => "async_task",
+        }
+    }
+}
+
+/// Rolling statistics of a timed scope over its last `PROFILER_WINDOW` samples.
+#[derive(Debug, Clone, Default)]
+pub struct ScopeStats {
+    samples: VecDeque<Duration>,
+    /// Number of times the scope ran since the statistics were reset.
+    pub calls: u64,
+}
+
+impl ScopeStats {
+    fn push(&mut self, duration: Duration) {
+        if self.samples.len() >= PROFILER_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(duration);
+        self.calls += 1;
+    }
+
+    pub fn last(&self) -> Duration {
+        self.samples.back().copied().unwrap_or_default()
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
+    }
+}
+
+/// Where a trace event ran: the engine's event loop or one of the async tasks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceLane {
+    EventLoop,
+    Task(TaskId),
+}
+
+/// A timed scope recorded during a capture.
+#[derive(Debug, Clone)]
+pub struct TraceEvent {
+    pub kind: ScopeKind,
+    pub name: String,
+    pub lane: TraceLane,
+    /// Since the start of the capture.
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// The scopes timed between `Profiler::start_capture` and `Profiler::stop_capture`.
+#[derive(Debug, Clone)]
+pub struct ProfileCapture {
+    pub events: Vec<TraceEvent>,
+    pub duration: Duration,
+}
+
+impl ProfileCapture {
+    /// The capture in Chrome trace-event JSON, to be opened in `chrome://tracing` or https://ui.perfetto.dev.
+    ///
+    /// The event loop scopes are on the first thread, the async tasks are spread
+    /// over as few other threads as possible without overlapping each other.
+    pub fn to_chrome_trace(&self) -> String {
+        let mut events = vec![thread_name(0, "Event loop")];
+
+        // Lanes of the async tasks and the end of their last task
+        let mut task_lanes: Vec<Duration> = vec![];
+
+        let mut sorted: Vec<&TraceEvent> = self.events.iter().collect();
+        sorted.sort_by_key(|event| event.start);
+
+        for event in sorted {
+            let tid = match event.lane {
+                TraceLane::EventLoop => 0,
+                TraceLane::Task(_) => {
+                    let end = event.start + event.duration;
+                    let lane = match task_lanes.iter().position(|free| *free <= event.start) {
+                        Some(lane) => {
+                            task_lanes[lane] = end;
+                            lane
+                        }
+                        None => {
+                            task_lanes.push(end);
+                            events.push(thread_name(
+                                task_lanes.len(),
+                                &format!("Tasks {}", task_lanes.len()),
+                            ));
+                            task_lanes.len() - 1
+                        }
+                    };
+
+                    lane + 1
+                }
+            };
+
+            let args = match event.lane {
+                TraceLane::Task(id) => format!(r#","args":{{"task":"{id}"}}"#),
+                TraceLane::EventLoop => String::new(),
+            };
+
+            events.push(format!(
+                r#"{{"name":"{}","cat":"{}","ph":"X","ts":{:.3},"dur":{:.3},"pid":1,"tid":{tid}{args}}}"#,
+                escape_json(&event.name),
+                event.kind.as_str(),
+                event.start.as_secs_f64() * 1_000_000.0,
+                event.duration.as_secs_f64() * 1_000_000.0,
+            ));
+        }
+
+        format!(
+            "{{\"traceEvents\":[\n{}\n],\"displayTimeUnit\":\"ms\"}}\n",
+            events.join(",\n")
+        )
+    }
+
+    /// Writes the capture to `path` in Chrome trace-event JSON.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write(&self, path: &str) -> Result<(), ProfilerError> {
+        std::fs::write(path, self.to_chrome_trace())?;
+        Ok(())
+    }
+}
+
+struct Capture {
+    started: Instant,
+    events: Vec<TraceEvent>,
+}
+
+/// The statistics of event scopes are kept per kind of event, the label is only formatted when shown.
+type ScopeKey = (ScopeKind, String, Option<EventKind>);
+
+#[derive(Default)]
+struct ProfilerData {
+    stats: HashMap<ScopeKey, ScopeStats>,
+    capture: Option<Capture>,
+}
+
+/// Times the work of an engine's frames: the apps' updates, commands and events and the command tasks.
+///
+/// Every timed scope feeds rolling statistics, and is recorded as a trace event while a capture runs
+/// (`profile start` / `profile stop <file>`).
+/// Cloning a `Profiler` gives another handle to the same statistics.
+#[derive(Clone, Default)]
+pub struct Profiler {
+    data: Arc<Mutex<ProfilerData>>,
+}
+
+impl Profiler {
+    /// Times the scope until the returned guard is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let _scope = profiler.scope(ScopeKind::Update, "default_scene");
+    /// app.update(delta_time);
+    /// ```
+    pub fn scope<'a>(&'a self, kind: ScopeKind, name: &'a str) -> ProfileScope<'a> {
+        ProfileScope {
+            profiler: self,
+            kind,
+            name,
+            event: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// Times the handling of an event of kind `event` by the app `name`, see `scope`.
+    pub fn event_scope<'a>(&'a self, name: &'a str, event: EventKind) -> ProfileScope<'a> {
+        ProfileScope {
+            profiler: self,
+            kind: ScopeKind::Event,
+            name,
+            event: Some(event),
+            started: Instant::now(),
+        }
+    }
+
+    /// Records a scope that started at `started` and lasted `duration`.
+    pub fn record(
+        &self,
+        kind: ScopeKind,
+        name: &str,
+        lane: TraceLane,
+        started: Instant,
+        duration: Duration,
+    ) {
+        self.record_scope(kind, name, None, lane, started, duration);
+    }
+
+    fn record_scope(
+        &self,
+        kind: ScopeKind,
+        name: &str,
+        event: Option<EventKind>,
+        lane: TraceLane,
+        started: Instant,
+        duration: Duration,
+    ) {
+        let mut data = self.data.lock().unwrap();
+
+        data.stats
+            .entry((kind, name.to_owned(), event))
+            .or_default()
+            .push(duration);
+
+        if let Some(capture) = data.capture.as_mut() {
+            // Scopes that started before the capture are cut at its start
+            let start = started.saturating_duration_since(capture.started);
+            let duration =
+                duration.saturating_sub(capture.started.saturating_duration_since(started));
+
+            capture.events.push(TraceEvent {
+                kind,
+                name: scope_label(name, event),
+                lane,
+                start,
+                duration,
+            });
+        }
+    }
+
+    /// Records the async task of a command from its report, from its spawn to its end.
+    pub fn record_task(&self, report: &TaskReport) {
+        self.record(
+            ScopeKind::AsyncTask,
+            &report.app,
+            TraceLane::Task(report.id),
+            report.started,
+            report.elapsed,
+        );
+    }
+
+    /// Starts a new capture, dropping the current one if any.
+    pub fn start_capture(&self) {
+        self.data.lock().unwrap().capture = Some(Capture {
+            started: Instant::now(),
+            events: vec![],
+        });
+    }
+
+    pub fn stop_capture(&self) -> Result<ProfileCapture, ProfilerError> {
+        let capture = self
+            .data
+            .lock()
+            .unwrap()
+            .capture
+            .take()
+            .ok_or(ProfilerError::NotCapturing)?;
+
+        Ok(ProfileCapture {
+            events: capture.events,
+            duration: capture.started.elapsed(),
+        })
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.data.lock().unwrap().capture.is_some()
+    }
+
+    pub fn stats(&self, kind: ScopeKind, name: &str) -> Option<ScopeStats> {
+        self.data
+            .lock()
+            .unwrap()
+            .stats
+            .get(&(kind, name.to_owned(), None))
+            .cloned()
+    }
+
+    /// Statistics of the app `name` handling the events of kind `event`.
+    pub fn event_stats(&self, name: &str, event: EventKind) -> Option<ScopeStats> {
+        self.data
+            .lock()
+            .unwrap()
+            .stats
+            .get(&(ScopeKind::Event, name.to_owned(), Some(event)))
+            .cloned()
+    }
+
+    pub fn reset_stats(&self) {
+        self.data.lock().unwrap().stats.clear();
+    }
+
+    /// One line per timed scope, the slowest on average first.
+    pub fn describe(&self) -> String {
+        let data = self.data.lock().unwrap();
+
+        let mut stats: Vec<(ScopeKind, String, &ScopeStats)> = data
+            .stats
+            .iter()
+            .map(|((kind, name, event), stats)| (*kind, scope_label(name, *event), stats))
+            .collect();
+        stats.sort_by(|a, b| {
+            b.2.average()
+                .cmp(&a.2.average())
+                .then_with(|| (a.0, &a.1).cmp(&(b.0, &b.1)))
+        });
+
+        let mut description = format!(
+            "profile (last {PROFILER_WINDOW} samples){}:",
+            if data.capture.is_some() {
+                ", capturing"
+            } else {
+                ""
+            }
+        );
+
+        for (kind, name, scope) in stats {
+            description.push_str(&format!(
+                "\n  {:<12} {name:<32} avg {:>9.3}ms  max {:>9.3}ms  last {:>9.3}ms  calls {}",
+                kind.as_str(),
+                scope.average().as_secs_f64() * 1000.0,
+                scope.max().as_secs_f64() * 1000.0,
+                scope.last().as_secs_f64() * 1000.0,
+                scope.calls,
+            ));
+        }
+
+        description
+    }
+}
+
+/// Records the time from its creation to its drop, see `Profiler::scope`.
+pub struct ProfileScope<'a> {
+    profiler: &'a Profiler,
+    kind: ScopeKind,
+    name: &'a str,
+    event: Option<EventKind>,
+    started: Instant,
+}
+
+impl Drop for ProfileScope<'_> {
+    fn drop(&mut self) {
+        self.profiler.record_scope(
+            self.kind,
+            self.name,
+            self.event,
+            TraceLane::EventLoop,
+            self.started,
+            self.started.elapsed(),
+        );
+    }
+}
+
+/// Ex: `default_scene Asset` for an event scope.
+fn scope_label(name: &str, event: Option<EventKind>) -> String {
+    match event {
+        Some(event) => format!("{name} {event:?}"),
+        None => name.to_owned(),
+    }
+}
+
+fn thread_name(tid: usize, name: &str) -> String {
+    format!(r#"{{"name":"thread_name","ph":"M","pid":1,"tid":{tid},"args":{{"name":"{name}"}}}}"#)
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::tasks::TaskOutcome;
+
+    #[test]
+    fn event_scopes_are_kept_per_kind_of_event() {
+        let profiler = Profiler::default();
+
+        drop(profiler.event_scope("default_scene", EventKind::Asset));
+        drop(profiler.event_scope("default_scene", EventKind::Asset));
+        drop(profiler.event_scope("default_scene", EventKind::Exit));
+
+        assert_eq!(
+            profiler
+                .event_stats("default_scene", EventKind::Asset)
+                .unwrap()
+                .calls,
+            2
+        );
+        assert!(profiler.describe().contains("default_scene Exit"));
+    }
+
+    #[test]
+    fn tasks_are_recorded_from_their_spawn() {
+        let profiler = Profiler::default();
+        profiler.start_capture();
+
+        let started = Instant::now();
+        let report = TaskReport {
+            id: TaskId(1),
+            app: "asset_server".into(),
+            args: None,
+            outcome: TaskOutcome::Completed,
+            started,
+            elapsed: Duration::from_millis(5),
+        };
+        // Handled a while after the task ended
+        std::thread::sleep(Duration::from_millis(20));
+        profiler.record_task(&report);
+
+        let capture = profiler.stop_capture().unwrap();
+        assert_eq!(capture.events.len(), 1);
+        assert!(capture.events[0].start < Duration::from_millis(5));
+        assert_eq!(capture.events[0].duration, Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{SendError, Sender},
+    Arc, Mutex,
+};
+
+use winit::event_loop::{EventLoopClosed, EventLoopProxy};
+
+use crate::{
+    assets::handle::HandleRegistry,
+    core::{alias::Aliases, command_queue::Command, events::CommandEvent},
+};
+
+/// Run flags shared by an `Engine` and every `EngineProxy` handed out by it.
+pub struct EngineStatus {
+    running: AtomicBool,
+    initialized: AtomicBool,
+}
+
+impl Default for EngineStatus {
+    fn default() -> Self {
+        Self {
+            running: AtomicBool::new(true),
+            initialized: AtomicBool::new(false),
+        }
+    }
+}
+
+impl EngineStatus {
+    /// Whether the engine finished initializing (the renderer has its first pipeline).
+    pub fn initialized(&self) -> bool {
+        self.initialized.load(Ordering::Acquire)
+    }
+
+    pub fn finish_init(&self) {
+        self.initialized.store(true, Ordering::Release);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    pub fn terminate(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone)]
+enum EventSender {
+    Winit(EventLoopProxy<CommandEvent>),
+    Headless(Sender<CommandEvent>),
+}
+
+/// Handle used by apps and command tasks to send `CommandEvent`s back into the engine they belong to.
+/// Under winit the events go through the event loop, in headless mode they go through a channel
+/// that the headless runner drains every frame.
+///
+/// The proxy can also queue `Command`s from other threads (Ex: the stdin CLI thread),
+/// they are dispatched to their app at the start of the next engine update.
+#[derive(Clone)]
+pub struct EngineProxy {
+    sender: EventSender,
+    commands: Sender<Command>,
+    status: Arc<EngineStatus>,
+    aliases: Arc<Mutex<Aliases>>,
+    handles: HandleRegistry,
+}
+
+impl EngineProxy {
+    pub(crate) fn winit(
+        elp: EventLoopProxy<CommandEvent>,
+        commands: Sender<Command>,
+        status: Arc<EngineStatus>,
+        aliases: Arc<Mutex<Aliases>>,
+        handles: HandleRegistry,
+    ) -> Self {
+        Self {
+            sender: EventSender::Winit(elp),
+            commands,
+            status,
+            aliases,
+            handles,
+        }
+    }
+
+    /// Creates a proxy that is not connected to a winit event loop
+    /// together with the receiving end of its events.
+    pub(crate) fn headless(
+        commands: Sender<Command>,
+        status: Arc<EngineStatus>,
+        aliases: Arc<Mutex<Aliases>>,
+        handles: HandleRegistry,
+    ) -> (Self, std::sync::mpsc::Receiver<CommandEvent>) {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let proxy = Self {
+            sender: EventSender::Headless(sender),
+            commands,
+            status,
+            aliases,
+            handles,
+        };
+
+        (proxy, receiver)
+    }
+
+    pub fn send_event(
+        &self,
+        event: CommandEvent,
+    ) -> Result<(), Box<EventLoopClosed<CommandEvent>>> {
+        match &self.sender {
+            EventSender::Winit(elp) => elp.send_event(event).map_err(Box::new),
+            EventSender::Headless(sender) => sender
+                .send(event)
+                .map_err(|err| Box::new(EventLoopClosed(err.0))),
+        }
+    }
+
+    /// Queues `cmd` to be dispatched to its app at the start of the next engine update.
+    pub fn send_command(&self, cmd: Command) -> Result<(), SendError<Command>> {
+        self.commands.send(cmd)
+    }
+
+    /// The CLI aliases of the engine, expanded before the commands are dispatched.
+    pub fn aliases(&self) -> &Mutex<Aliases> {
+        &self.aliases
+    }
+
+    /// The asset handles of the engine, shared by its apps.
+    pub fn handles(&self) -> &HandleRegistry {
+        &self.handles
+    }
+
+    pub fn is_headless(&self) -> bool {
+        matches!(self.sender, EventSender::Headless(_))
+    }
+
+    /// Whether the engine this proxy belongs to finished initializing.
+    pub fn initialized(&self) -> bool {
+        self.status.initialized()
+    }
+
+    pub fn finish_init(&self) {
+        self.status.finish_init();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status.is_running()
+    }
+
+    pub fn terminate(&self) {
+        self.status.terminate();
+    }
+}
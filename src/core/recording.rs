@@ -0,0 +1,689 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use thiserror::Error;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        DeviceEvent, DeviceId, ElementState, MouseButton, MouseScrollDelta, RawKeyEvent,
+        TouchPhase, WindowEvent,
+    },
+    keyboard::PhysicalKey,
+    platform::scancode::PhysicalKeyExtScancode,
+    window::WindowId,
+};
+
+use crate::{
+    assets::{Asset, AssetStatus, AssetType},
+    core::{
+        command_queue::{Command, CommandType},
+        events::CommandEvent,
+        time::TimeControl,
+    },
+};
+
+/// First line of every recording, bumped whenever the format changes.
+pub const RECORDING_HEADER: &str = "sunset-recording 2";
+
+#[derive(Error, Debug)]
+pub enum RecordingError {
+    #[error("Recording io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a recording, expected header \"{RECORDING_HEADER}\"")]
+    InvalidHeader,
+    #[error("line {line}: {reason}")]
+    Invalid { line: usize, reason: String },
+}
+
+/// An input handled before an engine update.
+#[derive(Debug, Clone)]
+pub enum RecordedInput {
+    Event(CommandEvent),
+    /// `time` is the number of seconds between the start of the recording and the event.
+    Window {
+        time: f64,
+        event: WindowEvent,
+    },
+    Device {
+        time: f64,
+        event: DeviceEvent,
+    },
+}
+
+/// The inputs of one engine update: the events handled before it and the commands received through a proxy
+/// (Ex: from the CLI) or run by a script, in the order they were handled.
+#[derive(Debug, Clone, Default)]
+pub struct RecordedFrame {
+    pub frame: u64,
+    pub delta_time: f32,
+    pub inputs: Vec<RecordedInput>,
+    pub commands: Vec<(String, String)>,
+}
+
+impl RecordedFrame {
+    /// The recorded commands, ready to be sent through a proxy.
+    pub fn commands(&self) -> Vec<Command> {
+        self.commands
+            .iter()
+            .map(|(app, args)| Command {
+                processed: false,
+                app: app.clone(),
+                command_type: CommandType::TBD,
+                args: Some(args.clone()),
+                task: None,
+                future: None,
+                timeout: None,
+                reply_to: None,
+            })
+            .collect()
+    }
+}
+
+/// A recorded session, see `Recorder` for the file format.
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl Recording {
+    pub fn from_file(path: &str) -> Result<Recording, RecordingError> {
+        Recording::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> Result<Recording, RecordingError> {
+        let mut lines = source.lines().enumerate();
+
+        if lines.next().map(|(_, header)| header.trim()) != Some(RECORDING_HEADER) {
+            return Err(RecordingError::InvalidHeader);
+        }
+
+        let mut frames = vec![];
+        let mut pending = RecordedFrame::default();
+
+        for (i, text) in lines {
+            let line = i + 1;
+            let invalid = |reason: &str| RecordingError::Invalid {
+                line,
+                reason: reason.to_owned(),
+            };
+
+            let (kind, rest) = text.split_once(' ').unwrap_or((text, ""));
+            match kind {
+                "" => {}
+                "event" => pending.inputs.push(RecordedInput::Event(
+                    decode_event(rest).ok_or_else(|| invalid("invalid event"))?,
+                )),
+                "window" => {
+                    let (time, event) = decode_timed(rest, decode_window_event)
+                        .ok_or_else(|| invalid("invalid window event"))?;
+                    pending.inputs.push(RecordedInput::Window { time, event });
+                }
+                "device" => {
+                    let (time, event) = decode_timed(rest, decode_device_event)
+                        .ok_or_else(|| invalid("invalid device event"))?;
+                    pending.inputs.push(RecordedInput::Device { time, event });
+                }
+                "command" => {
+                    let (app, args) = rest.split_once(' ').unwrap_or((rest, ""));
+                    pending.commands.push((app.to_owned(), args.to_owned()));
+                }
+                "frame" => {
+                    let (frame, delta_time) = rest
+                        .split_once(' ')
+                        .ok_or_else(|| invalid("expected \"frame <number> <delta time>\""))?;
+
+                    pending.frame = frame.parse().map_err(|_| invalid("invalid frame number"))?;
+                    pending.delta_time = delta_time
+                        .parse()
+                        .map_err(|_| invalid("invalid delta time"))?;
+
+                    frames.push(std::mem::take(&mut pending));
+                }
+                _ => return Err(invalid(&format!("unknown entry {kind}"))),
+            }
+        }
+
+        // Events handled after the last update (Ex: the exit event)
+        if !pending.inputs.is_empty() || !pending.commands.is_empty() {
+            pending.frame = frames.last().map_or(0, |frame| frame.frame + 1);
+            frames.push(pending);
+        }
+
+        Ok(Recording { frames })
+    }
+}
+
+/// Writes the inputs of an engine to a file, frame by frame, so they can be replayed by a `HeadlessRunner`.
+///
+/// Only the serializable `CommandEvent`s are recorded (see `is_recordable`), they are the inputs coming from
+/// outside of the apps (CLI, asset callbacks, ...). The other events are produced by the apps from those inputs
+/// and are produced again during a replay.
+///
+/// Scripts are recorded as the commands their lines dispatched, a replay does not read them again.
+///
+/// The window and device input is recorded with the number of seconds since the start of the recording.
+/// A `KeyEvent` cannot be built outside of winit, so `WindowEvent::KeyboardInput` is not recorded:
+/// the keys are recorded through their `DeviceEvent::Key` instead.
+///
+/// The file is line based, the entries of a frame are written before the `frame` line closing it:
+///
+/// ```text
+/// sunset-recording 2
+/// event StopApp default_scene
+/// window 1.250413 CursorMoved 412 300.5
+/// device 1.250977 Button 1 Pressed
+/// command default_scene add --name Cube
+/// frame 12 0.016666668
+/// ```
+pub struct Recorder {
+    path: String,
+    writer: BufWriter<File>,
+    frame: u64,
+    started: web_time::Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Recorder, RecordingError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{RECORDING_HEADER}")?;
+
+        Ok(Recorder {
+            path: path.to_owned(),
+            writer,
+            frame: 0,
+            started: web_time::Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Number of frames recorded so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn record_event(&mut self, event: &CommandEvent) -> Result<(), RecordingError> {
+        if let Some(encoded) = encode_event(event) {
+            writeln!(self.writer, "event {encoded}")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn record_window_event(&mut self, event: &WindowEvent) -> Result<(), RecordingError> {
+        if let Some(encoded) = encode_window_event(event) {
+            let time = self.started.elapsed().as_secs_f64();
+            writeln!(self.writer, "window {time:.6} {encoded}")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn record_device_event(&mut self, event: &DeviceEvent) -> Result<(), RecordingError> {
+        if let Some(encoded) = encode_device_event(event) {
+            let time = self.started.elapsed().as_secs_f64();
+            writeln!(self.writer, "device {time:.6} {encoded}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Records `commands` and closes the current frame, flushing it to the file.
+    pub fn record_frame(
+        &mut self,
+        delta_time: f32,
+        commands: &[Command],
+    ) -> Result<(), RecordingError> {
+        for cmd in commands {
+            writeln!(
+                self.writer,
+                "command {} {}",
+                cmd.app,
+                cmd.args.as_deref().unwrap_or_default()
+            )?;
+        }
+
+        writeln!(self.writer, "frame {} {delta_time}", self.frame)?;
+        self.writer.flush()?;
+
+        self.frame += 1;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), RecordingError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Whether `event` is replaced by the recording during a replay: it is either written by a `Recorder`
+/// or, for `ExecScript`, recorded through the commands of the script.
+pub fn is_recordable(event: &CommandEvent) -> bool {
+    matches!(event, CommandEvent::ExecScript(_)) || encode_event(event).is_some()
+}
+
+fn encode_event(event: &CommandEvent) -> Option<String> {
+    let encoded = match event {
+        CommandEvent::Asset(asset) => format!(
+            "Asset {:?} {:?} {} {} {}",
+            asset.asset_type,
+            asset.status,
+            escape(&asset.name),
+            escape(&asset.path),
+            encode_hex(&asset.data)
+        ),
+        CommandEvent::ChangedAssets(paths) => {
+            let paths: Vec<String> = paths.iter().map(|path| escape(path)).collect();
+            format!("ChangedAssets {}", paths.join(" "))
+        }
+        CommandEvent::ListApps => "ListApps".into(),
+        CommandEvent::StopApp(name) => format!("StopApp {}", escape(name)),
+        CommandEvent::RestartApp(name) => format!("RestartApp {}", escape(name)),
+        CommandEvent::LoadPlugin(path) => format!("LoadPlugin {}", escape(path)),
+        CommandEvent::UnloadPlugin(name) => format!("UnloadPlugin {}", escape(name)),
+        CommandEvent::Time(control) => match control {
+            TimeControl::Pause => "Time Pause".into(),
+            TimeControl::Resume => "Time Resume".into(),
+            TimeControl::Step(ticks) => format!("Time Step {ticks}"),
+            TimeControl::Scale(scale) => format!("Time Scale {scale}"),
+            TimeControl::Status => "Time Status".into(),
+        },
+        CommandEvent::Exit => "Exit".into(),
+        _ => return None,
+    };
+
+    Some(encoded)
+}
+
+fn decode_event(text: &str) -> Option<CommandEvent> {
+    let mut fields = text.split(' ');
+
+    let event = match fields.next()? {
+        "Asset" => {
+            let asset_type = match fields.next()? {
+                "String" => AssetType::String,
+                "Shader" => AssetType::Shader,
+                "Texture" => AssetType::Texture,
+                "Font" => AssetType::Font,
+                "Mesh" => AssetType::Mesh,
+                "Material" => AssetType::Material,
+                "Model" => AssetType::Model,
+                "Unknown" => AssetType::Unknown,
+                _ => return None,
+            };
+            let status = match fields.next()? {
+                "Ready" => AssetStatus::Ready,
+                "NotFound" => AssetStatus::NotFound,
+                "Pending" => AssetStatus::Pending,
+                "Outdated" => AssetStatus::Outdated,
+                _ => return None,
+            };
+
+            CommandEvent::Asset(Asset {
+                asset_type,
+                status,
+                name: unescape(fields.next()?),
+                path: unescape(fields.next()?),
+                data: decode_hex(fields.next()?)?,
+            })
+        }
+        "ChangedAssets" => {
+            CommandEvent::ChangedAssets(fields.filter(|f| !f.is_empty()).map(unescape).collect())
+        }
+        "ListApps" => CommandEvent::ListApps,
+        "StopApp" => CommandEvent::StopApp(unescape(fields.next()?)),
+        "RestartApp" => CommandEvent::RestartApp(unescape(fields.next()?)),
+        "LoadPlugin" => CommandEvent::LoadPlugin(unescape(fields.next()?)),
+        "UnloadPlugin" => CommandEvent::UnloadPlugin(unescape(fields.next()?)),
+        "Time" => CommandEvent::Time(match fields.next()? {
+            "Pause" => TimeControl::Pause,
+            "Resume" => TimeControl::Resume,
+            "Step" => TimeControl::Step(fields.next()?.parse().ok()?),
+            "Scale" => TimeControl::Scale(fields.next()?.parse().ok()?),
+            "Status" => TimeControl::Status,
+            _ => return None,
+        }),
+        "Exit" => CommandEvent::Exit,
+        _ => return None,
+    };
+
+    Some(event)
+}
+
+fn encode_window_event(event: &WindowEvent) -> Option<String> {
+    let encoded = match event {
+        WindowEvent::Resized(size) => format!("Resized {} {}", size.width, size.height),
+        WindowEvent::CloseRequested => "CloseRequested".into(),
+        WindowEvent::Focused(focused) => format!("Focused {focused}"),
+        WindowEvent::CursorMoved { position, .. } => {
+            format!("CursorMoved {} {}", position.x, position.y)
+        }
+        WindowEvent::CursorEntered { .. } => "CursorEntered".into(),
+        WindowEvent::CursorLeft { .. } => "CursorLeft".into(),
+        WindowEvent::MouseWheel { delta, phase, .. } => {
+            format!("MouseWheel {} {phase:?}", encode_scroll(delta))
+        }
+        WindowEvent::MouseInput { state, button, .. } => {
+            format!("MouseInput {state:?} {}", encode_mouse_button(button))
+        }
+        _ => return None,
+    };
+
+    Some(encoded)
+}
+
+fn decode_window_event(text: &str) -> Option<WindowEvent> {
+    let mut fields = text.split(' ');
+    let device_id = replay_device_id();
+
+    let event = match fields.next()? {
+        "Resized" => WindowEvent::Resized(PhysicalSize::new(
+            fields.next()?.parse().ok()?,
+            fields.next()?.parse().ok()?,
+        )),
+        "CloseRequested" => WindowEvent::CloseRequested,
+        "Focused" => WindowEvent::Focused(fields.next()?.parse().ok()?),
+        "CursorMoved" => WindowEvent::CursorMoved {
+            device_id,
+            position: PhysicalPosition::new(
+                fields.next()?.parse().ok()?,
+                fields.next()?.parse().ok()?,
+            ),
+        },
+        "CursorEntered" => WindowEvent::CursorEntered { device_id },
+        "CursorLeft" => WindowEvent::CursorLeft { device_id },
+        "MouseWheel" => WindowEvent::MouseWheel {
+            device_id,
+            delta: decode_scroll(&mut fields)?,
+            phase: match fields.next()? {
+                "Started" => TouchPhase::Started,
+                "Moved" => TouchPhase::Moved,
+                "Ended" => TouchPhase::Ended,
+                "Cancelled" => TouchPhase::Cancelled,
+                _ => return None,
+            },
+        },
+        "MouseInput" => WindowEvent::MouseInput {
+            device_id,
+            state: decode_element_state(fields.next()?)?,
+            button: decode_mouse_button(fields.next()?)?,
+        },
+        _ => return None,
+    };
+
+    Some(event)
+}
+
+fn encode_device_event(event: &DeviceEvent) -> Option<String> {
+    let encoded = match event {
+        DeviceEvent::MouseMotion { delta: (x, y) } => format!("MouseMotion {x} {y}"),
+        DeviceEvent::MouseWheel { delta } => format!("MouseWheel {}", encode_scroll(delta)),
+        DeviceEvent::Motion { axis, value } => format!("Motion {axis} {value}"),
+        DeviceEvent::Button { button, state } => format!("Button {button} {state:?}"),
+        DeviceEvent::Key(RawKeyEvent {
+            physical_key,
+            state,
+        }) => format!("Key {} {state:?}", physical_key.to_scancode()?),
+        _ => return None,
+    };
+
+    Some(encoded)
+}
+
+fn decode_device_event(text: &str) -> Option<DeviceEvent> {
+    let mut fields = text.split(' ');
+
+    let event = match fields.next()? {
+        "MouseMotion" => DeviceEvent::MouseMotion {
+            delta: (fields.next()?.parse().ok()?, fields.next()?.parse().ok()?),
+        },
+        "MouseWheel" => DeviceEvent::MouseWheel {
+            delta: decode_scroll(&mut fields)?,
+        },
+        "Motion" => DeviceEvent::Motion {
+            axis: fields.next()?.parse().ok()?,
+            value: fields.next()?.parse().ok()?,
+        },
+        "Button" => DeviceEvent::Button {
+            button: fields.next()?.parse().ok()?,
+            state: decode_element_state(fields.next()?)?,
+        },
+        "Key" => DeviceEvent::Key(RawKeyEvent {
+            physical_key: PhysicalKey::from_scancode(fields.next()?.parse().ok()?),
+            state: decode_element_state(fields.next()?)?,
+        }),
+        _ => return None,
+    };
+
+    Some(event)
+}
+
+/// Splits the timestamp of a `window` or `device` entry from its event.
+fn decode_timed<T>(text: &str, decode: fn(&str) -> Option<T>) -> Option<(f64, T)> {
+    let (time, event) = text.split_once(' ')?;
+    Some((time.parse().ok()?, decode(event)?))
+}
+
+/// The device of every replayed input.
+pub fn replay_device_id() -> DeviceId {
+    // SAFETY: the id is only compared by the apps, it is never handed to winit
+    unsafe { DeviceId::dummy() }
+}
+
+/// The window of every replayed window event.
+pub fn replay_window_id() -> WindowId {
+    // SAFETY: the id is only compared by the apps, it is never handed to winit
+    unsafe { WindowId::dummy() }
+}
+
+fn encode_scroll(delta: &MouseScrollDelta) -> String {
+    match delta {
+        MouseScrollDelta::LineDelta(x, y) => format!("Line {x} {y}"),
+        MouseScrollDelta::PixelDelta(position) => format!("Pixel {} {}", position.x, position.y),
+    }
+}
+
+fn decode_scroll<'a>(fields: &mut impl Iterator<Item = &'a str>) -> Option<MouseScrollDelta> {
+    let kind = fields.next()?;
+    let x = fields.next()?;
+    let y = fields.next()?;
+
+    match kind {
+        "Line" => Some(MouseScrollDelta::LineDelta(
+            x.parse().ok()?,
+            y.parse().ok()?,
+        )),
+        "Pixel" => Some(MouseScrollDelta::PixelDelta(PhysicalPosition::new(
+            x.parse().ok()?,
+            y.parse().ok()?,
+        ))),
+        _ => None,
+    }
+}
+
+fn encode_mouse_button(button: &MouseButton) -> String {
+    match button {
+        MouseButton::Other(id) => format!("Other{id}"),
+        _ => format!("{button:?}"),
+    }
+}
+
+fn decode_mouse_button(text: &str) -> Option<MouseButton> {
+    let button = match text {
+        "Left" => MouseButton::Left,
+        "Right" => MouseButton::Right,
+        "Middle" => MouseButton::Middle,
+        "Back" => MouseButton::Back,
+        "Forward" => MouseButton::Forward,
+        _ => MouseButton::Other(text.strip_prefix("Other")?.parse().ok()?),
+    };
+
+    Some(button)
+}
+
+fn decode_element_state(text: &str) -> Option<ElementState> {
+    match text {
+        "Pressed" => Some(ElementState::Pressed),
+        "Released" => Some(ElementState::Released),
+        _ => None,
+    }
+}
+
+/// Keeps every field on a single space separated line.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ' ' => escaped.push_str("\\s"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+
+    // Empty fields would disappear between two separators
+    if escaped.is_empty() {
+        escaped.push_str("\\0");
+    }
+
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('0') => {}
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+
+    unescaped
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(data.len() * 2 + 1);
+    hex.push('x');
+
+    for byte in data {
+        hex.push_str(&format!("{byte:02x}"));
+    }
+
+    hex
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.strip_prefix('x')?;
+    if !hex.is_ascii() || hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_entries_are_parsed_into_their_frame() {
+        let recording = Recording::parse(
+            "sunset-recording 2\n\
+             event StopApp default\\sscene\n\
+             window 0.5 CursorMoved 412 300.5\n\
+             window 0.5 MouseInput Pressed Other7\n\
+             device 0.75 MouseWheel Line 0 -1\n\
+             command default_scene add --name Cube\n\
+             frame 0 0.016\n\
+             event Exit",
+        )
+        .unwrap();
+
+        assert_eq!(recording.frames.len(), 2);
+
+        let frame = &recording.frames[0];
+        assert_eq!(
+            frame.commands,
+            [("default_scene".to_owned(), "add --name Cube".to_owned())]
+        );
+        assert!(matches!(
+            &frame.inputs[..],
+            [
+                RecordedInput::Event(CommandEvent::StopApp(name)),
+                RecordedInput::Window { time: 0.5, event: WindowEvent::CursorMoved { position, .. } },
+                RecordedInput::Window {
+                    event: WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Other(7), .. },
+                    ..
+                },
+                RecordedInput::Device {
+                    time: 0.75,
+                    event: DeviceEvent::MouseWheel { delta: MouseScrollDelta::LineDelta(x, y) },
+                },
+            ] if name == "default scene" && position.x == 412.0 && position.y == 300.5 && *x == 0.0 && *y == -1.0
+        ));
+
+        assert_eq!(recording.frames[1].frame, 1);
+        assert!(matches!(
+            &recording.frames[1].inputs[..],
+            [RecordedInput::Event(CommandEvent::Exit)]
+        ));
+    }
+
+    #[test]
+    fn encoded_input_is_decoded_back() {
+        let window_events = [
+            WindowEvent::Resized(PhysicalSize::new(800, 600)),
+            WindowEvent::Focused(false),
+            WindowEvent::MouseWheel {
+                device_id: replay_device_id(),
+                delta: MouseScrollDelta::PixelDelta(PhysicalPosition::new(1.5, -2.0)),
+                phase: TouchPhase::Ended,
+            },
+        ];
+        for event in window_events {
+            let encoded = encode_window_event(&event).unwrap();
+            assert_eq!(decode_window_event(&encoded), Some(event));
+        }
+
+        let device_events = [
+            DeviceEvent::MouseMotion { delta: (3.0, -4.5) },
+            DeviceEvent::Button {
+                button: 1,
+                state: ElementState::Released,
+            },
+        ];
+        for event in device_events {
+            let encoded = encode_device_event(&event).unwrap();
+            assert_eq!(decode_device_event(&encoded), Some(event));
+        }
+    }
+
+    #[test]
+    fn scripts_are_replaced_by_their_commands() {
+        assert!(is_recordable(&CommandEvent::ExecScript(
+            "scene.sunset".into()
+        )));
+        assert!(encode_event(&CommandEvent::ExecScript("scene.sunset".into())).is_none());
+    }
+}
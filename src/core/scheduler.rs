@@ -0,0 +1,341 @@
+use async_trait::async_trait;
+use tracing::info;
+
+use crate::core::{
+    app::*,
+    cli::parse_cli_line,
+    command_queue::*,
+    command_registry::*,
+    command_result::{CommandFailure, CommandOutput},
+    events::EventKind,
+    proxy::EngineProxy,
+};
+
+/// When a scheduled command runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+    /// Once, after the given number of seconds of simulation time.
+    After(f32),
+    /// Every given number of seconds of simulation time, until cancelled.
+    Every(f32),
+    /// Once, after the given number of frames counted from when it is scheduled (1 for the next frame).
+    /// There is no absolute frame number, the engine does not count its frames.
+    AfterFrames(u64),
+}
+
+/// A CLI line waiting for its trigger, see `Scheduler`.
+#[derive(Debug, Clone)]
+pub struct ScheduledCommand {
+    pub id: u64,
+    /// The `app args...` line to run.
+    pub line: String,
+    pub trigger: Trigger,
+    /// Seconds (or frames for `Trigger::AfterFrames`) left before it runs.
+    remaining: f64,
+}
+
+impl ScheduledCommand {
+    pub fn describe(&self) -> String {
+        match self.trigger {
+            Trigger::After(_) => format!("#{} in {:.2}s: {}", self.id, self.remaining, self.line),
+            Trigger::Every(interval) => format!(
+                "#{} every {interval:.2}s, next in {:.2}s: {}",
+                self.id, self.remaining, self.line
+            ),
+            Trigger::AfterFrames(_) => {
+                format!("#{} in {} frames: {}", self.id, self.remaining, self.line)
+            }
+        }
+    }
+}
+
+/// Runs CLI lines later: after a delay, at an interval or after a number of frames from now
+/// (Ex: `schedule every 10 asset_server check`, `schedule frame 1 default_scene add --name Cube` on the next frame).
+///
+/// Delays are counted in simulation time from the engine `Clock` (`App::fixed_update`), so they follow
+/// `pause`, `step` and `timescale`. Frames are counted by `App::update`.
+/// The commands run as if typed in the CLI, their results are logged.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut scheduler = Scheduler::default();
+/// let id = scheduler.schedule("cli time", Trigger::Every(1.0));
+/// scheduler.cancel(id);
+/// ```
+#[derive(Default)]
+pub struct Scheduler {
+    commands: Vec<Command>,
+    scheduled: Vec<ScheduledCommand>,
+    next_id: u64,
+}
+
+impl Scheduler {
+    /// Schedules the CLI line `line` (Ex: `default_scene add --name Cube`).
+    ///
+    /// # Returns
+    ///
+    /// The id to cancel it with.
+    pub fn schedule(&mut self, line: &str, trigger: Trigger) -> u64 {
+        self.next_id += 1;
+
+        let remaining = match trigger {
+            Trigger::After(delay) | Trigger::Every(delay) => delay.max(0.0) as f64,
+            Trigger::AfterFrames(frames) => frames as f64,
+        };
+
+        self.scheduled.push(ScheduledCommand {
+            id: self.next_id,
+            line: line.trim().to_owned(),
+            trigger,
+            remaining,
+        });
+
+        self.next_id
+    }
+
+    /// Removes a scheduled command before it runs (again).
+    ///
+    /// # Returns
+    ///
+    /// The removed command, if `id` was scheduled.
+    pub fn cancel(&mut self, id: u64) -> Option<ScheduledCommand> {
+        let index = self.scheduled.iter().position(|entry| entry.id == id)?;
+        Some(self.scheduled.remove(index))
+    }
+
+    /// The commands waiting for their trigger, in the order they were scheduled.
+    pub fn scheduled(&self) -> &[ScheduledCommand] {
+        &self.scheduled
+    }
+
+    pub fn describe(&self) -> String {
+        if self.scheduled.is_empty() {
+            return "no scheduled commands".into();
+        }
+
+        let lines: Vec<String> = self
+            .scheduled
+            .iter()
+            .map(|entry| entry.describe())
+            .collect();
+        format!("scheduled commands:\n  {}", lines.join("\n  "))
+    }
+
+    /// Advances the triggers matched by `advances` by `amount`, then queues the lines that are due.
+    fn advance(&mut self, amount: f64, advances: impl Fn(&Trigger) -> bool) {
+        let mut due = vec![];
+
+        self.scheduled.retain_mut(|entry| {
+            if !advances(&entry.trigger) {
+                return true;
+            }
+
+            entry.remaining -= amount;
+            if entry.remaining > 0.0 {
+                return true;
+            }
+
+            due.push((entry.id, entry.line.clone()));
+
+            match entry.trigger {
+                Trigger::Every(interval) if interval > 0.0 => {
+                    entry.remaining = (entry.remaining + interval as f64).max(0.0);
+                    true
+                }
+                _ => false,
+            }
+        });
+
+        for (id, line) in due {
+            info!("Scheduled #{id}: {line}");
+            self.commands.push(parse_cli_line(&line));
+        }
+    }
+
+    pub fn process_schedule_command(&mut self, mut cmd: Command, args: ParsedArgs) {
+        let result = match args.command() {
+            "after" => {
+                let delay = args.get_f32("seconds").unwrap();
+                Ok(self.schedule_from_args(&args, Trigger::After(delay)))
+            }
+            "every" => match args.get_f32("seconds").unwrap() {
+                // A zero interval would run on every tick
+                interval if interval <= 0.0 => Err(CommandFailure::Failed(
+                    "the interval must be greater than 0".into(),
+                )),
+                interval => Ok(self.schedule_from_args(&args, Trigger::Every(interval))),
+            },
+            "frame" => {
+                let frames = args.get_uint("frames").unwrap();
+                Ok(self.schedule_from_args(&args, Trigger::AfterFrames(frames)))
+            }
+            "list" => Ok(CommandOutput::message(self.describe())),
+            "cancel" => {
+                let id = args.get_uint("id").unwrap();
+                match self.cancel(id) {
+                    Some(entry) => Ok(CommandOutput::message(format!(
+                        "cancelled #{id}: {}",
+                        entry.line
+                    ))),
+                    None => Err(CommandFailure::Failed(format!(
+                        "no scheduled command #{id}"
+                    ))),
+                }
+            }
+            _ => Err(CommandFailure::Unsupported(
+                cmd.args.as_deref().unwrap_or_default().to_owned(),
+            )),
+        };
+
+        cmd.processed = true;
+        cmd.task = Some(Box::new(move || result.clone()));
+
+        self.commands.push(cmd);
+    }
+
+    fn schedule_from_args(&mut self, args: &ParsedArgs, trigger: Trigger) -> CommandOutput {
+        let line = join_args(args.get_list("command").unwrap());
+        self.schedule(&line, trigger);
+
+        let entry = self.scheduled.last().unwrap();
+        CommandOutput::message(format!("scheduled {}", entry.describe()))
+    }
+}
+
+#[async_trait(?Send)]
+impl App for Scheduler {
+    fn get_name(&self) -> String {
+        "Scheduler".into()
+    }
+
+    fn init(&mut self, _elp: EngineProxy) {}
+
+    fn fixed_update(&mut self, fixed_delta: f32) -> Vec<Command> {
+        self.advance(fixed_delta as f64, |trigger| {
+            matches!(trigger, Trigger::After(_) | Trigger::Every(_))
+        });
+
+        self.commands.drain(..).collect()
+    }
+
+    fn update(&mut self, _delta_time: f32) -> Vec<Command> {
+        self.advance(1.0, |trigger| matches!(trigger, Trigger::AfterFrames(_)));
+
+        self.commands.drain(..).collect()
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![]
+    }
+
+    fn commands(&self) -> Vec<CommandDesc> {
+        let command = || {
+            ArgDesc::rest(
+                "command",
+                "CLI line to run, Ex: default_scene add --name Cube",
+            )
+        };
+
+        vec![
+            CommandDesc::new("after", "Runs a command once after a delay")
+                .arg(ArgDesc::positional(
+                    "seconds",
+                    ArgType::Float,
+                    "Delay in simulation time",
+                ))
+                .arg(command()),
+            CommandDesc::new("every", "Runs a command at an interval until cancelled")
+                .arg(ArgDesc::positional(
+                    "seconds",
+                    ArgType::Float,
+                    "Interval in simulation time",
+                ))
+                .arg(command()),
+            CommandDesc::new(
+                "frame",
+                "Runs a command once after a number of frames, counted from now",
+            )
+            .arg(ArgDesc::positional(
+                "frames",
+                ArgType::UInt,
+                "Number of frames from now, 1 for the next one",
+            ))
+            .arg(command()),
+            CommandDesc::new("list", "Lists the scheduled commands"),
+            CommandDesc::new("cancel", "Removes a scheduled command").arg(ArgDesc::positional(
+                "id",
+                ArgType::UInt,
+                "Id of the scheduled command, as listed by list",
+            )),
+        ]
+    }
+
+    async fn process_command(&mut self, cmd: Command, args: ParsedArgs) {
+        self.process_schedule_command(cmd, args);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The `app args` lines of the commands that are due.
+    fn lines(commands: Vec<Command>) -> Vec<String> {
+        commands
+            .into_iter()
+            .map(|cmd| format!("{} {}", cmd.app, cmd.args.unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn runs_once_after_a_delay() {
+        let mut scheduler = Scheduler::default();
+        scheduler.schedule("cli time", Trigger::After(1.0));
+
+        assert!(scheduler.fixed_update(0.6).is_empty());
+        assert_eq!(lines(scheduler.fixed_update(0.6)), ["cli time"]);
+
+        assert!(scheduler.fixed_update(5.0).is_empty());
+        assert!(scheduler.scheduled().is_empty());
+    }
+
+    #[test]
+    fn runs_every_interval_until_cancelled() {
+        let mut scheduler = Scheduler::default();
+        let id = scheduler.schedule("asset_server check", Trigger::Every(1.0));
+
+        let due: Vec<Command> = (0..4).flat_map(|_| scheduler.fixed_update(0.5)).collect();
+        assert_eq!(lines(due), ["asset_server check", "asset_server check"]);
+        // Frames do not advance time triggers
+        assert!(scheduler.update(10.0).is_empty());
+
+        assert_eq!(scheduler.cancel(id).unwrap().line, "asset_server check");
+        assert!(scheduler.cancel(id).is_none());
+        assert!(scheduler.fixed_update(2.0).is_empty());
+    }
+
+    #[test]
+    fn runs_after_frames_counted_from_now() {
+        let mut scheduler = Scheduler::default();
+        scheduler.update(0.0);
+        scheduler.schedule("default_scene add --name Cube", Trigger::AfterFrames(2));
+
+        // Simulation time does not advance frame triggers
+        assert!(scheduler.fixed_update(10.0).is_empty());
+        assert!(scheduler.update(0.0).is_empty());
+        assert_eq!(
+            lines(scheduler.update(0.0)),
+            ["default_scene add --name Cube"]
+        );
+        assert!(scheduler.scheduled().is_empty());
+    }
+}
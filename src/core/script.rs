@@ -0,0 +1,484 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    time::Duration,
+};
+
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::core::{
+    command_queue::{Command, CommandType},
+    command_result::{CommandFailure, CommandReport},
+};
+
+#[derive(Error, Debug)]
+pub enum ScriptError {
+    #[error("Could not read script {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("line {line}: undefined variable ${name}")]
+    UndefinedVariable { line: usize, name: String },
+    #[error("line {line}: missing '}}' after \"${{\"")]
+    UnterminatedVariable { line: usize },
+    #[error("line {line}: missing closing {quote}")]
+    UnterminatedQuote { line: usize, quote: char },
+    #[error("line {line}: expected \"set <name> <value>\"")]
+    InvalidSet { line: usize },
+    #[error("line {line}: {source}")]
+    Failed { line: usize, source: CommandFailure },
+}
+
+/// A single command of a script, with its variables already substituted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptLine {
+    /// Line number in the script file, starting at 1.
+    pub line: usize,
+    pub app: String,
+    pub args: String,
+}
+
+impl ScriptLine {
+    pub fn command(&self) -> Command {
+        Command {
+            processed: false,
+            app: self.app.clone(),
+            command_type: CommandType::TBD,
+            args: Some(self.args.clone()),
+            task: None,
+            future: None,
+            timeout: None,
+            reply_to: None,
+        }
+    }
+}
+
+/// A list of CLI lines run one after the other (Ex: the commands that build a scene).
+/// A line is only run once the command of the previous one succeeded, the script stops at the first failure.
+///
+/// * Empty lines are ignored, so is everything after a `#` starting the line or a word
+/// * `"..."` and `'...'` quote an argument containing spaces or a `#` (Ex: `--name "Duck #2"`)
+/// * `set <name> <value>` defines a variable, `$name` or `${name}` is replaced by its value
+///   in every line below it (`$$` is a literal `$`)
+/// * Every other line is `<app> <args>`, the same as typed in the CLI
+///
+/// # Example
+///
+/// ```text
+/// # Spawns the duck in front of the camera
+/// set model models/test/duck.glb
+///
+/// default_scene add --name "Duck #1" --model $model  # quoted, the '#' is part of the name
+/// default_scene add --name Camera3D --camera 3D 1.8 45.0 0 0 4 0.0001 1000
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Script {
+    pub lines: Vec<ScriptLine>,
+}
+
+impl Script {
+    /// Parses `source`, `variables` are defined before the first line (Ex: passed by the host).
+    /// Fails on the first line using an undefined variable, before any command is run.
+    pub fn parse(source: &str, variables: &HashMap<String, String>) -> Result<Script, ScriptError> {
+        let mut variables = variables.clone();
+        let mut lines = vec![];
+
+        for (i, raw) in source.lines().enumerate() {
+            let line = i + 1;
+
+            let text = strip_comment(raw).trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            if let Some(quote) = unterminated_quote(text) {
+                return Err(ScriptError::UnterminatedQuote { line, quote });
+            }
+
+            let text = substitute(text, &variables, line)?;
+            let (first, rest) = text.split_once(char::is_whitespace).unwrap_or((&text, ""));
+            let rest = rest.trim();
+
+            if first == "set" {
+                let (name, value) = rest
+                    .split_once(char::is_whitespace)
+                    .ok_or(ScriptError::InvalidSet { line })?;
+
+                variables.insert(name.to_owned(), value.trim().to_owned());
+                continue;
+            }
+
+            lines.push(ScriptLine {
+                line,
+                app: first.to_owned(),
+                args: rest.to_owned(),
+            });
+        }
+
+        Ok(Script { lines })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(
+        path: &str,
+        variables: &HashMap<String, String>,
+    ) -> Result<Script, ScriptError> {
+        let source = std::fs::read_to_string(path).map_err(|source| ScriptError::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        Script::parse(&source, variables)
+    }
+}
+
+/// How long a `ScriptRun` waits for the result of a command before failing, see `ScriptRun::with_command_timeout`.
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a `ScriptRun` does next, see `ScriptRun::step`.
+#[derive(Debug)]
+pub enum ScriptStep {
+    /// The command of the current line has not produced its result yet.
+    Waiting,
+    /// The command of the next line, replying to the script.
+    Dispatch(Command),
+    /// The number of commands run, or the line that failed.
+    Finished(Result<usize, ScriptError>),
+}
+
+/// A script being run by an engine: one line is dispatched once the command of the previous one succeeded.
+/// A command without a result after the command timeout stops the script with `CommandFailure::TimedOut`.
+pub struct ScriptRun {
+    name: String,
+    lines: VecDeque<ScriptLine>,
+    pending: Option<PendingLine>,
+    ran: usize,
+    command_timeout: Duration,
+}
+
+struct PendingLine {
+    line: ScriptLine,
+    results: Receiver<CommandReport>,
+    dispatched: web_time::Instant,
+}
+
+impl ScriptRun {
+    /// # Arguments
+    ///
+    /// * `name` - Shown in the logs (Ex: the path of the script)
+    pub fn new(name: &str, script: Script) -> Self {
+        ScriptRun {
+            name: name.to_owned(),
+            lines: script.lines.into(),
+            pending: None,
+            ran: 0,
+            command_timeout: DEFAULT_COMMAND_TIMEOUT,
+        }
+    }
+
+    /// Sets how long the command of a line can take to produce its result (`DEFAULT_COMMAND_TIMEOUT` by default).
+    pub fn with_command_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = timeout;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Checks the result of the current line and hands out the next line once it succeeded.
+    pub fn step(&mut self) -> ScriptStep {
+        if let Some(PendingLine {
+            line,
+            results,
+            dispatched,
+        }) = &self.pending
+        {
+            match results.try_recv() {
+                Ok(CommandReport {
+                    result: Err(source),
+                    ..
+                }) => {
+                    return ScriptStep::Finished(Err(ScriptError::Failed {
+                        line: line.line,
+                        source,
+                    }))
+                }
+                Ok(CommandReport {
+                    result: Ok(output), ..
+                }) => {
+                    if let Some(message) = output.message {
+                        info!("{message}");
+                    }
+                }
+                Err(TryRecvError::Empty) if dispatched.elapsed() >= self.command_timeout => {
+                    return ScriptStep::Finished(Err(ScriptError::Failed {
+                        line: line.line,
+                        source: CommandFailure::TimedOut,
+                    }))
+                }
+                Err(TryRecvError::Empty) => return ScriptStep::Waiting,
+                Err(TryRecvError::Disconnected) => {
+                    warn!(
+                        "Script {} line {}: <{}> dropped the command without a result",
+                        self.name, line.line, line.app
+                    );
+                }
+            }
+
+            self.ran += 1;
+            self.pending = None;
+        }
+
+        let Some(line) = self.lines.pop_front() else {
+            return ScriptStep::Finished(Ok(self.ran));
+        };
+
+        let (reply_to, results) = channel();
+        let cmd = line.command().reply_to(reply_to);
+        self.pending = Some(PendingLine {
+            line,
+            results,
+            dispatched: web_time::Instant::now(),
+        });
+
+        ScriptStep::Dispatch(cmd)
+    }
+}
+
+fn substitute(
+    text: &str,
+    variables: &HashMap<String, String>,
+    line: usize,
+) -> Result<String, ScriptError> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+
+        let name: String = match chars.peek() {
+            Some('$') => {
+                chars.next();
+                result.push('$');
+                continue;
+            }
+            Some('{') => {
+                chars.next();
+
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break name,
+                        Some(c) => name.push(c),
+                        None => return Err(ScriptError::UnterminatedVariable { line }),
+                    }
+                }
+            }
+            _ => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                name
+            }
+        };
+
+        let value = variables
+            .get(&name)
+            .ok_or_else(|| ScriptError::UndefinedVariable {
+                line,
+                name: name.clone(),
+            })?;
+        result.push_str(value);
+    }
+
+    Ok(result)
+}
+
+/// `line` without its comment: a `#` starting the line or following a whitespace, outside of quotes.
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous: Option<char> = None;
+
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && previous.is_none_or(char::is_whitespace) => return &line[..i],
+            None => {}
+        }
+        previous = Some(c);
+    }
+
+    line
+}
+
+/// The quote left open in `line`, if any.
+fn unterminated_quote(line: &str) -> Option<char> {
+    let mut quote = None;
+
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None => {}
+        }
+    }
+
+    quote
+}
+
+/// Values given to `flag` on the command line, as `<flag> <value>` or `<flag>=<value>`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn flag_values(flag: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            match args.next() {
+                Some(value) => values.push(value),
+                None => tracing::error!("{flag} expects a value"),
+            }
+        } else if let Some(value) = arg.strip_prefix(flag).and_then(|v| v.strip_prefix('=')) {
+            values.push(value.to_owned());
+        }
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::command_result::{reply, CommandOutput};
+
+    fn parse(source: &str) -> Result<Script, ScriptError> {
+        Script::parse(source, &HashMap::new())
+    }
+
+    fn lines(script: &Script) -> Vec<(usize, &str, &str)> {
+        script
+            .lines
+            .iter()
+            .map(|line| (line.line, line.app.as_str(), line.args.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn comments_start_a_line_or_a_word_outside_of_quotes() {
+        let script = parse(
+            "# comment\n\
+             \n\
+             scene add --color #ff0000 # red\n\
+             scene add --name \"Duck #1\"\n\
+             scene add --name issue#2",
+        )
+        .unwrap();
+
+        assert_eq!(
+            lines(&script),
+            [
+                (3, "scene", "add --color"),
+                (4, "scene", "add --name \"Duck #1\""),
+                (5, "scene", "add --name issue#2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn variables_are_substituted_below_their_definition() {
+        let variables = HashMap::from([("root".to_owned(), "models".to_owned())]);
+        let script = Script::parse(
+            "set model ${root}/duck.glb\n\
+             scene add --model $model --price $$5",
+            &variables,
+        )
+        .unwrap();
+
+        assert_eq!(
+            lines(&script),
+            [(2, "scene", "add --model models/duck.glb --price $5")]
+        );
+    }
+
+    #[test]
+    fn invalid_lines_are_reported_with_their_number() {
+        assert!(matches!(
+            parse("scene add\nscene add --model $model"),
+            Err(ScriptError::UndefinedVariable { line: 2, name }) if name == "model"
+        ));
+        assert!(matches!(
+            parse("set model duck.glb\nscene add --model ${model"),
+            Err(ScriptError::UnterminatedVariable { line: 2 })
+        ));
+        assert!(matches!(
+            parse("scene add --name \"Duck"),
+            Err(ScriptError::UnterminatedQuote {
+                line: 1,
+                quote: '"'
+            })
+        ));
+        assert!(matches!(
+            parse("set model"),
+            Err(ScriptError::InvalidSet { line: 1 })
+        ));
+    }
+
+    #[test]
+    fn runs_one_line_at_a_time_and_stops_at_the_first_failure() {
+        let mut run = ScriptRun::new("test", parse("a one\nb two\nc three").unwrap());
+
+        let ScriptStep::Dispatch(first) = run.step() else {
+            panic!("expected the first line");
+        };
+        assert_eq!(first.app, "a");
+        assert!(matches!(run.step(), ScriptStep::Waiting));
+
+        reply(
+            first.reply_to.as_ref(),
+            "a",
+            Some("one"),
+            Ok(CommandOutput::default()),
+        );
+        let ScriptStep::Dispatch(second) = run.step() else {
+            panic!("expected the second line");
+        };
+
+        reply(
+            second.reply_to.as_ref(),
+            "b",
+            Some("two"),
+            Err(CommandFailure::Failed("no".into())),
+        );
+        assert!(matches!(
+            run.step(),
+            ScriptStep::Finished(Err(ScriptError::Failed { line: 2, .. }))
+        ));
+    }
+
+    #[test]
+    fn a_command_without_result_times_out() {
+        let mut run = ScriptRun::new("test", parse("a one\nb two").unwrap())
+            .with_command_timeout(Duration::ZERO);
+
+        let ScriptStep::Dispatch(_first) = run.step() else {
+            panic!("expected the first line");
+        };
+        assert!(matches!(
+            run.step(),
+            ScriptStep::Finished(Err(ScriptError::Failed {
+                line: 1,
+                source: CommandFailure::TimedOut
+            }))
+        ));
+    }
+}
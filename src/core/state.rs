@@ -1,179 +1,544 @@
-use std::{collections::HashMap, sync::atomic::AtomicBool};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
-use async_std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
-
-use once_cell::sync::Lazy;
+use thiserror::Error;
 use tracing::{error, info, warn};
-use winit::event_loop::EventLoopProxy;
 
-use crate::prelude::windower::NewWindowProps;
-#[allow(unused_imports)]
+#[cfg(not(target_arch = "wasm32"))]
+use crate::core::{
+    plugin::{Plugin, PluginError, UnloadingPlugin},
+    recording::{Recorder, RecordingError},
+};
 use crate::{
-    assets::{asset_cmd::AssetCommand, asset_server::AssetServer},
     core::{
         app::App,
-        cli::run_cli,
-        command_queue::{Command, CommandQueue, CommandType},
-        default_apps::default_apps,
-        events::CommandEvent,
+        command_queue::{Command, CommandQueue},
+        command_registry::{CommandError, CommandRegistry, ParsedCommand},
+        command_result::{reply, CommandOutput},
+        events::EventKind,
+        profiler::Profiler,
+        proxy::EngineProxy,
+        script::{ScriptRun, ScriptStep},
+        time::Clock,
     },
+    prelude::windower::NewWindowProps,
     window::windower::Windower,
 };
 
-static mut GLOBAL_STATE: Lazy<RwLock<State>> = Lazy::new(Default::default);
-static mut RUNNING: AtomicBool = AtomicBool::new(true);
-pub static mut ENGINE_INIT: AtomicBool = AtomicBool::new(false);
-
-pub fn initialized() -> bool {
-    unsafe { ENGINE_INIT.load(std::sync::atomic::Ordering::Acquire) }
-}
-
-pub fn finish_init() {
-    unsafe {
-        ENGINE_INIT.store(true, std::sync::atomic::Ordering::Release);
-    }
-}
-
-pub fn is_running() -> bool {
-    unsafe { RUNNING.load(std::sync::atomic::Ordering::Relaxed) }
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AppError {
+    #[error("State already contains app {0}!")]
+    AlreadyInserted(String),
+    #[error("App dependency cycle: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+    #[error("No app found with name: {0}")]
+    NotFound(String),
+    #[error("App {0} is stopped")]
+    Stopped(String),
 }
 
-pub fn terminate() {
-    unsafe {
-        RUNNING.store(false, std::sync::atomic::Ordering::SeqCst);
-    }
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum DispatchError {
+    #[error(transparent)]
+    App(#[from] AppError),
+    #[error("{app}: {source}")]
+    Command { app: String, source: CommandError },
 }
 
+/// The apps, command registries and command queue of a single `Engine`.
 #[derive(Default)]
 pub struct State {
     pub apps: HashMap<String, Box<dyn App>>,
+    pub registries: HashMap<String, CommandRegistry>,
+    pub subscriptions: HashMap<String, HashSet<EventKind>>,
     pub command_queue: CommandQueue,
-    pub event_loop_proxy: Option<winit::event_loop::EventLoopProxy<CommandEvent>>,
-}
+    pub event_loop_proxy: Option<EngineProxy>,
+    pub clock: Clock,
+    /// Times the apps and command tasks, see `Profiler`.
+    pub profiler: Profiler,
+    /// Scripts being run, one line at a time, see `Engine::exec_script`.
+    pub scripts: Vec<ScriptRun>,
 
-impl State {
-    async fn init() -> winit::event_loop::EventLoop<CommandEvent> {
-        init_trace();
+    /// Apps that were stopped: they keep their name and commands but are not updated and receive no events.
+    pub stopped_apps: HashMap<String, Box<dyn App>>,
 
-        let event_loop = winit::event_loop::EventLoopBuilder::<CommandEvent>::with_user_event()
-            .build()
-            .unwrap();
+    /// Declared after the apps so plugin apps are dropped before their library is closed.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub plugins: HashMap<String, Plugin>,
+
+    /// Plugins whose apps were removed, their library is closed once nothing they created can run anymore,
+    /// see `unload_plugin`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unloading_plugins: Vec<UnloadingPlugin>,
 
-        let event_loop_proxy = event_loop.create_proxy();
+    /// Records the inputs of every frame while set, see `Engine::start_recording`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub recorder: Option<Recorder>,
 
-        // Scoped to make sure the lock is dropped
-        {
-            let mut state_lock = State::write().await;
-            state_lock.event_loop_proxy = Some(event_loop_proxy.clone());
+    insertion_order: Vec<String>,
+    app_order: Vec<String>,
+}
+
+struct AppNode {
+    name: String,
+    priority: i32,
+    runs_after: Vec<String>,
+}
+
+impl State {
+    /// Parses the arguments of `cmd` against the commands registered by its target app
+    /// and hands it to the app if they are valid. `<app> help` replies with the generated help.
+    ///
+    /// Dispatch errors are sent to the issuer of `cmd` (see `Command::reply_to`), or logged if there is none.
+    pub async fn dispatch_command(&mut self, cmd: Command) {
+        let app = cmd.app.clone();
+        let args = cmd.args.clone().unwrap_or_default();
+        let reply_to = cmd.reply_to.clone();
+
+        match self.try_dispatch_command(cmd).await {
+            Ok(()) => {}
+            Err(err) if reply_to.is_some() => {
+                reply(reply_to.as_ref(), &app, Some(&args), Err(err.into()));
+            }
+            Err(DispatchError::App(AppError::Stopped(app))) => {
+                error!("App {app} is stopped, restart it to process: \"{args}\"");
+            }
+            Err(DispatchError::App(err)) => error!("{err} to process: \"{args}\""),
+            Err(DispatchError::Command { app, source }) => {
+                error!("{app}: {source}");
+                info!("type \"{app} help\" for supported commands");
+            }
         }
+    }
 
-        let default_apps = default_apps();
+    /// Same as `dispatch_command` but returns the error instead of logging it.
+    pub async fn try_dispatch_command(&mut self, cmd: Command) -> Result<(), DispatchError> {
+        if self.stopped_apps.contains_key(&cmd.app) {
+            return Err(AppError::Stopped(cmd.app).into());
+        }
 
-        for app in default_apps {
-            State::insert_app(&app.0, app.1).await;
+        let Some(app) = self.apps.get_mut(&cmd.app) else {
+            return Err(AppError::NotFound(cmd.app).into());
+        };
+
+        let registry = self
+            .registries
+            .get(&cmd.app)
+            .expect("Every inserted app has a command registry");
+
+        match registry.parse(cmd.args.as_deref().unwrap_or_default()) {
+            Ok(ParsedCommand::Help) => reply(
+                cmd.reply_to.as_ref(),
+                &cmd.app,
+                cmd.args.as_deref(),
+                Ok(CommandOutput::message(registry.help())),
+            ),
+            Ok(ParsedCommand::Command(args)) => app.process_command(cmd, args).await,
+            Err(source) => {
+                return Err(DispatchError::Command {
+                    app: cmd.app,
+                    source,
+                })
+            }
         }
 
-        {
-            let mut state_lock = State::write().await;
-            let apps = &mut state_lock.apps;
+        Ok(())
+    }
 
-            for app in apps.values_mut() {
-                app.init(event_loop_proxy.clone())
+    /// Dispatches the next line of every running script whose previous line succeeded,
+    /// the scripts that ended are removed.
+    ///
+    /// # Returns
+    ///
+    /// The commands of the dispatched lines, to be dispatched this frame.
+    pub fn step_scripts(&mut self) -> Vec<Command> {
+        let mut commands = vec![];
+
+        self.scripts.retain_mut(|script| match script.step() {
+            ScriptStep::Waiting => true,
+            ScriptStep::Dispatch(cmd) => {
+                commands.push(cmd);
+                true
+            }
+            ScriptStep::Finished(Ok(count)) => {
+                info!("Ran {count} commands from script {}", script.name());
+                false
             }
+            ScriptStep::Finished(Err(err)) => {
+                error!("Script {} stopped: {err}", script.name());
+                false
+            }
+        });
+
+        commands
+    }
+
+    /// Inserts `app` under `app_name` and recomputes the app order.
+    /// Fails without inserting the app if the name is taken or its `runs_after` creates a cycle.
+    pub fn insert_app(&mut self, app_name: &str, app: Box<dyn App>) -> Result<(), AppError> {
+        let app_name = app_name.to_ascii_lowercase();
+        if self.contains_app(&app_name) {
+            return Err(AppError::AlreadyInserted(app_name));
         }
 
-        State::update(0.0).await;
+        let mut nodes = self.app_nodes();
+        nodes.push(AppNode::new(&app_name, app.as_ref()));
+        self.app_order = sort_apps(&nodes)?;
+
+        let registry = CommandRegistry::new(&app_name, app.commands());
+
+        self.registries.insert(app_name.to_owned(), registry);
+        self.subscriptions.insert(
+            app_name.to_owned(),
+            app.subscriptions().into_iter().collect(),
+        );
+        self.apps.insert(app_name.to_owned(), app);
+        self.insertion_order.push(app_name);
 
-        event_loop
+        Ok(())
     }
 
-    async fn update(delta_time: f32) {
-        let mut frame_commands: Vec<Option<Command>> = vec![];
-        {
-            let apps = &mut State::write().await.apps;
+    /// Shuts down and removes the app (running or stopped) inserted under `app_name`.
+    pub fn remove_app(&mut self, app_name: &str) -> Result<Box<dyn App>, AppError> {
+        let app_name = app_name.to_ascii_lowercase();
+
+        // Stopped apps were already shut down
+        let app = if let Some(mut app) = self.apps.remove(&app_name) {
+            app.shutdown();
+            app
+        } else {
+            self.stopped_apps
+                .remove(&app_name)
+                .ok_or_else(|| AppError::NotFound(app_name.clone()))?
+        };
 
-            for app in apps.values_mut() {
-                let cmds = app.update(delta_time);
+        self.registries.remove(&app_name);
+        self.subscriptions.remove(&app_name);
+        self.insertion_order.retain(|name| *name != app_name);
+        self.app_order = sort_apps(&self.app_nodes())?;
 
-                for cmd in cmds {
-                    frame_commands.push(Some(cmd));
+        Ok(app)
+    }
+
+    /// Replaces the running app inserted under `app_name` with `app`, keeping its place in the insertion order.
+    /// The old app is shut down and returned, the new one is initialized if the engine already is.
+    pub fn replace_app(
+        &mut self,
+        app_name: &str,
+        mut app: Box<dyn App>,
+    ) -> Result<Box<dyn App>, AppError> {
+        let app_name = app_name.to_ascii_lowercase();
+        if self.stopped_apps.contains_key(&app_name) {
+            return Err(AppError::Stopped(app_name));
+        }
+        if !self.apps.contains_key(&app_name) {
+            return Err(AppError::NotFound(app_name));
+        }
+
+        let nodes: Vec<AppNode> = self
+            .app_nodes()
+            .into_iter()
+            .map(|node| {
+                if node.name == app_name {
+                    AppNode::new(&app_name, app.as_ref())
+                } else {
+                    node
                 }
-            }
+            })
+            .collect();
+        self.app_order = sort_apps(&nodes)?;
+
+        let registry = CommandRegistry::new(&app_name, app.commands());
+        self.registries.insert(app_name.clone(), registry);
+        self.subscriptions
+            .insert(app_name.clone(), app.subscriptions().into_iter().collect());
+
+        if let Some(proxy) = &self.event_loop_proxy {
+            app.init(proxy.clone());
+        }
+
+        let mut old_app = self.apps.insert(app_name, app).unwrap();
+        old_app.shutdown();
+
+        Ok(old_app)
+    }
+
+    /// Shuts down the running app `app_name` and keeps it aside until it is restarted.
+    pub fn stop_app(&mut self, app_name: &str) -> Result<(), AppError> {
+        let app_name = app_name.to_ascii_lowercase();
+        if self.stopped_apps.contains_key(&app_name) {
+            return Err(AppError::Stopped(app_name));
         }
 
-        {
-            let mut state_lock = State::write().await;
-
-            for command in &mut frame_commands {
-                if !command.as_ref().unwrap().processed {
-                    let cmd = command.take();
-                    if let Some(app) = state_lock.apps.get_mut(&cmd.as_ref().unwrap().app) {
-                        app.process_command(cmd.unwrap()).await;
-                    } else {
-                        error!(
-                            "No app found with name: {} to process: \"{}\"",
-                            &cmd.as_ref().unwrap().app,
-                            &cmd.as_ref().unwrap().args.as_ref().unwrap()
-                        );
-                    }
+        let mut app = self
+            .apps
+            .remove(&app_name)
+            .ok_or_else(|| AppError::NotFound(app_name.clone()))?;
+
+        app.shutdown();
+
+        self.stopped_apps.insert(app_name, app);
+        self.app_order = sort_apps(&self.app_nodes())?;
+
+        Ok(())
+    }
+
+    /// Starts a stopped app again, or shuts down and re-initializes a running one.
+    pub fn restart_app(&mut self, app_name: &str) -> Result<(), AppError> {
+        let app_name = app_name.to_ascii_lowercase();
+
+        if let Some(app) = self.stopped_apps.remove(&app_name) {
+            self.apps.insert(app_name.clone(), app);
+
+            // Apps inserted while this one was stopped could depend on it in a cycle
+            match sort_apps(&self.app_nodes()) {
+                Ok(order) => self.app_order = order,
+                Err(err) => {
+                    let app = self.apps.remove(&app_name).unwrap();
+                    self.stopped_apps.insert(app_name, app);
+                    return Err(err);
                 }
             }
+        } else if let Some(app) = self.apps.get_mut(&app_name) {
+            app.shutdown();
+        } else {
+            return Err(AppError::NotFound(app_name));
         }
 
-        {
-            State::write()
-                .await
-                .command_queue
-                .add_commands(frame_commands);
+        if let Some(proxy) = &self.event_loop_proxy {
+            self.apps.get_mut(&app_name).unwrap().init(proxy.clone());
         }
 
-        {
-            let elp: EventLoopProxy<CommandEvent>;
-            {
-                elp = State::get_proxy().await;
-            }
-            let mut state = State::write().await;
-            state.command_queue.execute(elp);
+        Ok(())
+    }
+
+    /// Shuts down every running app, in reverse app order.
+    pub fn shutdown_apps(&mut self) {
+        for app in self.apps_in_order().into_iter().rev() {
+            app.shutdown();
         }
     }
 
-    pub async fn process_window_events(
-        event: winit::event::WindowEvent,
-        id: winit::window::WindowId,
-        delta_time: f32,
-    ) {
-        let apps = &mut State::write().await.apps;
+    pub fn contains_app(&self, app_name: &str) -> bool {
+        self.apps.contains_key(app_name) || self.stopped_apps.contains_key(app_name)
+    }
 
-        for app in apps.values_mut() {
-            app.process_window_event(&event, id, delta_time).await;
+    /// One line per inserted app: running apps in app order, followed by the stopped ones.
+    pub fn describe_apps(&self) -> String {
+        let mut description = String::from("apps:");
+
+        for (i, name) in self.app_order.iter().enumerate() {
+            description.push_str(&format!("\n  {i}: {name} (running)"));
         }
+
+        for name in &self.insertion_order {
+            if self.stopped_apps.contains_key(name) {
+                description.push_str(&format!("\n  -: {name} (stopped)"));
+            }
+        }
+
+        description
     }
 
-    pub async fn process_user_events(event: CommandEvent, delta_time: f32) {
-        let apps = &mut State::write().await.apps;
+    /// Loads the plugin library at `path` and inserts the apps it registers,
+    /// they are initialized right away if the engine already is.
+    ///
+    /// # Returns
+    ///
+    /// The name of the loaded plugin.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_plugin(&mut self, path: &str) -> Result<String, PluginError> {
+        let (plugin, registrar) = Plugin::load(path)?;
+
+        if self.plugins.contains_key(&plugin.name) {
+            // The apps have to be dropped while their library is still loaded
+            drop(registrar);
+            return Err(PluginError::AlreadyLoaded(plugin.name));
+        }
+
+        let mut apps = registrar.apps.into_iter();
+        let mut inserted: Vec<String> = vec![];
 
-        for app in apps.values_mut() {
-            app.process_user_event(&event, delta_time).await;
+        for (app_name, app) in apps.by_ref() {
+            if let Err(err) = self.insert_app(&app_name, app) {
+                for app_name in &inserted {
+                    let _ = self.remove_app(app_name);
+                }
+                drop(apps);
+
+                return Err(err.into());
+            }
+
+            inserted.push(app_name);
+        }
+
+        if let Some(proxy) = &self.event_loop_proxy {
+            for app_name in &inserted {
+                self.apps.get_mut(app_name).unwrap().init(proxy.clone());
+            }
         }
+
+        let name = plugin.name.clone();
+        self.plugins.insert(name.clone(), plugin);
+
+        Ok(name)
     }
 
-    pub async fn process_device_events(
-        event: winit::event::DeviceEvent,
-        device_id: winit::event::DeviceId,
-        delta_time: f32,
-    ) {
-        let apps = &mut State::write().await.apps;
+    /// Shuts down and removes every app of the plugin `name` and cancels the tasks of their commands.
+    ///
+    /// The commands, tasks and events they created may still be queued and run code of the library,
+    /// so it is only closed once they are done (see `plugins_to_close` and `close_plugin`).
+    ///
+    /// # Returns
+    ///
+    /// The names of the removed apps.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn unload_plugin(&mut self, name: &str) -> Result<Vec<String>, PluginError> {
+        let plugin = self
+            .plugins
+            .remove(name)
+            .ok_or_else(|| PluginError::NotLoaded(name.to_owned()))?;
+
+        for app_name in &plugin.apps {
+            if let Err(err) = self.remove_app(app_name) {
+                warn!("Plugin {name} app {app_name} was already removed: {err}");
+            }
+        }
 
-        for app in apps.values_mut() {
-            app.process_device_event(&event, device_id, delta_time)
-                .await;
+        self.command_queue.tasks().cancel_apps(&plugin.apps);
+
+        let apps = plugin.apps.clone();
+        self.unloading_plugins.push(UnloadingPlugin::new(plugin));
+
+        Ok(apps)
+    }
+
+    /// Runs `record` on the recorder if the engine is recording, the recording stops if it fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn record(&mut self, record: impl FnOnce(&mut Recorder) -> Result<(), RecordingError>) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(err) = record(recorder) {
+                error!("Stopped recording: {err}");
+                self.recorder = None;
+            }
         }
     }
 
-    pub async fn on_new_window_requested(props: NewWindowProps, window: winit::window::Window) {
-        let mut state_lock = State::write().await;
+    /// Names of the unloaded plugins whose apps have no task left, called once the command queue ran.
+    ///
+    /// A `CommandEvent::ClosePlugin` has to be sent for each of them: it is handled after the events
+    /// queued before it, then the library can be closed with `close_plugin`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn plugins_to_close(&mut self) -> Vec<String> {
+        let tasks = self.command_queue.tasks().clone();
+
+        self.unloading_plugins
+            .iter_mut()
+            .filter(|unloading| !unloading.close_requested)
+            .filter(|unloading| !tasks.any_running_for(&unloading.plugin.apps))
+            .map(|unloading| {
+                unloading.close_requested = true;
+                unloading.plugin.name.clone()
+            })
+            .collect()
+    }
+
+    /// Closes the library of the unloaded plugin `name`, see `plugins_to_close`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn close_plugin(&mut self, name: &str) -> Result<(), PluginError> {
+        let index = self
+            .unloading_plugins
+            .iter()
+            .position(|unloading| unloading.close_requested && unloading.plugin.name == name)
+            .ok_or_else(|| PluginError::NotLoaded(name.to_owned()))?;
+
+        self.unloading_plugins.remove(index).plugin.close()
+    }
 
-        let windower = state_lock
+    /// Names of the inserted apps in the order they are initialized, updated and receive events.
+    pub fn app_order(&self) -> &[String] {
+        &self.app_order
+    }
+
+    /// The inserted apps in `app_order`.
+    pub fn apps_in_order(&mut self) -> Vec<&mut Box<dyn App>> {
+        self.named_apps_in_order()
+            .into_iter()
+            .map(|(_, app)| app)
+            .collect()
+    }
+
+    /// Same as `apps_in_order`, together with the name each app was inserted under.
+    pub fn named_apps_in_order(&mut self) -> Vec<(&str, &mut Box<dyn App>)> {
+        let order = &self.app_order;
+
+        let mut apps: Vec<(&String, &mut Box<dyn App>)> = self.apps.iter_mut().collect();
+        apps.sort_by_key(|(name, _)| order.iter().position(|ordered| ordered == *name));
+
+        apps.into_iter()
+            .map(|(name, app)| (name.as_str(), app))
+            .collect()
+    }
+
+    /// Whether any running app subscribed to `kind`.
+    pub fn has_subscribers(&self, kind: EventKind) -> bool {
+        self.apps.keys().any(|name| self.is_subscribed(name, kind))
+    }
+
+    /// The running apps subscribed to `kind`, in `app_order`.
+    pub fn subscribers_in_order(&mut self, kind: EventKind) -> Vec<&mut Box<dyn App>> {
+        self.named_subscribers_in_order(kind)
+            .into_iter()
+            .map(|(_, app)| app)
+            .collect()
+    }
+
+    /// Same as `subscribers_in_order`, together with the name each app was inserted under.
+    pub fn named_subscribers_in_order(
+        &mut self,
+        kind: EventKind,
+    ) -> Vec<(&str, &mut Box<dyn App>)> {
+        let order = &self.app_order;
+        let subscriptions = &self.subscriptions;
+
+        let mut apps: Vec<(&String, &mut Box<dyn App>)> = self
+            .apps
+            .iter_mut()
+            .filter(|(name, _)| {
+                subscriptions
+                    .get(*name)
+                    .is_some_and(|kinds| subscribes_to(kinds, kind))
+            })
+            .collect();
+        apps.sort_by_key(|(name, _)| order.iter().position(|ordered| ordered == *name));
+
+        apps.into_iter()
+            .map(|(name, app)| (name.as_str(), app))
+            .collect()
+    }
+
+    fn is_subscribed(&self, app_name: &str, kind: EventKind) -> bool {
+        self.subscriptions
+            .get(app_name)
+            .is_some_and(|kinds| subscribes_to(kinds, kind))
+    }
+
+    fn app_nodes(&self) -> Vec<AppNode> {
+        self.insertion_order
+            .iter()
+            .filter_map(|name| {
+                self.apps
+                    .get(name)
+                    .map(|app| AppNode::new(name, app.as_ref()))
+            })
+            .collect()
+    }
+
+    pub fn on_new_window_requested(
+        &mut self,
+        props: NewWindowProps,
+        window: winit::window::Window,
+    ) {
+        let windower = self
             .apps
             .get_mut("windower")
             .unwrap()
@@ -183,175 +548,331 @@ impl State {
 
         windower.create_window(props, window);
     }
+}
+
+impl AppNode {
+    fn new(name: &str, app: &dyn App) -> Self {
+        Self {
+            name: name.to_owned(),
+            priority: app.priority(),
+            runs_after: app
+                .runs_after()
+                .iter()
+                .map(|dep| dep.to_ascii_lowercase())
+                .collect(),
+        }
+    }
+}
+
+fn subscribes_to(kinds: &HashSet<EventKind>, kind: EventKind) -> bool {
+    kinds.contains(&kind) || kinds.contains(&EventKind::All)
+}
 
-    pub async fn get_proxy() -> winit::event_loop::EventLoopProxy<CommandEvent> {
-        State::read().await.event_loop_proxy.clone().unwrap()
+/// Orders `nodes` (given in insertion order) so every app comes after the apps in its `runs_after`.
+/// Apps that are free to run are picked by ascending priority, then by insertion order.
+fn sort_apps(nodes: &[AppNode]) -> Result<Vec<String>, AppError> {
+    let index: HashMap<&str, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.name.as_str(), i))
+        .collect();
+
+    let mut in_degree = vec![0; nodes.len()];
+    let mut dependents: Vec<Vec<usize>> = vec![vec![]; nodes.len()];
+
+    for (i, node) in nodes.iter().enumerate() {
+        for dep in &node.runs_after {
+            if let Some(&dep_index) = index.get(dep.as_str()) {
+                in_degree[i] += 1;
+                dependents[dep_index].push(i);
+            }
+        }
     }
 
-    pub async fn read() -> RwLockReadGuard<'static, State> {
-        unsafe { GLOBAL_STATE.read().await }
+    let mut ready: BTreeSet<(i32, usize)> = (0..nodes.len())
+        .filter(|&i| in_degree[i] == 0)
+        .map(|i| (nodes[i].priority, i))
+        .collect();
+
+    let mut placed = vec![false; nodes.len()];
+    let mut order = Vec::with_capacity(nodes.len());
+
+    while let Some((_, i)) = ready.pop_first() {
+        placed[i] = true;
+        order.push(nodes[i].name.clone());
+
+        for &dependent in &dependents[i] {
+            in_degree[dependent] -= 1;
+            if in_degree[dependent] == 0 {
+                ready.insert((nodes[dependent].priority, dependent));
+            }
+        }
     }
 
-    pub async fn write() -> RwLockWriteGuard<'static, State> {
-        unsafe { GLOBAL_STATE.write().await }
+    if order.len() == nodes.len() {
+        return Ok(order);
     }
 
-    pub async fn insert_app(app_name: &str, app: Box<dyn App>) {
-        let app_name = app_name.to_ascii_lowercase();
-        if !State::read().await.apps.contains_key(app_name.as_str()) {
-            State::write().await.apps.insert(app_name.to_owned(), app);
-        } else {
-            warn!("State already contains app {app_name}!");
+    // Every app left has at least one dependency that was not placed either,
+    // following those dependencies from any of them has to loop back
+    let mut path: Vec<usize> = vec![placed.iter().position(|placed| !placed).unwrap()];
+    loop {
+        let current = *path.last().unwrap();
+        let next = nodes[current]
+            .runs_after
+            .iter()
+            .filter_map(|dep| index.get(dep.as_str()).copied())
+            .find(|&dep_index| !placed[dep_index])
+            .unwrap();
+
+        if let Some(start) = path.iter().position(|&i| i == next) {
+            let mut cycle: Vec<String> = path[start..]
+                .iter()
+                .map(|&i| nodes[i].name.clone())
+                .collect();
+            cycle.push(nodes[next].name.clone());
+
+            return Err(AppError::DependencyCycle(cycle));
         }
+
+        path.push(next);
     }
 }
 
-#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
-pub async fn run() {
-    let event_loop = State::init().await;
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
 
-    info!("Initialzied State!");
+    use async_trait::async_trait;
 
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        let builder = std::thread::Builder::new().name("CLI".into());
+    use super::*;
+    use crate::core::{
+        cli::parse_cli_line,
+        command_registry::{CommandDesc, ParsedArgs},
+        engine::Engine,
+        events::CommandEvent,
+    };
 
-        let runtime_cli = tokio::runtime::Builder::new_multi_thread()
-            .worker_threads(8)
-            .build()
-            .unwrap();
+    /// Writes the calls it receives to a log shared with the test.
+    struct Probe {
+        log: Arc<Mutex<Vec<String>>>,
+        subscriptions: Vec<EventKind>,
+    }
 
-        builder
-            .spawn(move || {
-                runtime_cli.block_on(run_cli());
-            })
-            .unwrap();
+    impl Probe {
+        fn boxed(subscriptions: Vec<EventKind>) -> (Box<dyn App>, Arc<Mutex<Vec<String>>>) {
+            let log = Arc::new(Mutex::new(vec![]));
+            let probe = Probe {
+                log: log.clone(),
+                subscriptions,
+            };
+
+            (Box::new(probe), log)
+        }
+
+        fn write(&self, call: &str) {
+            self.log.lock().unwrap().push(call.to_owned());
+        }
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
-    let runtime = tokio::runtime::Builder::new_multi_thread()
-        .worker_threads(16)
-        .build()
-        .unwrap();
+    #[async_trait(?Send)]
+    impl App for Probe {
+        fn get_name(&self) -> String {
+            "Probe".into()
+        }
 
-    let mut current_time = web_time::Instant::now();
+        fn init(&mut self, _elp: EngineProxy) {
+            self.write("init");
+        }
 
-    event_loop
-        .run(move |event, elwt: &winit::event_loop::EventLoopWindowTarget<CommandEvent>| {
-            if !is_running() {
-                elwt.exit()
-            }
+        fn shutdown(&mut self) {
+            self.write("shutdown");
+        }
 
-            // Calculate frame time (delta time)
-            let new_time = web_time::Instant::now();
-            let frame_time = (new_time - current_time).as_nanos();
-            #[allow(unused_mut)]    
-            let mut delta_time = frame_time as f32 * 0.000000001;
-            current_time = new_time;
-
-            #[cfg(target_arch = "wasm32")]
-            {
-                if delta_time < 0.0001 {
-                    delta_time = 0.0001;
-                }
-            }
+        fn update(&mut self, _delta_time: f32) -> Vec<Command> {
+            vec![]
+        }
 
-            //info!("{frame_time}ms");
-
-            elwt.set_control_flow(winit::event_loop::ControlFlow::Poll);
-
-            match event {
-                winit::event::Event::UserEvent(event) => {
-                    cfg_if::cfg_if! {
-                        if #[cfg(not(target_arch = "wasm32"))] {
-                            runtime.block_on(State::process_user_events(event.clone(), delta_time));
-                        }
-                        else {
-                            wasm_bindgen_futures::spawn_local(State::process_user_events(event.clone(), delta_time));
-                        }
-                    }
-                    match event {
-                        CommandEvent::RequestNewWindow(props) => {
-                            let window = winit::window::WindowBuilder::new()
-                                .with_inner_size(winit::dpi::Size::Physical(props.size))
-                                .with_title(props.name.clone())
-                                .build(elwt)
-                                .expect("Could not create new window T-T");
-
-                            cfg_if::cfg_if! {
-                                if #[cfg(not(target_arch = "wasm32"))] {
-                                    runtime.block_on(State::on_new_window_requested(props, window));
-                                }
-                                else {
-                                    wasm_bindgen_futures::spawn_local(State::on_new_window_requested(props, window));
-                                }
-                            }
-                        }
-                        CommandEvent::Exit => {
-                            terminate();
-                        }
-                        _ => {}
-                    }
-                }
-                winit::event::Event::WindowEvent { window_id, event } => {
-                    cfg_if::cfg_if! {
-                        if #[cfg(not(target_arch = "wasm32"))] {
-                            runtime.block_on(State::process_window_events(event.clone(), window_id, delta_time));
-                        }
-                        else {
-                            wasm_bindgen_futures::spawn_local(State::process_window_events(event.clone(), window_id, delta_time));
-                        }
-                    }
-                }
-                winit::event::Event::DeviceEvent { device_id, event } => {
-                    cfg_if::cfg_if! {
-                        if #[cfg(not(target_arch = "wasm32"))] {
-                            runtime.block_on(State::process_device_events(event.clone(), device_id, delta_time));
-                        }
-                        else {
-                            wasm_bindgen_futures::spawn_local(State::process_device_events(event.clone(), device_id, delta_time));
-                        }
-                    }
-                }
-                _ => {}
-            }
-            cfg_if::cfg_if! {
-                if #[cfg(not(target_arch = "wasm32"))] {
-                    runtime.block_on(State::update(delta_time));
-                }
-                else {
-                    wasm_bindgen_futures::spawn_local(State::update(delta_time));
-                }
+        fn commands(&self) -> Vec<CommandDesc> {
+            vec![CommandDesc::new("ping", "Writes ping to the log")]
+        }
+
+        async fn process_command(&mut self, _cmd: Command, _args: ParsedArgs) {
+            self.write("ping");
+        }
+
+        fn subscriptions(&self) -> Vec<EventKind> {
+            self.subscriptions.clone()
+        }
+
+        async fn process_user_event(&mut self, event: &CommandEvent, _delta_time: f32) {
+            match event.downcast_ref::<u32>() {
+                Some(value) => self.write(&format!("event {value}")),
+                None => self.write("event"),
             }
-        })
-        .unwrap();
-}
+        }
 
-#[cfg(target_arch = "wasm32")]
-use wasm_bindgen::prelude::*;
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
 
-#[cfg(target_arch = "wasm32")]
-fn init_trace() {
-    console_error_panic_hook::set_once();
-    tracing_wasm::set_as_global_default_with_config(tracing_wasm::WASMLayerConfig::default());
-}
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+    }
 
-#[cfg(not(target_arch = "wasm32"))]
-fn init_trace() {
-    use tracing::Level;
-    use tracing_subscriber::FmtSubscriber;
-
-    // Trace initialization
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::TRACE)
-        .without_time()
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_thread_names(true)
-        .with_file(true)
-        .with_line_number(true)
-        .finish();
-
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("Could not set default trace subscriver!");
+    /// An engine whose inserted apps are initialized with a headless proxy.
+    async fn engine() -> Engine {
+        let engine = Engine::new();
+        engine.write().await.event_loop_proxy = Some(engine.headless_proxy().0);
+        engine
+    }
+
+    /// Sends `probe ping` and a `u32` event.
+    async fn ping(engine: &Engine, value: u32) -> Result<(), DispatchError> {
+        engine
+            .process_user_events(CommandEvent::custom(value), 0.0)
+            .await;
+        engine
+            .write()
+            .await
+            .try_dispatch_command(parse_cli_line("probe ping"))
+            .await
+    }
+
+    fn calls(log: &Mutex<Vec<String>>) -> Vec<String> {
+        std::mem::take(&mut log.lock().unwrap())
+    }
+
+    #[test]
+    fn removed_and_stopped_apps_receive_nothing() {
+        pollster::block_on(async {
+            let engine = engine().await;
+            let (probe, log) = Probe::boxed(vec![EventKind::of::<u32>()]);
+            engine.insert_app("probe", probe).await.unwrap();
+
+            ping(&engine, 1).await.unwrap();
+            assert_eq!(calls(&log), ["event 1", "ping"]);
+
+            engine.write().await.stop_app("probe").unwrap();
+            assert_eq!(
+                ping(&engine, 2).await,
+                Err(AppError::Stopped("probe".into()).into())
+            );
+            assert_eq!(calls(&log), ["shutdown"]);
+
+            engine.write().await.restart_app("probe").unwrap();
+            assert_eq!(calls(&log), ["init"]);
+            ping(&engine, 3).await.unwrap();
+            assert_eq!(calls(&log), ["event 3", "ping"]);
+
+            engine.remove_app("probe").await.unwrap();
+            assert_eq!(
+                ping(&engine, 4).await,
+                Err(AppError::NotFound("probe".into()).into())
+            );
+            assert_eq!(calls(&log), ["shutdown"]);
+        });
+    }
+
+    #[test]
+    fn replaced_and_restarted_apps_are_initialized_again() {
+        pollster::block_on(async {
+            let engine = engine().await;
+            let (probe, old_log) = Probe::boxed(vec![EventKind::of::<u32>()]);
+            engine.insert_app("probe", probe).await.unwrap();
+
+            let (probe, log) = Probe::boxed(vec![EventKind::of::<u32>()]);
+            engine.replace_app("probe", probe).await.unwrap();
+            assert_eq!(calls(&old_log), ["shutdown"]);
+            assert_eq!(calls(&log), ["init"]);
+
+            ping(&engine, 1).await.unwrap();
+            assert!(calls(&old_log).is_empty());
+            assert_eq!(calls(&log), ["event 1", "ping"]);
+
+            engine.write().await.restart_app("probe").unwrap();
+            assert_eq!(calls(&log), ["shutdown", "init"]);
+        });
+    }
+
+    #[test]
+    fn apps_only_receive_the_events_they_subscribed_to() {
+        pollster::block_on(async {
+            let engine = engine().await;
+            let (numbers, numbers_log) = Probe::boxed(vec![EventKind::of::<u32>()]);
+            let (everything, everything_log) = Probe::boxed(vec![EventKind::All]);
+            let (nothing, nothing_log) = Probe::boxed(vec![]);
+            engine.insert_app("numbers", numbers).await.unwrap();
+            engine.insert_app("everything", everything).await.unwrap();
+            engine.insert_app("nothing", nothing).await.unwrap();
+            calls(&numbers_log);
+            calls(&everything_log);
+            calls(&nothing_log);
+
+            engine
+                .process_user_events(CommandEvent::custom(1u32), 0.0)
+                .await;
+            engine
+                .process_user_events(CommandEvent::custom("text"), 0.0)
+                .await;
+
+            assert_eq!(calls(&numbers_log), ["event 1"]);
+            assert_eq!(calls(&everything_log), ["event 1", "event"]);
+            assert!(calls(&nothing_log).is_empty());
+        });
+    }
+
+    fn node(name: &str, priority: i32, runs_after: &[&str]) -> AppNode {
+        AppNode {
+            name: name.to_owned(),
+            priority,
+            runs_after: runs_after.iter().map(|dep| dep.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn sorts_a_chain_after_its_dependencies() {
+        let nodes = [
+            node("c", 0, &["b"]),
+            node("b", 0, &["a"]),
+            node("a", 0, &[]),
+        ];
+
+        assert_eq!(sort_apps(&nodes).unwrap(), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn sorts_a_diamond_by_priority_then_insertion() {
+        let nodes = [
+            node("d", -10, &["b", "c"]),
+            node("b", 0, &["a"]),
+            node("c", -1, &["a"]),
+            node("a", 5, &[]),
+            node("e", 0, &[]),
+        ];
+
+        // d runs last despite its priority, c before b thanks to its priority
+        assert_eq!(sort_apps(&nodes).unwrap(), ["e", "a", "c", "b", "d"]);
+    }
+
+    #[test]
+    fn reports_dependency_cycles() {
+        let nodes = [
+            node("a", 0, &["c"]),
+            node("b", 0, &["a"]),
+            node("c", 0, &["b"]),
+            node("free", 0, &[]),
+        ];
+
+        let err = sort_apps(&nodes).unwrap_err();
+        assert_eq!(err.to_string(), "App dependency cycle: a -> c -> b -> a");
+    }
+
+    #[test]
+    fn ignores_apps_that_are_not_inserted() {
+        let nodes = [node("a", 0, &["missing"]), node("b", 0, &["a"])];
+
+        assert_eq!(sort_apps(&nodes).unwrap(), ["a", "b"]);
+    }
 }
@@ -0,0 +1,389 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use futures::{
+    future::{abortable, AbortHandle, Aborted},
+    FutureExt,
+};
+use tracing::debug;
+
+use crate::core::{
+    command_queue::{send_events, AsyncTask},
+    command_result::{reply, CommandFailure, CommandResult, ReplySender},
+    events::CommandEvent,
+    proxy::EngineProxy,
+};
+
+static TASK_THREADS: AtomicUsize = AtomicUsize::new(4);
+
+// Tasks of every engine share one runtime, it lives as long as the process so dropping an engine
+// from inside an async context never has to shut a runtime down
+#[cfg(not(target_arch = "wasm32"))]
+static TASK_RUNTIME: once_cell::sync::Lazy<tokio::runtime::Runtime> =
+    once_cell::sync::Lazy::new(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(TASK_THREADS.load(Ordering::Relaxed))
+            .thread_name("Task")
+            .enable_time()
+            .build()
+            .expect("Could not build the task runtime")
+    });
+
+/// Sets the number of worker threads of the task runtime (`EngineConfig::task_threads`).
+/// The runtime is shared by every engine of the process and started with the first task,
+/// so this only has an effect before that.
+pub fn set_task_threads(threads: usize) {
+    TASK_THREADS.store(threads.max(1), Ordering::Relaxed);
+}
+
+/// Identifies a task spawned by a `CommandQueue`, shown as `#<id>` (Ex: `cli cancel 3`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TaskId(pub u64);
+
+impl Display for TaskId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// How a task ended, see `TaskReport`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskOutcome {
+    /// The task returned its output, its events were sent before the report.
+    Completed,
+    Failed(CommandFailure),
+    Cancelled,
+    TimedOut,
+}
+
+/// Sent as `CommandEvent::TaskFinished` once a task ended, whatever the outcome.
+#[derive(Debug, Clone)]
+pub struct TaskReport {
+    pub id: TaskId,
+    /// App of the command the task belongs to.
+    pub app: String,
+    pub args: Option<String>,
+    pub outcome: TaskOutcome,
+    /// When the task was spawned.
+    pub started: web_time::Instant,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub id: TaskId,
+    pub app: String,
+    pub args: Option<String>,
+    pub started: web_time::Instant,
+    pub timeout: Option<Duration>,
+}
+
+struct RunningTask {
+    info: TaskInfo,
+    abort: AbortHandle,
+}
+
+/// Removes a task from the running ones once dropped, so a task that panicked does not stay listed.
+struct RunningGuard {
+    running: Arc<Mutex<HashMap<TaskId, RunningTask>>>,
+    id: TaskId,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.running
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.id);
+    }
+}
+
+/// Runs the async tasks of commands off the event loop thread and keeps track of the running ones.
+///
+/// Each task reports its end through the engine proxy: the events it returned (if it completed)
+/// followed by a `CommandEvent::TaskFinished`, so frames never wait on a task.
+/// Tasks run on a shared tokio runtime natively and on the browser's executor on the web.
+#[derive(Clone, Default)]
+pub struct TaskTracker {
+    next_id: Arc<AtomicU64>,
+    running: Arc<Mutex<HashMap<TaskId, RunningTask>>>,
+}
+
+impl TaskTracker {
+    /// Spawns `future`, it is cancelled once `timeout` has elapsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `app` - App of the command the task belongs to, reported back with its outcome
+    /// * `args` - Arguments of the command, reported back with its outcome
+    /// * `reply_to` - Issuer of the command, receives the result of the task
+    pub fn spawn(
+        &self,
+        app: &str,
+        args: Option<String>,
+        future: AsyncTask<CommandResult>,
+        timeout: Option<Duration>,
+        reply_to: Option<ReplySender>,
+        elp: EngineProxy,
+    ) -> TaskId {
+        let id = TaskId(self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let (future, abort) = abortable(future);
+
+        let info = TaskInfo {
+            id,
+            app: app.to_owned(),
+            args,
+            started: web_time::Instant::now(),
+            timeout,
+        };
+
+        // Registered before spawning so a task finishing right away can always remove itself
+        self.running.lock().unwrap().insert(
+            id,
+            RunningTask {
+                info: info.clone(),
+                abort,
+            },
+        );
+
+        let guard = RunningGuard {
+            running: Arc::clone(&self.running),
+            id,
+        };
+        let task = async move {
+            let future = AssertUnwindSafe(future).catch_unwind();
+            let result = match with_timeout(future, timeout).await {
+                None => Err(CommandFailure::TimedOut),
+                Some(Err(_panic)) => Err(CommandFailure::Failed("the task panicked".into())),
+                Some(Ok(Err(Aborted))) => Err(CommandFailure::Cancelled),
+                Some(Ok(Ok(result))) => send_events(result, &elp),
+            };
+
+            drop(guard);
+
+            let outcome = match &result {
+                Ok(_) => TaskOutcome::Completed,
+                Err(CommandFailure::Cancelled) => TaskOutcome::Cancelled,
+                Err(CommandFailure::TimedOut) => TaskOutcome::TimedOut,
+                Err(failure) => TaskOutcome::Failed(failure.clone()),
+            };
+
+            debug!(
+                "Task {id} of <{}> ended after {:?}: {outcome:?}",
+                info.app,
+                info.started.elapsed()
+            );
+
+            reply(reply_to.as_ref(), &info.app, info.args.as_deref(), result);
+
+            let report = TaskReport {
+                id,
+                app: info.app,
+                args: info.args,
+                outcome,
+                started: info.started,
+                elapsed: info.started.elapsed(),
+            };
+
+            // Nobody is left to be told when the engine exited in the meantime
+            let _ = elp.send_event(CommandEvent::TaskFinished(report));
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        TASK_RUNTIME.spawn(task);
+
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(task);
+
+        id
+    }
+
+    /// Asks the task to stop, it reports `TaskOutcome::Cancelled` unless it already finished.
+    ///
+    /// # Returns
+    ///
+    /// `false` if no task with this id is running.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        match self.running.lock().unwrap().get(&id) {
+            Some(task) => {
+                task.abort.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels every running task (Ex: when the engine exits).
+    ///
+    /// # Returns
+    ///
+    /// The number of cancelled tasks.
+    pub fn cancel_all(&self) -> usize {
+        let running = self.running.lock().unwrap();
+        for task in running.values() {
+            task.abort.abort();
+        }
+
+        running.len()
+    }
+
+    /// Cancels the running tasks of the commands of `apps` (Ex: the apps of an unloaded plugin).
+    ///
+    /// # Returns
+    ///
+    /// The number of cancelled tasks.
+    pub fn cancel_apps(&self, apps: &[String]) -> usize {
+        let running = self.running.lock().unwrap();
+        let tasks: Vec<&RunningTask> = running
+            .values()
+            .filter(|task| apps.contains(&task.info.app))
+            .collect();
+
+        for task in &tasks {
+            task.abort.abort();
+        }
+
+        tasks.len()
+    }
+
+    /// Whether a task of the commands of one of `apps` is still running.
+    pub fn any_running_for(&self, apps: &[String]) -> bool {
+        self.running
+            .lock()
+            .unwrap()
+            .values()
+            .any(|task| apps.contains(&task.info.app))
+    }
+
+    /// The running tasks, oldest first.
+    pub fn running(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<TaskInfo> = self
+            .running
+            .lock()
+            .unwrap()
+            .values()
+            .map(|task| task.info.clone())
+            .collect();
+        tasks.sort_by_key(|task| task.id);
+
+        tasks
+    }
+
+    pub fn len(&self) -> usize {
+        self.running.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// One line per running task.
+    pub fn describe(&self) -> String {
+        let mut description = String::from("tasks:");
+
+        for task in self.running() {
+            description.push_str(&format!("\n  {}: {}", task.id, task.app));
+            if let Some(args) = &task.args {
+                description.push_str(&format!(" {args}"));
+            }
+
+            description.push_str(&format!(" ({:.1}s", task.started.elapsed().as_secs_f32()));
+            if let Some(timeout) = task.timeout {
+                description.push_str(&format!(", timeout {:.1}s", timeout.as_secs_f32()));
+            }
+            description.push(')');
+        }
+
+        description
+    }
+}
+
+/// # Returns
+///
+/// `None` if `timeout` elapsed before `future` completed.
+async fn with_timeout<F: Future>(future: F, timeout: Option<Duration>) -> Option<F::Output> {
+    let Some(timeout) = timeout else {
+        return Some(future.await);
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        tokio::time::timeout(timeout, future).await.ok()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        use futures::future::{select, Either};
+
+        let future = std::pin::pin!(future);
+        let sleep = std::pin::pin!(sleep(timeout));
+
+        match select(future, sleep).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn sleep(duration: Duration) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        web_sys::window()
+            .expect("No window to time out tasks")
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                &resolve,
+                duration.as_millis() as i32,
+            )
+            .expect("Could not set a task timeout");
+    });
+
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn panicking_tasks_are_reported_and_no_longer_running() {
+        let (commands, _) = std::sync::mpsc::channel();
+        let (elp, events) = EngineProxy::headless(
+            commands,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let tracker = TaskTracker::default();
+
+        let id = tracker.spawn(
+            "scene",
+            None,
+            Box::pin(async { panic!("task failure") }),
+            None,
+            None,
+            elp,
+        );
+
+        let report = loop {
+            match events.recv_timeout(Duration::from_secs(5)) {
+                Ok(CommandEvent::TaskFinished(report)) => break report,
+                Ok(_) => continue,
+                Err(err) => panic!("no report for task {id}: {err}"),
+            }
+        };
+
+        assert_eq!(report.id, id);
+        assert!(matches!(report.outcome, TaskOutcome::Failed(_)));
+        assert!(tracker.is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ClockError {
+    #[error("Invalid time scale {0}, expected a finite number >= 0")]
+    InvalidTimeScale(f32),
+}
+
+/// Controls of the simulation `Clock`, sent as `CommandEvent::Time` (Ex: by `cli pause`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeControl {
+    Pause,
+    Resume,
+    /// Pauses the clock and runs the given number of ticks.
+    Step(u32),
+    Scale(f32),
+    /// Logs the state of the clock.
+    Status,
+}
+
+/// Simulation time of an `Engine`: turns the variable frame times into fixed update ticks.
+///
+/// Every frame `advance` adds the (scaled) frame time to an accumulator and returns how many ticks of
+/// `fixed_delta` fit in it, at most `max_ticks_per_frame`. Time beyond that limit is dropped
+/// so a long frame (Ex: a breakpoint) does not make the simulation spiral trying to catch up.
+/// While paused, the stepped ticks are run at most `max_ticks_per_frame` per frame as well.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    fixed_delta: f32,
+    max_ticks_per_frame: u32,
+    time_scale: f32,
+    paused: bool,
+
+    pending_steps: u32,
+    accumulator: f32,
+    tick: u64,
+    elapsed: f64,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new(1.0 / 60.0, 5)
+    }
+}
+
+impl Clock {
+    pub fn new(fixed_delta: f32, max_ticks_per_frame: u32) -> Self {
+        Clock {
+            fixed_delta,
+            max_ticks_per_frame: max_ticks_per_frame.max(1),
+            time_scale: 1.0,
+            paused: false,
+            pending_steps: 0,
+            accumulator: 0.0,
+            tick: 0,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Accounts for a frame that took `frame_delta` seconds.
+    ///
+    /// # Returns
+    ///
+    /// The number of fixed ticks to run this frame.
+    pub fn advance(&mut self, frame_delta: f32) -> u32 {
+        let ticks = if self.paused {
+            let ticks = self.pending_steps.min(self.max_ticks_per_frame);
+            self.pending_steps -= ticks;
+            ticks
+        } else {
+            self.accumulator += frame_delta.max(0.0) * self.time_scale;
+
+            let ticks = (self.accumulator / self.fixed_delta) as u32;
+            if ticks > self.max_ticks_per_frame {
+                self.accumulator %= self.fixed_delta;
+                self.max_ticks_per_frame
+            } else {
+                self.accumulator -= ticks as f32 * self.fixed_delta;
+                ticks
+            }
+        };
+
+        self.tick += ticks as u64;
+        self.elapsed += ticks as f64 * self.fixed_delta as f64;
+
+        ticks
+    }
+
+    pub fn apply(&mut self, control: TimeControl) -> Result<(), ClockError> {
+        match control {
+            TimeControl::Pause => self.pause(),
+            TimeControl::Resume => self.resume(),
+            TimeControl::Step(ticks) => self.step(ticks),
+            TimeControl::Scale(scale) => self.set_time_scale(scale)?,
+            TimeControl::Status => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes from where the clock was paused, the time spent paused is not caught up.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.pending_steps = 0;
+        self.accumulator = 0.0;
+    }
+
+    /// Pauses the clock and runs `ticks` more ticks, starting on the next frame.
+    pub fn step(&mut self, ticks: u32) {
+        self.paused = true;
+        self.pending_steps = self.pending_steps.saturating_add(ticks);
+    }
+
+    /// Ex: 0.5 runs the simulation at half speed, 0 stops it.
+    ///
+    /// # Returns
+    ///
+    /// `ClockError::InvalidTimeScale` for negative or non-finite scales, the scale is left unchanged.
+    pub fn set_time_scale(&mut self, time_scale: f32) -> Result<(), ClockError> {
+        self.time_scale = Clock::validate_time_scale(time_scale)?;
+        Ok(())
+    }
+
+    /// Checks `time_scale` can be given to `set_time_scale` (Ex: before sending a `TimeControl::Scale`).
+    pub fn validate_time_scale(time_scale: f32) -> Result<f32, ClockError> {
+        if time_scale.is_finite() && time_scale >= 0.0 {
+            Ok(time_scale)
+        } else {
+            Err(ClockError::InvalidTimeScale(time_scale))
+        }
+    }
+
+    pub fn set_fixed_delta(&mut self, fixed_delta: f32) {
+        self.fixed_delta = fixed_delta;
+    }
+
+    pub fn set_max_ticks_per_frame(&mut self, max_ticks_per_frame: u32) {
+        self.max_ticks_per_frame = max_ticks_per_frame.max(1);
+    }
+
+    pub fn fixed_delta(&self) -> f32 {
+        self.fixed_delta
+    }
+
+    pub fn max_ticks_per_frame(&self) -> u32 {
+        self.max_ticks_per_frame
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Number of ticks run since the engine started.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Simulated time in seconds (`tick * fixed_delta`).
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// How far the simulation is into the next tick, from 0 to 1 (Ex: to interpolate rendering).
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.fixed_delta).clamp(0.0, 1.0)
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "tick {} ({:.3}s), {} Hz, time scale {}{}",
+            self.tick,
+            self.elapsed,
+            (1.0 / self.fixed_delta).round(),
+            self.time_scale,
+            if self.paused { ", paused" } else { "" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_turned_into_fixed_ticks() {
+        let mut clock = Clock::new(0.5, 5);
+
+        assert_eq!(clock.advance(0.25), 0);
+        assert_eq!(clock.advance(0.5), 1);
+        assert_eq!(clock.alpha(), 0.5);
+        assert_eq!(clock.tick(), 1);
+        assert_eq!(clock.elapsed(), 0.5);
+
+        // A long frame runs at most max_ticks_per_frame ticks, the rest is dropped
+        assert_eq!(clock.advance(100.0), 5);
+        assert_eq!(clock.advance(0.0), 0);
+    }
+
+    #[test]
+    fn time_scale_speeds_up_the_ticks() {
+        let mut clock = Clock::new(0.5, 5);
+        clock.set_time_scale(2.0).unwrap();
+
+        assert_eq!(clock.advance(0.5), 2);
+
+        clock.set_time_scale(0.0).unwrap();
+        assert_eq!(clock.advance(10.0), 0);
+    }
+
+    #[test]
+    fn invalid_time_scales_are_rejected() {
+        let mut clock = Clock::new(0.5, 5);
+
+        for scale in [-1.0, f32::NAN, f32::INFINITY] {
+            assert!(clock.set_time_scale(scale).is_err());
+            assert!(clock.apply(TimeControl::Scale(scale)).is_err());
+        }
+        assert_eq!(clock.time_scale(), 1.0);
+    }
+
+    #[test]
+    fn steps_run_while_paused_at_most_max_ticks_per_frame() {
+        let mut clock = Clock::new(0.5, 5);
+        clock.step(7);
+
+        assert!(clock.is_paused());
+        assert_eq!(clock.advance(0.0), 5);
+        assert_eq!(clock.advance(0.0), 2);
+        assert_eq!(clock.advance(10.0), 0);
+        assert_eq!(clock.tick(), 7);
+    }
+
+    #[test]
+    fn pending_steps_saturate() {
+        let mut clock = Clock::new(0.5, u32::MAX);
+        clock.step(u32::MAX);
+        clock.step(u32::MAX);
+
+        assert_eq!(clock.advance(0.0), u32::MAX);
+        assert_eq!(clock.advance(0.0), 0);
+    }
+
+    #[test]
+    fn resume_drops_the_pending_steps() {
+        let mut clock = Clock::new(0.5, 5);
+        clock.step(3);
+        clock.resume();
+
+        assert!(!clock.is_paused());
+        assert_eq!(clock.advance(0.0), 0);
+    }
+}
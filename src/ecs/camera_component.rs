@@ -43,6 +43,15 @@ impl CameraComponent {
             return None;
         }
 
+        // Each value has to be a number, the camera is not created otherwise
+        let parse = |index: usize, name: &str| match args[index].parse::<f32>() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                error!("Could not parse value for '{name}': {}", args[index]);
+                None
+            }
+        };
+
         match args[0] {
             "2D" => {
                 if args.len() < 10 {
@@ -58,45 +67,18 @@ impl CameraComponent {
                     );
                 }
 
-                let left: f32 = args[1].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'left': {}", args[1]);
-                    0.0
-                });
-                let right: f32 = args[2].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'right': {}", args[2]);
-                    0.0
-                });
-                let bottom: f32 = args[3].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'bottom': {}", args[3]);
-                    0.0
-                });
-                let top: f32 = args[4].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'top': {}", args[4]);
-                    0.0
-                });
-
-                let x: f32 = args[5].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'camera_pos:x': {}", args[5]);
-                    0.0
-                });
-                let y: f32 = args[6].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'camera_pos:y': {}", args[6]);
-                    0.0
-                });
-                let z: f32 = args[7].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'camera_pos:z': {}", args[7]);
-                    0.0
-                });
+                let left = parse(1, "left")?;
+                let right = parse(2, "right")?;
+                let bottom = parse(3, "bottom")?;
+                let top = parse(4, "top")?;
+
+                let x = parse(5, "camera_pos:x")?;
+                let y = parse(6, "camera_pos:y")?;
+                let z = parse(7, "camera_pos:z")?;
 
                 let camera_pos: cgmath::Point3<f32> = cgmath::Point3 { x, y, z };
-                let znear: f32 = args[8].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'znear': {}", args[8]);
-                    0.0
-                });
-                let zfar: f32 = args[9].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'zfar': {}", args[9]);
-                    0.0
-                });
+                let znear = parse(8, "znear")?;
+                let zfar = parse(9, "zfar")?;
 
                 let props = OrthogonalProps {
                     left,
@@ -123,9 +105,9 @@ impl CameraComponent {
                 })
             }
             "3D" => {
-                if args.len() < 7 {
+                if args.len() < 8 {
                     error!(
-                        "Expected at least 7 arguments. Add 'help' to argument list to see usage."
+                        "Expected at least 8 arguments. Add 'help' to argument list to see usage."
                     );
                     return None;
                 }
@@ -138,37 +120,16 @@ impl CameraComponent {
                     );
                 }
 
-                let aspect: f32 = args[1].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'aspect ratio': {}", args[0]);
-                    0.0
-                });
-                let fovy: f32 = args[2].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'fovy': {}", args[1]);
-                    0.0
-                });
-
-                let x: f32 = args[3].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'camera_pos:x': {}", args[2]);
-                    0.0
-                });
-                let y: f32 = args[4].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'camera_pos:y': {}", args[3]);
-                    0.0
-                });
-                let z: f32 = args[5].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'camera_pos:z': {}", args[4]);
-                    0.0
-                });
+                let aspect = parse(1, "aspect ratio")?;
+                let fovy = parse(2, "fovy")?;
+
+                let x = parse(3, "camera_pos:x")?;
+                let y = parse(4, "camera_pos:y")?;
+                let z = parse(5, "camera_pos:z")?;
 
                 let camera_pos: cgmath::Point3<f32> = cgmath::Point3 { x, y, z };
-                let znear: f32 = args[6].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'znear': {}", args[5]);
-                    0.0
-                });
-                let zfar: f32 = args[7].parse().unwrap_or_else(|_| {
-                    error!("Could not parse value for 'zfar': {}", args[6]);
-                    0.0
-                });
+                let znear = parse(6, "znear")?;
+                let zfar = parse(7, "zfar")?;
 
                 let props = PerspectiveProps { aspect, fovy };
 
@@ -1,9 +1,16 @@
 use bevy_ecs::component::Component;
 
+use crate::{
+    assets::handle::{Handle, HandleRegistry},
+    renderer::resources::model::SunModel,
+};
+
 #[derive(Debug, Clone, Component)]
 pub struct ModelComponent {
     pub id: uuid::Uuid,
     pub model_path: String,
+    /// Keeps the model loaded while the component exists, none without a model path.
+    pub handle: Option<Handle<SunModel>>,
 }
 
 impl ModelComponent {
@@ -11,13 +18,18 @@ impl ModelComponent {
         Self {
             id: uuid::Uuid::new_v4(),
             model_path: "".to_string(),
+            handle: None,
         }
     }
 
-    pub fn from_args(args: Vec<&str>) -> Self {
+    /// # Arguments
+    ///
+    /// * `handles` - The handles of the engine, see `EngineProxy::handles`
+    pub fn from_args(args: Vec<&str>, handles: &HandleRegistry) -> Self {
         Self {
             id: uuid::Uuid::new_v4(),
             model_path: args[0].to_owned(),
+            handle: Some(handles.handle(args[0])),
         }
     }
 }
@@ -1,6 +1,6 @@
 use bevy_ecs::component::Component;
 
-#[derive(Debug, Component)]
+#[derive(Debug, Clone, Component)]
 pub struct NameComponent {
     pub name: String,
 }
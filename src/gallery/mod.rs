@@ -1,2 +1,3 @@
 pub mod scene;
+pub mod scene_history;
 pub mod ui_handler;
@@ -7,18 +7,17 @@ use bevy_ecs::{
 };
 use cgmath::Rotation3;
 use tracing::{error, warn};
-use winit::{
-    event::{ElementState, MouseScrollDelta},
-    event_loop::EventLoopProxy,
-};
+use winit::event::{ElementState, MouseScrollDelta};
 
 use crate::{
+    assets::handle::HandleRegistry,
     core::{
         app::App,
         command_queue::{Command, CommandType, Task},
-        events::CommandEvent,
-        state::initialized,
-        util,
+        command_registry::{ArgDesc, ArgType, CommandDesc, ParsedArgs},
+        command_result::{CommandFailure, CommandOutput, CommandResult},
+        events::{CommandEvent, EventKind},
+        proxy::EngineProxy,
     },
     ecs::{
         camera_component::{ActiveCameraComponent, CameraComponent},
@@ -28,7 +27,6 @@ use crate::{
     prelude::{
         camera_component::CamType,
         resources::model::RenderModelDesc,
-        state,
         sun::RenderFrameDesc,
         text_component::TextDesc,
         transform_component::TransformComponent,
@@ -37,16 +35,31 @@ use crate::{
     },
 };
 
-use super::ui_handler::UIHandler;
+use super::{
+    scene_history::{self, SceneComponent, SceneHistory, SceneOp},
+    ui_handler::UIHandler,
+};
+
+/// Script spawning the default scene, run on startup unless the `exec` setting says otherwise.
+pub const DEFAULT_SCENE_SCRIPT: &str = "scripts/default_scene.sunset";
+
+/// There are no script files on the web, `DEFAULT_SCENE_SCRIPT` is built in instead.
+#[cfg(target_arch = "wasm32")]
+pub const DEFAULT_SCENE_SOURCE: &str = include_str!("../../scripts/default_scene.sunset");
 
 pub struct Scene {
     pub world: bevy_ecs::world::World,
     pub commands: Vec<Command>,
 
-    pub proxy: Option<EventLoopProxy<CommandEvent>>,
+    pub proxy: Option<EngineProxy>,
+    /// The handles of the engine, held by the model components (see `EngineProxy::handles`).
+    handles: HandleRegistry,
 
     pub ui_handler: UIHandler,
 
+    /// Undo and redo of the scene commands, cleared when the scene is reloaded.
+    pub history: SceneHistory,
+
     // Temp cam controls
     cam_speed: f32,
     cam_should_move: bool,
@@ -56,8 +69,6 @@ pub struct Scene {
     rotation_speed: f32,
     obj_should_rotate: bool,
     mouse_delta_x: f32,
-
-    time: web_time::Instant,
 }
 
 impl Default for Scene {
@@ -66,14 +77,15 @@ impl Default for Scene {
             world: World::new(),
             commands: vec![],
             proxy: None,
+            handles: HandleRegistry::default(),
             ui_handler: UIHandler::new(),
+            history: SceneHistory::default(),
             cam_speed: 0.0,
             cam_should_move: false,
             mouse_delta_y: 0.0,
             rotation_speed: 0.0,
             obj_should_rotate: false,
             mouse_delta_x: 0.0,
-            time: web_time::Instant::now(),
         }
     }
 }
@@ -93,77 +105,77 @@ impl Scene {
         scene
     }
 
-    pub async fn process_scene_commands(&mut self, mut cmd: Command) {
-        let args = cmd.args.clone().unwrap();
-        let vec_args: Vec<&str> = args.split(' ').collect();
-
-        let task = match vec_args[0].to_ascii_lowercase().trim() {
-            "add" => self.add_entity_from_args(vec_args[1..].join(" ").as_str()),
-            "remove" => self.remove_entity(vec_args[1..].join(" ").as_str()),
-            "set_model" => {
-                if vec_args[1..].len() >= 2 {
-                    self.set_model(vec_args[1], vec_args[2])
-                } else {
-                    error!("Expected 2 or more arguments");
-                    None
-                }
-            }
-            _ => Scene::unsupported(args.as_str()),
+    pub async fn process_scene_commands(&mut self, mut cmd: Command, args: ParsedArgs) {
+        // The operations of one command are undone together
+        self.history.begin(cmd.args.as_deref().unwrap_or_default());
+
+        let task = match args.command() {
+            "add" => self.add_entity_from_args(&args),
+            "remove" => self.remove_entity(args.get_str("name").unwrap()),
+            "set_model" => self.set_model(
+                args.get_str("model_path").unwrap(),
+                args.get_str("entity_name").unwrap(),
+            ),
+            "undo" => self.undo(args.get_uint("count").unwrap()),
+            "redo" => self.redo(args.get_uint("count").unwrap()),
+            "history" => self.describe_history(),
+            _ => Scene::unsupported(cmd.args.as_deref().unwrap_or_default()),
         };
 
+        self.history.commit();
+
         cmd.processed = true;
         cmd.task = task;
-        cmd.args = Some(args);
 
         self.commands.push(cmd);
     }
 
-    pub fn add_entity_from_args(&mut self, args: &str) -> Option<Task<Vec<CommandEvent>>> {
-        let components = util::extract_arguments(args);
+    pub fn add_entity_from_args(&mut self, args: &ParsedArgs) -> Option<Task<CommandResult>> {
+        // Validated before anything is spawned, an invalid command leaves the scene and its history untouched
+        let camera = match args.get_list("camera") {
+            Some(camera_args) => {
+                let camera_args: Vec<&str> = camera_args.iter().map(String::as_str).collect();
+                match CameraComponent::from_args(camera_args.clone()) {
+                    Some(camera) => Some(camera),
+                    None => {
+                        let failure = CommandFailure::Failed(format!(
+                            "Failed to create component <camera> with args <{:?}>",
+                            camera_args
+                        ));
+                        return Some(Box::new(move || Err(failure.clone())));
+                    }
+                }
+            }
+            None => None,
+        };
 
-        let mut events = Vec::new();
+        let mut components = vec![];
 
-        let mut entity = self.world.spawn_empty();
+        if let Some(name) = args.get_str("name") {
+            components.push(SceneComponent::Name(NameComponent {
+                name: name.to_owned(),
+            }));
+        }
 
-        for (component_name, args) in components {
-            match component_name {
-                "name" => {
-                    let name = NameComponent::from_args(args).unwrap();
-                    entity.insert(name);
-                }
-                "model" => {
-                    let model = ModelComponent::from_args(args.clone());
-                    let transform = TransformComponent::zero();
-                    events.push(CommandEvent::RequestCreateModel(model.clone()));
-                    entity.insert((model, transform));
-                }
-                "camera" => {
-                    let camera = CameraComponent::from_args(args.clone());
-                    if let Some(cam) = camera {
-                        entity.insert(cam);
+        if let Some(model_path) = args.get_str("model") {
+            components.push(SceneComponent::Model(ModelComponent::from_args(
+                vec![model_path],
+                &self.handles,
+            )));
+            components.push(SceneComponent::Transform(TransformComponent::zero()));
+        }
 
-                        warn!("Note: (@A40) Please change active camera functionality!");
-                        entity.insert(ActiveCameraComponent {});
-                    } else {
-                        error!(
-                            "Failed to create component <{}> with args <{:?}>",
-                            component_name,
-                            args.clone()
-                        );
-                    }
-                }
-                _ => {
-                    error!(
-                        "Unknown component: {} with args: {:?}",
-                        component_name, args
-                    );
-                }
-            }
+        if let Some(camera) = camera {
+            components.push(SceneComponent::Camera(camera));
+
+            warn!("Note: (@A40) Please change active camera functionality!");
+            components.push(SceneComponent::ActiveCamera);
         }
 
-        let task = move || events.clone();
+        let (entity, events) = scene_history::spawn(&mut self.world, &components);
+        self.history.record(SceneOp::Spawn { entity, components });
 
-        Some(Box::new(task))
+        Some(Box::new(move || Ok(events.clone().into())))
     }
 
     pub fn add_entity<T: Bundle>(&mut self, components: T) -> Entity {
@@ -212,45 +224,131 @@ impl Scene {
         &mut self,
         model_path: &str,
         entity_name: &str,
-    ) -> Option<Task<Vec<CommandEvent>>> {
-        let mut events = Vec::new();
-        let entity = self.get_entity_with_name(entity_name);
-        if let Some(e) = entity {
-            let model = self.world.get_mut::<ModelComponent>(e);
-            if let Some(mut m) = model {
-                m.model_path = model_path.to_owned();
-                events.push(CommandEvent::RequestCreateModel(m.clone()));
-            } else {
-                error!("Entity <{}> has no model component!", entity_name);
-                let model = ModelComponent::from_args(vec![model_path]);
-                let transform = TransformComponent::zero();
-                events.push(CommandEvent::RequestCreateModel(model.clone()));
+    ) -> Option<Task<CommandResult>> {
+        let Some(entity) = self.get_entity_with_name(entity_name) else {
+            let failure = CommandFailure::Failed(format!("Entity <{}> not found!", entity_name));
+            return Some(Box::new(move || Err(failure.clone())));
+        };
 
-                self.world
-                    .get_entity_mut(e)
-                    .unwrap()
-                    .insert((model, transform));
+        let mut components = vec![];
+        match self.world.get::<ModelComponent>(entity) {
+            Some(model) => components.push(SceneComponent::Model(ModelComponent {
+                id: model.id,
+                ..ModelComponent::from_args(vec![model_path], &self.handles)
+            })),
+            None => {
+                error!("Entity <{}> has no model component!", entity_name);
+                components.push(SceneComponent::Model(ModelComponent::from_args(
+                    vec![model_path],
+                    &self.handles,
+                )));
+                components.push(SceneComponent::Transform(TransformComponent::zero()));
             }
-        } else {
-            error!("Entity <{}> not found!", entity_name);
         }
 
-        let task = move || events.clone();
+        let mut events = vec![];
+        for component in components {
+            events.extend(self.insert_component(entity, component));
+        }
+
+        let task = move || Ok(events.clone().into());
 
         Some(Box::new(task))
     }
 
-    pub fn remove_entity(&mut self, _name: &str) -> Option<Task<Vec<CommandEvent>>> {
-        let _entitis = self.query_world::<With<NameComponent>>();
+    /// Inserts `component` into `entity`, replacing the one of the same type, as an undoable operation.
+    pub fn insert_component(
+        &mut self,
+        entity: Entity,
+        component: SceneComponent,
+    ) -> Vec<CommandEvent> {
+        let replaced = component.kind().get(&self.world, entity);
+        let events = component.insert(&mut self.world, entity);
+
+        self.history.record(SceneOp::Insert {
+            entity,
+            component,
+            replaced,
+        });
+
+        events
+    }
 
-        let events = Vec::new();
+    pub fn remove_entity(&mut self, name: &str) -> Option<Task<CommandResult>> {
+        let Some(entity) = self.get_entity_with_name(name) else {
+            let failure = CommandFailure::Failed(format!("Entity <{}> not found!", name));
+            return Some(Box::new(move || Err(failure.clone())));
+        };
 
-        let task = move || events.clone();
+        let components = scene_history::snapshot(&self.world, entity);
+        self.world.despawn(entity);
+        self.history.record(SceneOp::Despawn { entity, components });
 
-        Some(Box::new(task))
+        Some(Box::new(|| Ok(vec![].into())))
+    }
+
+    /// Reverts the last `count` scene commands, see `SceneHistory`.
+    pub fn undo(&mut self, count: u64) -> Option<Task<CommandResult>> {
+        self.step_history(count, true)
+    }
+
+    /// Applies the last `count` undone scene commands again.
+    pub fn redo(&mut self, count: u64) -> Option<Task<CommandResult>> {
+        self.step_history(count, false)
+    }
+
+    fn describe_history(&self) -> Option<Task<CommandResult>> {
+        let history = self.history.describe();
+
+        Some(Box::new(move || {
+            Ok(CommandOutput::message(history.clone()))
+        }))
+    }
+
+    fn step_history(&mut self, count: u64, undo: bool) -> Option<Task<CommandResult>> {
+        let mut events = vec![];
+        let mut descriptions = vec![];
+
+        for _ in 0..count {
+            let step = if undo {
+                self.history.undo(&mut self.world)
+            } else {
+                self.history.redo(&mut self.world)
+            };
+
+            let Some((description, step_events)) = step else {
+                break;
+            };
+            events.extend(step_events);
+            descriptions.push(description);
+        }
+
+        let verb = if undo { "undid" } else { "redid" };
+        let result = if descriptions.is_empty() {
+            Err(CommandFailure::Failed(format!(
+                "nothing to {}",
+                if undo { "undo" } else { "redo" }
+            )))
+        } else {
+            Ok(CommandOutput::from(events)
+                .with_message(format!("{verb}: {}", descriptions.join(", "))))
+        };
+
+        Some(Box::new(move || result.clone()))
     }
 
-    pub fn cleanup(&mut self) {}
+    /// Whether the engine finished initializing (the renderer is ready to receive the scene).
+    pub fn initialized(&self) -> bool {
+        self.proxy.as_ref().is_some_and(|proxy| proxy.initialized())
+    }
+
+    /// Despawns every entity and drops the pending commands and the history so `init` starts from an empty scene.
+    pub fn cleanup(&mut self) {
+        self.world.clear_all();
+        self.commands.clear();
+        self.history.clear();
+        self.ui_handler = UIHandler::new();
+    }
 }
 
 #[async_trait(?Send)]
@@ -258,86 +356,126 @@ impl App for Scene {
     fn get_name(&self) -> String {
         "Scene".into()
     }
-    fn init(&mut self, elp: EventLoopProxy<CommandEvent>) {
+    fn init(&mut self, elp: EngineProxy) {
         self.proxy = Some(elp.clone());
+        self.handles = elp.handles().clone();
 
+        // The entities are spawned by the startup scripts (Ex: `DEFAULT_SCENE_SCRIPT`)
         self.ui_handler.init_ui(&mut self.world);
+    }
 
-        let load_missing_tex = Command::new(
-            "asset_server",
-            CommandType::Get,
-            Some("get textures/missing.jpg texture".into()),
-            None,
-        );
-
-        let load_font = Command::new(
-            "asset_server",
-            CommandType::Get,
-            Some("get fonts/OpenSans-Regular.ttf font".into()),
-            None,
-        );
-
-        let load_basic_cube = Command::new(
-            "default_scene",
-            CommandType::Get,
-            Some("add --name Cube --model models/test/duck.glb".into()),
-            None,
-        );
-
-        let load_camera_2d = Command::new(
-            "default_scene",
-            CommandType::Get,
-            Some("add --name Camera3D --camera 3D 1.8 45.0 0 0 4 0.0001 1000".into()),
-            None,
-        );
-
-        self.commands.append(&mut vec![
-            load_missing_tex,
-            load_basic_cube,
-            load_camera_2d,
-            load_font,
-        ]);
-    }
-
-    async fn process_command(&mut self, cmd: Command) {
-        self.process_scene_commands(cmd).await;
+    fn shutdown(&mut self) {
+        self.cleanup();
     }
 
-    fn update(&mut self, _delta_time: f32) -> Vec<Command> {
-        let scene_time = self.time.elapsed().as_secs_f32();
-        if scene_time > 0.01 {
-            self.time = web_time::Instant::now();
-            let text_changed = TextDesc {
-                changed: true,
-                text: format!("Camera speed: {}", self.cam_speed),
-                ..Default::default()
-            };
+    fn commands(&self) -> Vec<CommandDesc> {
+        vec![
+            CommandDesc::new("add", "Spawns an entity with the given components")
+                .arg(ArgDesc::option("name", ArgType::String, "Name component"))
+                .arg(ArgDesc::option(
+                    "model",
+                    ArgType::String,
+                    "Model component from the given model path",
+                ))
+                .arg(ArgDesc::option(
+                    "camera",
+                    ArgType::List,
+                    "Camera component: 2D <left> <right> <bottom> <top> <x> <y> <z> <znear> <zfar> or 3D <aspect> <fovy> <x> <y> <z> <znear> <zfar>",
+                )),
+            CommandDesc::new("remove", "Removes the entity with the given name").arg(
+                ArgDesc::positional("name", ArgType::String, "Entity name"),
+            ),
+            CommandDesc::new("set_model", "Sets the model of the named entity")
+                .arg(ArgDesc::positional(
+                    "model_path",
+                    ArgType::String,
+                    "Model path",
+                ))
+                .arg(ArgDesc::positional(
+                    "entity_name",
+                    ArgType::String,
+                    "Entity name",
+                )),
+            CommandDesc::new("undo", "Reverts the last scene commands").arg(
+                ArgDesc::positional("count", ArgType::UInt, "Number of commands").with_default("1"),
+            ),
+            CommandDesc::new("redo", "Applies the last undone scene commands again").arg(
+                ArgDesc::positional("count", ArgType::UInt, "Number of commands").with_default("1"),
+            ),
+            CommandDesc::new("history", "Lists the scene commands that can be undone and redone"),
+        ]
+    }
 
-            let ui_changed = UIComponent {
-                id: uuid::Uuid::new_v4(),
-                string_id: "cam-speed".to_string(),
-                parent_id: Some("test-child-1".to_string()),
-                ui_type: UIType::Text(text_changed),
-                visible: true,
-            };
+    async fn process_command(&mut self, cmd: Command, args: ParsedArgs) {
+        self.process_scene_commands(cmd, args).await;
+    }
 
-            let mut ui_changed_trans = TransformComponent::zero();
-            ui_changed_trans.scale.x += 25.0;
-            ui_changed_trans.scale.y += 25.0;
-            ui_changed_trans.translation.y = 20.0;
+    fn fixed_update(&mut self, fixed_delta: f32) -> Vec<Command> {
+        // Mouse motion gathered from the device events since the last tick
+        let mouse_delta_x = std::mem::take(&mut self.mouse_delta_x);
+        let mouse_delta_y = std::mem::take(&mut self.mouse_delta_y);
 
-            let task = Box::new(move || {
-                vec![CommandEvent::SignalChange(ChangeComponentState::UI((
-                    ui_changed.clone(),
-                    Some(ui_changed_trans),
-                )))]
-            });
+        if self.cam_should_move {
+            let cams = self.query_world::<With<ActiveCameraComponent>>();
 
-            let cmd = Command::new("sun", CommandType::Other, None, Some(task));
-            self.commands.push(cmd);
+            for cam in cams {
+                if let Some(mut cam_component) = self.world.get_mut::<CameraComponent>(cam) {
+                    cam_component.eye.z += mouse_delta_y * fixed_delta * self.cam_speed;
+                }
+            }
+        }
+
+        let objects = self.query_world::<With<TransformComponent>>();
+
+        for obj in objects {
+            if let Some(mut transform) = self.world.get_mut::<TransformComponent>(obj) {
+                if self.obj_should_rotate {
+                    let new_rot_y = cgmath::Quaternion::<f32>::from_angle_y(cgmath::Rad(
+                        mouse_delta_x * self.rotation_speed * fixed_delta,
+                    ));
+                    transform.rotation = transform.rotation * new_rot_y;
+                    transform.dirty = true;
+                }
+                transform.recalculate();
+            }
         }
 
-        if initialized() {
+        // Camera speed label
+        let text_changed = TextDesc {
+            changed: true,
+            text: format!("Camera speed: {}", self.cam_speed),
+            ..Default::default()
+        };
+
+        let ui_changed = UIComponent {
+            id: uuid::Uuid::new_v4(),
+            string_id: "cam-speed".to_string(),
+            parent_id: Some("test-child-1".to_string()),
+            ui_type: UIType::Text(text_changed),
+            visible: true,
+        };
+
+        let mut ui_changed_trans = TransformComponent::zero();
+        ui_changed_trans.scale.x += 25.0;
+        ui_changed_trans.scale.y += 25.0;
+        ui_changed_trans.translation.y = 20.0;
+
+        let task = Box::new(move || {
+            Ok(vec![CommandEvent::SignalChange(ChangeComponentState::UI((
+                ui_changed.clone(),
+                Some(ui_changed_trans),
+            )))]
+            .into())
+        });
+
+        let cmd = Command::new("sun", CommandType::Other, None, Some(task));
+        self.commands.push(cmd);
+
+        vec![]
+    }
+
+    fn update(&mut self, _delta_time: f32) -> Vec<Command> {
+        if self.initialized() {
             self.world.clear_trackers();
             self.commands.drain(..).collect()
         } else {
@@ -345,6 +483,10 @@ impl App for Scene {
         }
     }
 
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![EventKind::SignalChange]
+    }
+
     async fn process_user_event(
         &mut self,
         event: &crate::core::events::CommandEvent,
@@ -411,7 +553,7 @@ impl App for Scene {
                     .send_event(CommandEvent::RenderFrame(render_desc))
                     .unwrap();
 
-                if !state::initialized() {
+                if !self.initialized() {
                     return;
                 }
             }
@@ -436,7 +578,7 @@ impl App for Scene {
         &mut self,
         event: &winit::event::DeviceEvent,
         _device_id: winit::event::DeviceId,
-        delta_time: f32,
+        _delta_time: f32,
     ) {
         match event {
             winit::event::DeviceEvent::Button { button, state } => {
@@ -455,8 +597,8 @@ impl App for Scene {
                 }
             }
             winit::event::DeviceEvent::MouseMotion { delta } => {
-                self.mouse_delta_y = delta.1 as f32;
-                self.mouse_delta_x = delta.0 as f32;
+                self.mouse_delta_y += delta.1 as f32;
+                self.mouse_delta_x += delta.0 as f32;
             }
             winit::event::DeviceEvent::MouseWheel { delta } => match delta {
                 MouseScrollDelta::LineDelta(_x, y) => {
@@ -474,33 +616,6 @@ impl App for Scene {
             },
             _ => {}
         }
-
-        // TODO (@A40): Should be done in update or in a system
-        if self.cam_should_move {
-            let cams = self.query_world::<With<ActiveCameraComponent>>();
-
-            for cam in cams {
-                if let Some(mut cam_component) = self.world.get_mut::<CameraComponent>(cam) {
-                    cam_component.eye.z += self.mouse_delta_y * delta_time * self.cam_speed;
-                }
-            }
-        }
-
-        // TODO (@A40): Should be done in update or in a system
-        let objects = self.query_world::<With<TransformComponent>>();
-
-        for obj in objects {
-            if let Some(mut transform) = self.world.get_mut::<TransformComponent>(obj) {
-                if self.obj_should_rotate {
-                    let new_rot_y = cgmath::Quaternion::<f32>::from_angle_y(cgmath::Rad(
-                        self.mouse_delta_x * self.rotation_speed * delta_time,
-                    ));
-                    transform.rotation = transform.rotation * new_rot_y;
-                    transform.dirty = true;
-                }
-                transform.recalculate();
-            }
-        }
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -517,3 +632,40 @@ impl Drop for Scene {
         self.cleanup();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::command_registry::{CommandRegistry, ParsedCommand};
+
+    fn add(scene: &mut Scene, args: &str) -> CommandResult {
+        let registry = CommandRegistry::new("default_scene", scene.commands());
+        let Ok(ParsedCommand::Command(args)) = registry.parse(args) else {
+            panic!("invalid test command {args}");
+        };
+
+        let mut task = scene.add_entity_from_args(&args).unwrap();
+        task()
+    }
+
+    #[test]
+    fn invalid_cameras_spawn_nothing() {
+        let mut scene = Scene::default();
+
+        for camera in ["3D 1.8 45.0 0 0 4 0.0001", "3D 1.8 wide 0 0 4 0.0001 1000"] {
+            let result = add(&mut scene, &format!("add --name Camera --camera {camera}"));
+
+            assert!(matches!(result, Err(CommandFailure::Failed(_))));
+            assert!(scene.query_world::<()>().is_empty());
+            assert!(!scene.history.can_undo());
+        }
+
+        add(
+            &mut scene,
+            "add --name Camera --camera 3D 1.8 45.0 0 0 4 0.0001 1000",
+        )
+        .unwrap();
+        assert!(scene.get_entity_with_name("Camera").is_some());
+        assert!(scene.history.can_undo());
+    }
+}
@@ -0,0 +1,614 @@
+use std::collections::VecDeque;
+
+use bevy_ecs::{entity::Entity, world::World};
+
+use crate::{
+    core::events::CommandEvent,
+    ecs::{
+        camera_component::{ActiveCameraComponent, CameraComponent},
+        model_component::ModelComponent,
+        name_component::NameComponent,
+        transform_component::TransformComponent,
+    },
+};
+
+/// Number of changes a scene can undo when it does not say otherwise.
+pub const DEFAULT_SCENE_HISTORY: usize = 100;
+
+/// A component of a scene entity, as kept by the `SceneHistory`.
+#[derive(Debug, Clone)]
+pub enum SceneComponent {
+    Name(NameComponent),
+    Model(ModelComponent),
+    Transform(TransformComponent),
+    Camera(CameraComponent),
+    ActiveCamera,
+}
+
+/// Type of a `SceneComponent`, to read or remove it from an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentKind {
+    Name,
+    Model,
+    Transform,
+    Camera,
+    ActiveCamera,
+}
+
+impl ComponentKind {
+    pub const ALL: [ComponentKind; 5] = [
+        ComponentKind::Name,
+        ComponentKind::Model,
+        ComponentKind::Transform,
+        ComponentKind::Camera,
+        ComponentKind::ActiveCamera,
+    ];
+
+    /// The component of this type `entity` has, if any.
+    pub fn get(&self, world: &World, entity: Entity) -> Option<SceneComponent> {
+        let entity = world.get_entity(entity)?;
+
+        match self {
+            ComponentKind::Name => entity
+                .get::<NameComponent>()
+                .cloned()
+                .map(SceneComponent::Name),
+            ComponentKind::Model => entity
+                .get::<ModelComponent>()
+                .cloned()
+                .map(SceneComponent::Model),
+            ComponentKind::Transform => entity
+                .get::<TransformComponent>()
+                .copied()
+                .map(SceneComponent::Transform),
+            ComponentKind::Camera => entity
+                .get::<CameraComponent>()
+                .cloned()
+                .map(SceneComponent::Camera),
+            ComponentKind::ActiveCamera => entity
+                .contains::<ActiveCameraComponent>()
+                .then_some(SceneComponent::ActiveCamera),
+        }
+    }
+
+    fn remove(&self, world: &mut World, entity: Entity) {
+        let Some(mut entity) = world.get_entity_mut(entity) else {
+            return;
+        };
+
+        match self {
+            ComponentKind::Name => {
+                entity.remove::<NameComponent>();
+            }
+            ComponentKind::Model => {
+                entity.remove::<ModelComponent>();
+            }
+            ComponentKind::Transform => {
+                entity.remove::<TransformComponent>();
+            }
+            ComponentKind::Camera => {
+                entity.remove::<CameraComponent>();
+            }
+            ComponentKind::ActiveCamera => {
+                entity.remove::<ActiveCameraComponent>();
+            }
+        }
+    }
+}
+
+impl SceneComponent {
+    pub fn kind(&self) -> ComponentKind {
+        match self {
+            SceneComponent::Name(_) => ComponentKind::Name,
+            SceneComponent::Model(_) => ComponentKind::Model,
+            SceneComponent::Transform(_) => ComponentKind::Transform,
+            SceneComponent::Camera(_) => ComponentKind::Camera,
+            SceneComponent::ActiveCamera => ComponentKind::ActiveCamera,
+        }
+    }
+
+    /// Inserts the component into `entity`, replacing the one of the same type.
+    ///
+    /// # Returns
+    ///
+    /// The events the component needs once inserted (Ex: a model to be created by the renderer).
+    pub fn insert(&self, world: &mut World, entity: Entity) -> Vec<CommandEvent> {
+        let Some(mut entity) = world.get_entity_mut(entity) else {
+            return vec![];
+        };
+
+        match self {
+            SceneComponent::Name(name) => {
+                entity.insert(name.clone());
+            }
+            SceneComponent::Model(model) => {
+                entity.insert(model.clone());
+                return vec![CommandEvent::RequestCreateModel(model.clone())];
+            }
+            SceneComponent::Transform(transform) => {
+                entity.insert(*transform);
+            }
+            SceneComponent::Camera(camera) => {
+                entity.insert(camera.clone());
+            }
+            SceneComponent::ActiveCamera => {
+                entity.insert(ActiveCameraComponent {});
+            }
+        }
+
+        vec![]
+    }
+}
+
+/// Every `SceneComponent` of `entity`.
+pub fn snapshot(world: &World, entity: Entity) -> Vec<SceneComponent> {
+    ComponentKind::ALL
+        .iter()
+        .filter_map(|kind| kind.get(world, entity))
+        .collect()
+}
+
+/// A reversible mutation of a scene.
+#[derive(Debug, Clone)]
+pub enum SceneOp {
+    Spawn {
+        entity: Entity,
+        components: Vec<SceneComponent>,
+    },
+    Despawn {
+        entity: Entity,
+        components: Vec<SceneComponent>,
+    },
+    /// Inserts `component`, `replaced` is the component of the same type the entity had before.
+    Insert {
+        entity: Entity,
+        component: SceneComponent,
+        replaced: Option<SceneComponent>,
+    },
+    Remove {
+        entity: Entity,
+        component: SceneComponent,
+    },
+}
+
+impl SceneOp {
+    /// The operation undoing this one.
+    pub fn inverse(&self) -> SceneOp {
+        match self.clone() {
+            SceneOp::Spawn { entity, components } => SceneOp::Despawn { entity, components },
+            SceneOp::Despawn { entity, components } => SceneOp::Spawn { entity, components },
+            SceneOp::Insert {
+                entity,
+                component,
+                replaced: Some(replaced),
+            } => SceneOp::Insert {
+                entity,
+                component: replaced,
+                replaced: Some(component),
+            },
+            SceneOp::Insert {
+                entity,
+                component,
+                replaced: None,
+            } => SceneOp::Remove { entity, component },
+            SceneOp::Remove { entity, component } => SceneOp::Insert {
+                entity,
+                component,
+                replaced: None,
+            },
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            SceneOp::Spawn { entity, .. } => format!("spawn {entity:?}"),
+            SceneOp::Despawn { entity, .. } => format!("despawn {entity:?}"),
+            SceneOp::Insert {
+                entity, component, ..
+            } => format!("insert {:?} into {entity:?}", component.kind()),
+            SceneOp::Remove { entity, component } => {
+                format!("remove {:?} from {entity:?}", component.kind())
+            }
+        }
+    }
+
+    fn entity_mut(&mut self) -> &mut Entity {
+        match self {
+            SceneOp::Spawn { entity, .. }
+            | SceneOp::Despawn { entity, .. }
+            | SceneOp::Insert { entity, .. }
+            | SceneOp::Remove { entity, .. } => entity,
+        }
+    }
+
+    /// Applies the operation to `world`.
+    ///
+    /// # Returns
+    ///
+    /// The events of the inserted components, and for a spawn the entity it was spawned as
+    /// (a despawned entity never comes back with the same id).
+    fn apply(&self, world: &mut World) -> (Vec<CommandEvent>, Option<Entity>) {
+        match self {
+            SceneOp::Spawn { components, .. } => {
+                let (entity, events) = spawn(world, components);
+                (events, Some(entity))
+            }
+            SceneOp::Despawn { entity, .. } => {
+                world.despawn(*entity);
+                (vec![], None)
+            }
+            SceneOp::Insert {
+                entity, component, ..
+            } => (component.insert(world, *entity), None),
+            SceneOp::Remove { entity, component } => {
+                component.kind().remove(world, *entity);
+                (vec![], None)
+            }
+        }
+    }
+}
+
+/// Spawns an entity made of `components`.
+///
+/// # Returns
+///
+/// The spawned entity and the events its components need, see `SceneComponent::insert`.
+pub fn spawn(world: &mut World, components: &[SceneComponent]) -> (Entity, Vec<CommandEvent>) {
+    let entity = world.spawn_empty().id();
+    let events = components
+        .iter()
+        .flat_map(|component| component.insert(world, entity))
+        .collect();
+
+    (entity, events)
+}
+
+/// The operations of one scene command, undone and redone together.
+#[derive(Debug, Clone)]
+pub struct SceneChange {
+    /// The command that made the change (Ex: `add --name Cube`).
+    pub description: String,
+    pub ops: Vec<SceneOp>,
+}
+
+/// Undo and redo stacks of a scene's changes, holding at most `limit` changes to undo.
+///
+/// The operations of a command are grouped between `begin` and `commit`, recording a new change
+/// drops the changes that were undone.
+///
+/// # Example
+///
+/// ```ignore
+/// history.begin("add --name Cube");
+/// let (entity, _) = spawn(&mut world, &components);
+/// history.record(SceneOp::Spawn { entity, components });
+/// history.commit();
+///
+/// history.undo(&mut world);
+/// ```
+#[derive(Debug)]
+pub struct SceneHistory {
+    undo: VecDeque<SceneChange>,
+    redo: Vec<SceneChange>,
+    pending: Option<SceneChange>,
+    limit: usize,
+}
+
+impl Default for SceneHistory {
+    fn default() -> Self {
+        SceneHistory::new(DEFAULT_SCENE_HISTORY)
+    }
+}
+
+impl SceneHistory {
+    pub fn new(limit: usize) -> Self {
+        SceneHistory {
+            undo: VecDeque::new(),
+            redo: vec![],
+            pending: None,
+            limit,
+        }
+    }
+
+    /// Groups the operations recorded until `commit` into one change.
+    pub fn begin(&mut self, description: &str) {
+        self.commit();
+        self.pending = Some(SceneChange {
+            description: description.to_owned(),
+            ops: vec![],
+        });
+    }
+
+    /// Records an operation that was applied to the scene.
+    /// Outside of `begin` / `commit` it is a change of its own.
+    pub fn record(&mut self, op: SceneOp) {
+        match self.pending.as_mut() {
+            Some(change) => change.ops.push(op),
+            None => self.push(SceneChange {
+                description: op.describe(),
+                ops: vec![op],
+            }),
+        }
+    }
+
+    /// Ends the change started by `begin`, a change without operations is dropped.
+    pub fn commit(&mut self) {
+        if let Some(change) = self.pending.take() {
+            if !change.ops.is_empty() {
+                self.push(change);
+            }
+        }
+    }
+
+    fn push(&mut self, change: SceneChange) {
+        self.redo.clear();
+
+        if self.limit == 0 {
+            return;
+        }
+
+        if self.undo.len() >= self.limit {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(change);
+    }
+
+    /// Reverts the last change.
+    ///
+    /// # Returns
+    ///
+    /// The description of the reverted change and the events of the components it restored,
+    /// none if there is nothing to undo.
+    pub fn undo(&mut self, world: &mut World) -> Option<(String, Vec<CommandEvent>)> {
+        self.commit();
+        let mut change = self.undo.pop_back()?;
+
+        let events = self.apply(world, &mut change.ops, true);
+        let description = change.description.clone();
+        self.redo.push(change);
+
+        Some((description, events))
+    }
+
+    /// Applies the last undone change again.
+    ///
+    /// # Returns
+    ///
+    /// The description of the change and the events of the components it inserted,
+    /// none if there is nothing to redo.
+    pub fn redo(&mut self, world: &mut World) -> Option<(String, Vec<CommandEvent>)> {
+        self.commit();
+        let mut change = self.redo.pop()?;
+
+        let events = self.apply(world, &mut change.ops, false);
+        let description = change.description.clone();
+        self.undo.push_back(change);
+
+        Some((description, events))
+    }
+
+    /// Drops every change (Ex: the scene was reloaded).
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.pending = None;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// The changes that can be undone, most recent first, then the ones that can be redone.
+    pub fn describe(&self) -> String {
+        let mut lines = vec![format!(
+            "scene history ({} / {} changes):",
+            self.undo.len(),
+            self.limit
+        )];
+
+        lines.extend(
+            self.undo
+                .iter()
+                .rev()
+                .map(|change| format!("  undo: {}", change.description)),
+        );
+        lines.extend(
+            self.redo
+                .iter()
+                .rev()
+                .map(|change| format!("  redo: {}", change.description)),
+        );
+
+        lines.join("\n")
+    }
+
+    /// Applies `ops` in order, or their inverses in reverse order to revert them.
+    fn apply(&mut self, world: &mut World, ops: &mut [SceneOp], revert: bool) -> Vec<CommandEvent> {
+        let mut order: Vec<usize> = (0..ops.len()).collect();
+        if revert {
+            order.reverse();
+        }
+
+        let mut events = vec![];
+        for i in order {
+            let op = if revert {
+                ops[i].inverse()
+            } else {
+                ops[i].clone()
+            };
+            let (op_events, spawned) = op.apply(world);
+            events.extend(op_events);
+
+            if let (SceneOp::Spawn { entity, .. }, Some(spawned)) = (&op, spawned) {
+                self.remap(*entity, spawned);
+                remap_ops(ops, *entity, spawned);
+            }
+        }
+
+        events
+    }
+
+    /// Points the operations on `old` to `new`, a respawned entity.
+    fn remap(&mut self, old: Entity, new: Entity) {
+        let changes = self.undo.iter_mut().chain(self.redo.iter_mut());
+
+        for change in changes {
+            remap_ops(&mut change.ops, old, new);
+        }
+    }
+}
+
+fn remap_ops(ops: &mut [SceneOp], old: Entity, new: Entity) {
+    for op in ops {
+        let entity = op.entity_mut();
+        if *entity == old {
+            *entity = new;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> SceneComponent {
+        SceneComponent::Name(NameComponent {
+            name: name.to_owned(),
+        })
+    }
+
+    fn moved(x: f32) -> SceneComponent {
+        let mut transform = TransformComponent::zero();
+        transform.translation.x = x;
+        SceneComponent::Transform(transform)
+    }
+
+    /// Spawns a named entity at `x` as one change.
+    fn add(history: &mut SceneHistory, world: &mut World, entity: &str, x: f32) -> Entity {
+        let components = vec![name(entity), moved(x)];
+        history.begin(&format!("add --name {entity}"));
+        let (spawned, _) = spawn(world, &components);
+        history.record(SceneOp::Spawn {
+            entity: spawned,
+            components,
+        });
+        history.commit();
+        spawned
+    }
+
+    /// Moves `entity` to `x` as one change.
+    fn move_to(history: &mut SceneHistory, world: &mut World, entity: Entity, x: f32) {
+        let component = moved(x);
+        let replaced = ComponentKind::Transform.get(world, entity);
+        component.insert(world, entity);
+        history.record(SceneOp::Insert {
+            entity,
+            component,
+            replaced,
+        });
+    }
+
+    /// The name and x translation of every entity of the scene, sorted by name.
+    fn scene(world: &mut World) -> Vec<(String, f32)> {
+        let mut entities: Vec<(String, f32)> = world
+            .query::<(&NameComponent, &TransformComponent)>()
+            .iter(world)
+            .map(|(name, transform)| (name.name.clone(), transform.translation.x))
+            .collect();
+        entities.sort_by(|a, b| a.0.cmp(&b.0));
+        entities
+    }
+
+    fn undone(history: &mut SceneHistory, world: &mut World) -> Option<String> {
+        history.undo(world).map(|(description, _)| description)
+    }
+
+    fn redone(history: &mut SceneHistory, world: &mut World) -> Option<String> {
+        history.redo(world).map(|(description, _)| description)
+    }
+
+    #[test]
+    fn undo_then_redo_restores_the_same_scene() {
+        let mut world = World::new();
+        let mut history = SceneHistory::default();
+        let cube = add(&mut history, &mut world, "cube", 1.0);
+        move_to(&mut history, &mut world, cube, 2.0);
+        let changed = scene(&mut world);
+        assert_eq!(changed, [("cube".to_owned(), 2.0)]);
+
+        assert!(undone(&mut history, &mut world).is_some());
+        assert_eq!(scene(&mut world), [("cube".to_owned(), 1.0)]);
+        assert_eq!(
+            undone(&mut history, &mut world).as_deref(),
+            Some("add --name cube")
+        );
+        assert!(scene(&mut world).is_empty());
+
+        // The cube is spawned again as another entity, the move must follow it.
+        assert_eq!(
+            redone(&mut history, &mut world).as_deref(),
+            Some("add --name cube")
+        );
+        assert!(redone(&mut history, &mut world).is_some());
+        assert_eq!(scene(&mut world), changed);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn a_new_change_clears_the_redo_stack() {
+        let mut world = World::new();
+        let mut history = SceneHistory::default();
+        add(&mut history, &mut world, "cube", 0.0);
+        add(&mut history, &mut world, "sphere", 0.0);
+
+        undone(&mut history, &mut world);
+        assert!(history.can_redo());
+
+        add(&mut history, &mut world, "light", 0.0);
+        assert!(!history.can_redo());
+        assert_eq!(redone(&mut history, &mut world), None);
+        assert_eq!(
+            scene(&mut world),
+            [("cube".to_owned(), 0.0), ("light".to_owned(), 0.0)]
+        );
+    }
+
+    #[test]
+    fn keeps_at_most_limit_changes() {
+        let mut world = World::new();
+        let mut history = SceneHistory::new(2);
+        for entity in ["a", "b", "c"] {
+            add(&mut history, &mut world, entity, 0.0);
+        }
+
+        assert_eq!(
+            undone(&mut history, &mut world).as_deref(),
+            Some("add --name c")
+        );
+        assert_eq!(
+            undone(&mut history, &mut world).as_deref(),
+            Some("add --name b")
+        );
+        assert_eq!(undone(&mut history, &mut world), None);
+        assert_eq!(scene(&mut world), [("a".to_owned(), 0.0)]);
+    }
+
+    #[test]
+    fn undo_past_the_start_changes_nothing() {
+        let mut world = World::new();
+        let mut history = SceneHistory::default();
+        assert_eq!(undone(&mut history, &mut world), None);
+
+        add(&mut history, &mut world, "cube", 0.0);
+        undone(&mut history, &mut world);
+        assert_eq!(undone(&mut history, &mut world), None);
+        assert!(scene(&mut world).is_empty());
+
+        assert!(redone(&mut history, &mut world).is_some());
+        assert_eq!(scene(&mut world), [("cube".to_owned(), 0.0)]);
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use crate::{
+    assets::{
+        loader::{AssetLoader, LoadedValue},
+        Asset,
+    },
+    core::command_queue::AsyncTask,
+    renderer::resources::{font::SunFont, model::GlbModel},
+};
+
+/// The source of a wgsl shader, named after its file (Ex: `basic_shader.wgsl`) like the pipeline made from it.
+#[derive(Debug, Clone)]
+pub struct WgslShader {
+    pub name: String,
+    pub source: String,
+}
+
+pub struct WgslLoader;
+
+impl AssetLoader for WgslLoader {
+    fn asset_type(&self) -> &str {
+        "shader"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wgsl"]
+    }
+
+    fn load(&self, asset: Asset) -> AsyncTask<anyhow::Result<LoadedValue>> {
+        Box::pin(async move {
+            let shader = WgslShader {
+                source: String::from_utf8(asset.data)?,
+                name: asset.name,
+            };
+
+            Ok(Arc::new(shader) as LoadedValue)
+        })
+    }
+}
+
+/// Generates the glyph atlas of a font, the renderer uploads it.
+pub struct FontLoader;
+
+impl AssetLoader for FontLoader {
+    fn asset_type(&self) -> &str {
+        "font"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ttf", "otf"]
+    }
+
+    fn load(&self, asset: Asset) -> AsyncTask<anyhow::Result<LoadedValue>> {
+        Box::pin(async move {
+            let font = SunFont::from_font_bytes(&asset.name, &asset.data).await?;
+            Ok(Arc::new(font) as LoadedValue)
+        })
+    }
+}
+
+/// Parses glb models, the renderer uploads their meshes and materials.
+pub struct GlbLoader;
+
+impl AssetLoader for GlbLoader {
+    fn asset_type(&self) -> &str {
+        "model"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["glb"]
+    }
+
+    fn load(&self, asset: Asset) -> AsyncTask<anyhow::Result<LoadedValue>> {
+        Box::pin(async move { Ok(Arc::new(GlbModel::parse(&asset)?) as LoadedValue) })
+    }
+}
@@ -1,4 +1,5 @@
 pub mod buffer;
+pub mod loaders;
 pub mod pipeline;
 pub mod primitive;
 pub mod resources;
@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     collections::HashMap,
     io::{BufReader, Cursor},
     ops::Range,
@@ -30,13 +31,17 @@ pub struct SunModel {
     pub materials: HashMap<uuid::Uuid, SunMaterial>,
 }
 
-impl SunModel {
-    pub fn from_glb(
-        asset: &Asset,
-        bind_group_layout: &wgpu::BindGroupLayout,
-        queue: &wgpu::Queue,
-        device: &wgpu::Device,
-    ) -> anyhow::Result<Self> {
+/// A glb file parsed off the main thread (see `GlbLoader`), turned into a `SunModel` by the renderer.
+#[derive(Debug)]
+pub struct GlbModel {
+    pub name: String,
+    pub document: Document,
+    /// The binary section, holding the vertex and texture data.
+    pub bin: Vec<u8>,
+}
+
+impl GlbModel {
+    pub fn parse(asset: &Asset) -> anyhow::Result<Self> {
         let cursor = Cursor::new(&asset.data);
         let reader = BufReader::new(cursor);
 
@@ -48,7 +53,36 @@ impl SunModel {
         };
 
         let root = Root::from_slice(&glb_model.json)?;
-        let doc = Document::from_json(root)?;
+        let document = Document::from_json(root)?;
+
+        Ok(GlbModel {
+            name: asset.name.clone(),
+            document,
+            bin: bin.into_owned(),
+        })
+    }
+}
+
+impl SunModel {
+    pub fn from_glb(
+        asset: &Asset,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        queue: &wgpu::Queue,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        let glb = GlbModel::parse(asset)?;
+        SunModel::from_glb_model(&glb, bind_group_layout, queue, device)
+    }
+
+    /// Uploads the meshes and materials of a parsed glb file.
+    pub fn from_glb_model(
+        glb: &GlbModel,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        queue: &wgpu::Queue,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        let doc = &glb.document;
+        let bin = Cow::Borrowed(glb.bin.as_slice());
 
         let mut meshes: Vec<SunMesh> = Vec::new();
         let mut materials: HashMap<uuid::Uuid, SunMaterial> =
@@ -2,19 +2,25 @@ use std::{collections::HashMap, sync::Arc};
 
 use async_trait::async_trait;
 use tracing::{error, info};
-use winit::{event::WindowEvent, event_loop::EventLoopProxy, window::Window};
+use winit::{event::WindowEvent, window::Window};
 
 use crate::{
-    core::{app::App, command_queue::Command, events::CommandEvent},
+    assets::handle::{Assets, Handle},
+    core::{
+        app::App,
+        command_queue::Command,
+        command_registry::ParsedArgs,
+        events::{CommandEvent, EventKind},
+        proxy::EngineProxy,
+    },
     prelude::{
         camera_component::{CameraComponent, ModelUniform},
         command_queue::CommandType,
-        state,
         text_component::TextDesc,
         transform_component::TransformComponent,
         ui_component::{RenderUIDesc, UIComponent, UIType},
         window_component::WindowContainer,
-        Asset, AssetStatus, AssetType, ChangeComponentState,
+        ChangeComponentState,
     },
 };
 
@@ -23,11 +29,12 @@ pub type PrimitiveID = uuid::Uuid;
 
 use super::{
     buffer::SunBuffer,
+    loaders::WgslShader,
     pipeline::{PipelineDesc, SunPipeline},
     primitive::{Render2D, VertexExt},
     resources::{
         font::SunFont,
-        model::{DrawModel, RenderModelDesc, SunModel},
+        model::{DrawModel, GlbModel, RenderModelDesc, SunModel},
         texture::SunTexture,
     },
 };
@@ -47,13 +54,13 @@ pub struct Sun {
 
     pub viewports: HashMap<winit::window::WindowId, Viewport>,
     pub pipelines: HashMap<String, SunPipeline>,
-    pub shaders: HashMap<String, Asset>,
+    pub shaders: Assets<WgslShader>,
 
     pub mvp_buffer: Option<SunBuffer>,
     pub mvp_bindgroup: Option<wgpu::BindGroup>,
 
-    pub models: HashMap<String, SunModel>,
-    pub fonts: HashMap<String, SunFont>,
+    pub models: Assets<SunModel>,
+    pub fonts: Assets<SunFont>,
 
     pub quad_instance_buffer: Option<SunBuffer>,
 
@@ -63,7 +70,7 @@ pub struct Sun {
 
     commands: Vec<Command>,
 
-    pub proxy: Option<EventLoopProxy<CommandEvent>>,
+    pub proxy: Option<EngineProxy>,
 
     frame_time: web_time::Instant,
     time: web_time::Instant,
@@ -160,8 +167,8 @@ impl Sun {
 
         self.pipelines.insert(name, pipeline);
 
-        if !state::initialized() {
-            state::finish_init();
+        if !self.initialized() {
+            self.proxy.as_ref().unwrap().finish_init();
             info!("Initialized Render Engine!")
         }
     }
@@ -253,8 +260,13 @@ impl Sun {
         }
     }
 
+    /// Whether the engine finished initializing (set once the first pipeline is created).
+    pub fn initialized(&self) -> bool {
+        self.proxy.as_ref().is_some_and(|proxy| proxy.initialized())
+    }
+
     pub async fn redraw(&mut self, render_desc: RenderFrameDesc) {
-        if !state::initialized() {
+        if !self.initialized() {
             return;
         }
         self.regenerate_buffers(&render_desc).await;
@@ -338,7 +350,8 @@ impl Sun {
                             );
 
                             // Draw model through the active camera
-                            if let Some(model) = self.models.get(&model.model_path) {
+                            let handle = model.handle.as_ref();
+                            if let Some(model) = handle.and_then(|handle| self.models.get(handle)) {
                                 rpass.draw_model(&model, mvp_bg);
                             }
                         }
@@ -441,13 +454,13 @@ impl Default for Sun {
 
             viewports: HashMap::new(),
             pipelines: HashMap::new(),
-            shaders: HashMap::new(),
+            shaders: Assets::new(),
 
             mvp_buffer: None,
             mvp_bindgroup: None,
 
-            models: HashMap::new(),
-            fonts: HashMap::new(),
+            models: Assets::new(),
+            fonts: Assets::new(),
 
             quad_instance_buffer: None,
 
@@ -470,7 +483,16 @@ impl App for Sun {
     fn get_name(&self) -> String {
         "Renderer".into()
     }
-    fn init(&mut self, elp: EventLoopProxy<CommandEvent>) {
+
+    fn runs_after(&self) -> Vec<String> {
+        vec!["windower".into()]
+    }
+
+    // Render after every other app had the chance to update the frame
+    fn priority(&self) -> i32 {
+        10
+    }
+    fn init(&mut self, elp: EngineProxy) {
         self.proxy = Some(elp.clone());
 
         let load_basic_shader = Command::new(
@@ -483,7 +505,17 @@ impl App for Sun {
         self.commands.append(&mut vec![load_basic_shader]);
     }
 
-    async fn process_command(&mut self, _cmd: Command) {}
+    async fn process_command(&mut self, _cmd: Command, _args: ParsedArgs) {}
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![
+            EventKind::OnWindowCreated,
+            EventKind::OnWindowClosed,
+            EventKind::RenderFrame,
+            EventKind::RequestPipeline,
+            EventKind::LoadedAsset,
+        ]
+    }
 
     async fn process_user_event(
         &mut self,
@@ -519,107 +551,98 @@ impl App for Sun {
                 .await;
             }
 
-            CommandEvent::Asset(asset) => {
-                if asset.status == AssetStatus::NotFound {
-                    return;
-                }
-                match asset.asset_type {
-                    AssetType::Font => {
-                        let font = SunFont::from_font_bytes(&asset.name, &asset.data).await;
-                        match font {
-                            Ok(font) => {
-                                info!("Successfully created font: {}", font.font_file);
-
-                                let font_copy = font.clone();
-                                let task = Box::new(move || {
-                                    vec![CommandEvent::SignalChange(
-                                        ChangeComponentState::FontAtlas(font_copy.clone()),
-                                    )]
-                                });
-
-                                let cmd = Command::new("sun", CommandType::Other, None, Some(task));
-                                self.commands.push(cmd);
-
-                                let Ok(font_atlas_texture) = SunTexture::from_image(
-                                    &font.font_file,
-                                    self.device.as_ref().unwrap(),
-                                    self.queue.as_ref().unwrap(),
-                                    font.atlas.image.clone(),
-                                ) else {
-                                    error!(
-                                        "Failed to create texture from font image: {}",
-                                        font.font_file
-                                    );
-                                    return;
-                                };
-
-                                let font_atlas_texture_bg_layout = &self
-                                    .pipelines
-                                    .get("text_shader.wgsl")
-                                    .unwrap()
-                                    .bind_group_layouts[0];
-
-                                let font_bind_group =
-                                    self.device.as_ref().unwrap().create_bind_group(
-                                        &wgpu::BindGroupDescriptor {
-                                            label: None,
-                                            layout: font_atlas_texture_bg_layout,
-                                            entries: &[
-                                                wgpu::BindGroupEntry {
-                                                    binding: 0,
-                                                    resource: wgpu::BindingResource::TextureView(
-                                                        &font_atlas_texture.view,
-                                                    ),
-                                                },
-                                                wgpu::BindGroupEntry {
-                                                    binding: 1,
-                                                    resource: wgpu::BindingResource::Sampler(
-                                                        &font_atlas_texture.sampler,
-                                                    ),
-                                                },
-                                            ],
-                                        },
-                                    );
-                                self.bind_groups
-                                    .insert(font.font_file.clone(), font_bind_group);
-                                self.fonts.insert(font.font_file.clone(), font);
-                            }
-                            Err(err) => {
-                                error!("{err}");
-                            }
-                        }
-                    }
-                    AssetType::Shader => {
-                        self.shaders.insert(asset.name.clone(), asset.clone());
-                    }
-                    AssetType::Model => {
-                        let diffuse_texture_bg_layout = &self
-                            .pipelines
-                            .get("basic_shader.wgsl")
-                            .unwrap()
-                            .bind_group_layouts[1];
+            CommandEvent::LoadedAsset(loaded) => {
+                // Decoded off the main thread by the asset server's loaders, only the GPU copies are made here
+                if let Some(font) = loaded.downcast_ref::<SunFont>() {
+                    let font = font.clone();
+                    info!("Successfully created font: {}", font.font_file);
 
-                        let model = SunModel::from_glb(
-                            asset,
-                            &diffuse_texture_bg_layout,
-                            self.queue.as_ref().unwrap(),
-                            self.device.as_ref().unwrap(),
+                    let font_copy = font.clone();
+                    let task = Box::new(move || {
+                        Ok(
+                            vec![CommandEvent::SignalChange(ChangeComponentState::FontAtlas(
+                                font_copy.clone(),
+                            ))]
+                            .into(),
+                        )
+                    });
+
+                    let cmd = Command::new("sun", CommandType::Other, None, Some(task));
+                    self.commands.push(cmd);
+
+                    let Ok(font_atlas_texture) = SunTexture::from_image(
+                        &font.font_file,
+                        self.device.as_ref().unwrap(),
+                        self.queue.as_ref().unwrap(),
+                        font.atlas.image.clone(),
+                    ) else {
+                        error!(
+                            "Failed to create texture from font image: {}",
+                            font.font_file
                         );
+                        return;
+                    };
 
-                        match model {
-                            Ok(model) => {
-                                info!("Created Model: {:?}", asset.path);
-                                self.models.insert(asset.path.clone(), model);
-                            }
-                            Err(err) => {
-                                error!(
-                                    "Failed to create model from Glb: {} with error: {}",
-                                    asset.path, err
-                                );
-                            }
+                    let font_atlas_texture_bg_layout = &self
+                        .pipelines
+                        .get("text_shader.wgsl")
+                        .unwrap()
+                        .bind_group_layouts[0];
+
+                    let font_bind_group = self.device.as_ref().unwrap().create_bind_group(
+                        &wgpu::BindGroupDescriptor {
+                            label: None,
+                            layout: font_atlas_texture_bg_layout,
+                            entries: &[
+                                wgpu::BindGroupEntry {
+                                    binding: 0,
+                                    resource: wgpu::BindingResource::TextureView(
+                                        &font_atlas_texture.view,
+                                    ),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 1,
+                                    resource: wgpu::BindingResource::Sampler(
+                                        &font_atlas_texture.sampler,
+                                    ),
+                                },
+                            ],
+                        },
+                    );
+                    self.bind_groups
+                        .insert(font.font_file.clone(), font_bind_group);
+                    self.fonts.insert(&loaded.handle.cast(), font);
+                } else if let Some(shader) = loaded.downcast_ref::<WgslShader>() {
+                    let handle = loaded.handle.cast();
+                    if insert_shader(&mut self.shaders, &mut self.pipelines, &handle, shader) {
+                        info!("Reloaded shader {}", shader.name);
+                    }
+                } else if let Some(glb) = loaded.downcast_ref::<GlbModel>() {
+                    let diffuse_texture_bg_layout = &self
+                        .pipelines
+                        .get("basic_shader.wgsl")
+                        .unwrap()
+                        .bind_group_layouts[1];
+
+                    let model = SunModel::from_glb_model(
+                        glb,
+                        diffuse_texture_bg_layout,
+                        self.queue.as_ref().unwrap(),
+                        self.device.as_ref().unwrap(),
+                    );
+
+                    match model {
+                        Ok(model) => {
+                            info!("Created Model: {:?}", loaded.path);
+                            self.models.insert(&loaded.handle.cast(), model);
+                        }
+                        Err(err) => {
+                            error!(
+                                "Failed to create model from Glb: {} with error: {}",
+                                loaded.path, err
+                            );
                         }
                     }
-                    _ => {}
                 }
             }
             _ => {}
@@ -652,9 +675,12 @@ impl App for Sun {
                     let width = new_size.width as f32;
                     let height = new_size.height as f32;
                     let task = Box::new(move || {
-                        vec![CommandEvent::SignalChange(ChangeComponentState::Window(
-                            WindowContainer { width, height },
-                        ))]
+                        Ok(
+                            vec![CommandEvent::SignalChange(ChangeComponentState::Window(
+                                WindowContainer { width, height },
+                            ))]
+                            .into(),
+                        )
                     });
 
                     let cmd = Command::new("sun", CommandType::Other, None, Some(task));
@@ -666,7 +692,8 @@ impl App for Sun {
             }
 
             WindowEvent::RedrawRequested => {
-                for (name, shader) in &self.shaders {
+                for (_, shader) in self.shaders.iter() {
+                    let name = &shader.name;
                     // Create model and text pipeline from a received shader
                     if !self.pipelines.contains_key(name) {
                         let basic_tex_bg_layout_desc = wgpu::BindGroupLayoutDescriptor {
@@ -742,7 +769,7 @@ impl App for Sun {
                         let pipe_desc = PipelineDesc {
                             name: name.clone(),
                             win_id: window_id,
-                            shader_src: String::from_utf8(shader.data.clone()).unwrap(),
+                            shader_src: shader.source.clone(),
                             vertex_entry_fn_name: "vs_main".to_string(),
                             fragment_entry_fn_name: "fs_main".to_string(),
                             vertex_buffer_layouts: vec![super::primitive::ModelVertex::desc()],
@@ -765,7 +792,7 @@ impl App for Sun {
                         let pipe_desc = PipelineDesc {
                             name: "text_shader.wgsl".to_string(),
                             win_id: window_id,
-                            shader_src: String::from_utf8(shader.data.clone()).unwrap(),
+                            shader_src: shader.source.clone(),
                             vertex_entry_fn_name: "vs_text".to_string(),
                             fragment_entry_fn_name: "fs_text".to_string(),
                             vertex_buffer_layouts: vec![super::primitive::Quad2DVertex::desc()],
@@ -785,7 +812,7 @@ impl App for Sun {
                         let pipe_desc = PipelineDesc {
                             name: "quad_shader.wgsl".to_string(),
                             win_id: window_id,
-                            shader_src: String::from_utf8(shader.data.clone()).unwrap(),
+                            shader_src: shader.source.clone(),
                             vertex_entry_fn_name: "vs_quad".to_string(),
                             fragment_entry_fn_name: "fs_colored_quad".to_string(),
                             vertex_buffer_layouts: vec![super::primitive::Quad2DVertex::desc()],
@@ -808,6 +835,16 @@ impl App for Sun {
     }
 
     fn update(&mut self, _delta_time: f32) -> Vec<Command> {
+        // The GPU copies are freed with the last handle to their asset
+        for (path, _) in self.models.free_unused() {
+            info!("Released model {path}");
+        }
+        for (path, font) in self.fonts.free_unused() {
+            info!("Released font {path}");
+            self.bind_groups.remove(&font.font_file);
+        }
+        self.shaders.free_unused();
+
         let renderer_time = self.time.elapsed().as_secs_f32();
 
         let frame_time = self.frame_time.elapsed().as_nanos() as f32 / 1_000_000.0;
@@ -835,10 +872,11 @@ impl App for Sun {
             ui_changed_trans.translation.y = 20.0;
 
             let task = Box::new(move || {
-                vec![CommandEvent::SignalChange(ChangeComponentState::UI((
+                Ok(vec![CommandEvent::SignalChange(ChangeComponentState::UI((
                     ui_changed.clone(),
                     Some(ui_changed_trans),
                 )))]
+                .into())
             });
 
             let cmd = Command::new("sun", CommandType::Other, None, Some(task));
@@ -943,3 +981,59 @@ impl Viewport {
         &self.desc
     }
 }
+
+/// Stores a received shader. A reloaded shader drops the pipeline built from its previous version,
+/// rebuilt from the new one on the next redraw (see `WindowEvent::RedrawRequested`).
+///
+/// # Returns
+///
+/// Whether a pipeline was dropped.
+fn insert_shader<P>(
+    shaders: &mut Assets<WgslShader>,
+    pipelines: &mut HashMap<String, P>,
+    handle: &Handle<WgslShader>,
+    shader: &WgslShader,
+) -> bool {
+    let Some(previous) = shaders.insert(handle, shader.clone()) else {
+        return false;
+    };
+
+    let dropped = pipelines.remove(&previous.name).is_some();
+    pipelines.remove(&shader.name).is_some() || dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::handle::HandleRegistry;
+
+    #[test]
+    fn reloaded_shaders_invalidate_their_pipeline() {
+        let handles = HandleRegistry::default();
+        let handle = handles.handle("shaders/basic_shader.wgsl");
+        let mut shaders = Assets::new();
+        let mut pipelines = HashMap::from([("basic_shader.wgsl".to_owned(), ())]);
+
+        let shader = |source: &str| WgslShader {
+            name: "basic_shader.wgsl".into(),
+            source: source.into(),
+        };
+
+        assert!(!insert_shader(
+            &mut shaders,
+            &mut pipelines,
+            &handle,
+            &shader("v1")
+        ));
+        assert!(pipelines.contains_key("basic_shader.wgsl"));
+
+        assert!(insert_shader(
+            &mut shaders,
+            &mut pipelines,
+            &handle,
+            &shader("v2")
+        ));
+        assert!(pipelines.is_empty());
+        assert_eq!(shaders.get(&handle).unwrap().source, "v2");
+    }
+}
@@ -1,14 +1,18 @@
 use std::{collections::HashMap, sync::Arc};
 
 use async_trait::async_trait;
-use tracing::{debug, error, info};
-use winit::{dpi::PhysicalSize, event_loop::EventLoopProxy};
+use tracing::debug;
+use winit::dpi::PhysicalSize;
 
 use crate::{
     core::{
         app::App,
         command_queue::{Command, Task},
-        events::CommandEvent,
+        command_registry::{ArgDesc, ArgType, CommandDesc, ParsedArgs},
+        command_result::{CommandFailure, CommandResult},
+        config::{EngineConfig, WindowConfig},
+        events::{CommandEvent, EventKind},
+        proxy::EngineProxy,
     },
     prelude::{
         command_queue::CommandType, window_component::WindowContainer, ChangeComponentState,
@@ -28,74 +32,72 @@ pub struct WinID {
     pub id: winit::window::WindowId,
 }
 
-#[derive(Default)]
 pub struct Windower {
     pub windows: HashMap<winit::window::WindowId, Arc<winit::window::Window>>,
     pub window_names: HashMap<winit::window::WindowId, String>,
     pub commands: Vec<Command>,
 
-    pub proxy: Option<EventLoopProxy<CommandEvent>>,
+    pub proxy: Option<EngineProxy>,
+
+    /// Opened on init, see `EngineConfig::windows`.
+    pub initial_windows: Vec<WindowConfig>,
+}
+
+impl Default for Windower {
+    fn default() -> Self {
+        Windower::new(EngineConfig::default().windows)
+    }
 }
 
 impl Windower {
-    pub fn process_window_command(&mut self, mut cmd: Command) {
-        let args = cmd.args.clone().unwrap();
-        let vec_args: Vec<&str> = args.split(' ').collect();
-
-        let task = match vec_args[0].to_ascii_lowercase().as_str() {
-            "open" => self.open(vec_args[1..].join(" ")),
-            "close" => self.close(vec_args[1..].join(" ")),
-            "help" => self.help(),
-            _ => Windower::unsupported(args.as_str()),
+    pub fn new(initial_windows: Vec<WindowConfig>) -> Self {
+        Windower {
+            windows: HashMap::new(),
+            window_names: HashMap::new(),
+            commands: vec![],
+            proxy: None,
+            initial_windows,
+        }
+    }
+
+    pub fn process_window_command(&mut self, mut cmd: Command, args: ParsedArgs) {
+        let task = match args.command() {
+            "open" => self.open(
+                args.get_str("name").unwrap().to_owned(),
+                args.get_u32("width").unwrap(),
+                args.get_u32("height").unwrap(),
+            ),
+            "close" => self.close(args.get_str("name").unwrap()),
+            _ => Windower::unsupported(cmd.args.as_deref().unwrap_or_default()),
         };
 
         cmd.task = task;
-        cmd.args = Some(args);
         cmd.processed = true;
 
         self.commands.push(cmd);
     }
 
-    pub fn open(&self, args: String) -> Option<Task<Vec<CommandEvent>>> {
-        let args_vec: Vec<&str> = args.split(' ').collect();
-        if args_vec.len() != 3 {
-            error!("Windower <open> takes 3 arguments!");
-            error!("args: {:?}", args_vec);
-            return None;
-        }
-
+    pub fn open(&self, name: String, width: u32, height: u32) -> Option<Task<CommandResult>> {
         let cmd = move || {
-            let args_vec: Vec<&str> = args.split(' ').collect();
             let event = CommandEvent::RequestNewWindow(NewWindowProps {
-                name: args_vec[0].to_string(),
-                size: PhysicalSize {
-                    width: args_vec[1].parse::<u32>().unwrap_or(1024),
-                    height: args_vec[2].parse::<u32>().unwrap_or(1024),
-                },
-                element_id: args_vec[0].to_string(),
+                name: name.clone(),
+                size: PhysicalSize { width, height },
+                element_id: name.clone(),
             });
 
             let event1 =
                 CommandEvent::SignalChange(ChangeComponentState::Window(WindowContainer {
-                    width: args_vec[1].parse::<u32>().unwrap_or(1024) as f32,
-                    height: args_vec[2].parse::<u32>().unwrap_or(1024) as f32,
+                    width: width as f32,
+                    height: height as f32,
                 }));
 
-            vec![event, event1]
+            Ok(vec![event, event1].into())
         };
 
         Some(Box::new(cmd))
     }
 
-    pub fn close(&mut self, args: String) -> Option<Task<Vec<CommandEvent>>> {
-        let args: Vec<&str> = args.split(' ').collect();
-        let name = args[0].to_owned();
-        if args.len() != 1 {
-            error!("Windower <close> takes 1 argument!");
-            error!("args: {:?}", args);
-            return None;
-        }
-
+    pub fn close(&mut self, name: &str) -> Option<Task<CommandResult>> {
         let mut windows: Vec<winit::window::WindowId> = vec![];
 
         for (id, window_name) in &self.window_names {
@@ -107,26 +109,15 @@ impl Windower {
         let mut events: Vec<CommandEvent> = vec![];
 
         for window in &windows {
-            events.push(CommandEvent::OnWindowClosed((*window, name.clone())));
+            events.push(CommandEvent::OnWindowClosed((*window, name.to_owned())));
         }
 
         if !events.is_empty() {
-            return Some(Box::new(move || events.clone()));
+            return Some(Box::new(move || Ok(events.clone().into())));
         }
 
-        if windows.is_empty() {
-            error!("Window <{}> not found!", args[0]);
-        }
-        None
-    }
-
-    pub fn help(&self) -> Option<Task<Vec<CommandEvent>>> {
-        info!(
-            "open <Name> <Width> <Height> -> Opens a window with the specified name and dimensions"
-        );
-        info!("close <Name> -> closes the specified window");
-
-        None
+        let failure = CommandFailure::Failed(format!("Window <{}> not found!", name));
+        Some(Box::new(move || Err(failure.clone())))
     }
 
     pub fn create_window(&mut self, props: NewWindowProps, window: winit::window::Window) {
@@ -156,20 +147,30 @@ impl App for Windower {
     fn get_name(&self) -> String {
         "Windower".into()
     }
-    fn init(&mut self, elp: EventLoopProxy<CommandEvent>) {
-        self.proxy = Some(elp.clone());
-
-        let task = self.open("Sandbox 1920 1080".into());
 
-        let cmd = Command {
-            processed: true,
-            app: "Windower".into(),
-            args: None,
-            command_type: CommandType::Open,
-            task,
-        };
+    // Windows have to exist before any other app gets to use them
+    fn priority(&self) -> i32 {
+        -10
+    }
+    fn init(&mut self, elp: EngineProxy) {
+        self.proxy = Some(elp.clone());
 
-        self.commands.push(cmd);
+        for window in self.initial_windows.clone() {
+            let task = self.open(window.name, window.width, window.height);
+
+            let cmd = Command {
+                processed: true,
+                app: "Windower".into(),
+                args: None,
+                command_type: CommandType::Open,
+                task,
+                future: None,
+                timeout: None,
+                reply_to: None,
+            };
+
+            self.commands.push(cmd);
+        }
     }
 
     fn update(&mut self, _delta_time: f32) -> Vec<Command> {
@@ -226,8 +227,25 @@ impl App for Windower {
         self.commands.drain(..).collect()
     }
 
-    async fn process_command(&mut self, cmd: Command) {
-        self.process_window_command(cmd);
+    fn commands(&self) -> Vec<CommandDesc> {
+        vec![
+            CommandDesc::new(
+                "open",
+                "Opens a window with the specified name and dimensions",
+            )
+            .arg(ArgDesc::positional("name", ArgType::String, "Window name"))
+            .arg(ArgDesc::positional("width", ArgType::U32, "Window width").with_default("1024"))
+            .arg(ArgDesc::positional("height", ArgType::U32, "Window height").with_default("1024")),
+            CommandDesc::new("close", "Closes the specified window").arg(ArgDesc::positional(
+                "name",
+                ArgType::String,
+                "Window name",
+            )),
+        ]
+    }
+
+    async fn process_command(&mut self, cmd: Command, args: ParsedArgs) {
+        self.process_window_command(cmd, args);
     }
 
     async fn process_window_event(
@@ -242,6 +260,10 @@ impl App for Windower {
         }
     }
 
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![EventKind::OnWindowClosed]
+    }
+
     async fn process_user_event(&mut self, event: &CommandEvent, _delta_time: f32) {
         if let CommandEvent::OnWindowClosed((id, _)) = event {
             self.windows.remove(id);